@@ -13,8 +13,11 @@
 //! KeyCode supports a subset of primitive data types, encoded as follows:
 //!
 //! bool:    0x00 for false, 0x01 for true.
+//! u8:      The byte itself.
 //! u64:     Big-endian binary representation.
+//! i32:     Big-endian binary representation, with sign bit flipped.
 //! i64:     Big-endian binary representation, with sign bit flipped.
+//! i128:    Big-endian binary representation, with sign bit flipped.
 //! f64:     Big-endian binary representation, with sign bit flipped, and rest
 //! if negative. Vec<u8>: 0x00 is escaped as 0x00ff, terminated with 0x0000.
 //! String:  Like Vec<u8>.
@@ -22,6 +25,7 @@
 //! Additionally, several container types are supported:
 //!
 //! Tuple:  Concatenation of elements, with no surrounding structure.
+//! Struct: Like tuple, in field order.
 //! Array:  Like tuple.
 //! Vec:    Like tuple.
 //! Enum:   The variant's enum index as a single u8 byte.
@@ -107,7 +111,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     type SerializeTupleVariant = Self;
     type SerializeTupleStruct = ser::Impossible<(), Error>;
     type SerializeMap = ser::Impossible<(), Error>;
-    type SerializeStruct = ser::Impossible<(), Error>;
+    type SerializeStruct = Self;
     type SerializeStructVariant = ser::Impossible<(), Error>;
 
     /// bool simply uses 1 for true and 0 for false.
@@ -124,8 +128,13 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         unimplemented!()
     }
 
-    fn serialize_i32(self, _: i32) -> Result<()> {
-        unimplemented!()
+    /// i32 uses the big-endian two's complement encoding with the sign bit
+    /// flipped, like i64.
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        let mut bytes = v.to_be_bytes();
+        bytes[0] ^= 1 << 7; // flip sign bit
+        self.output.extend(bytes);
+        Ok(())
     }
 
     /// i64 uses the big-endian two's completement encoding, but flips the
@@ -142,8 +151,19 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
-    fn serialize_u8(self, _: u8) -> Result<()> {
-        unimplemented!()
+    /// i128 uses the big-endian two's complement encoding with the sign bit
+    /// flipped, like i64.
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        let mut bytes = v.to_be_bytes();
+        bytes[0] ^= 1 << 7; // flip sign bit
+        self.output.extend(bytes);
+        Ok(())
+    }
+
+    /// u8 simply uses the byte itself.
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.output.push(v);
+        Ok(())
     }
 
     fn serialize_u16(self, _: u16) -> Result<()> {
@@ -280,8 +300,9 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         unimplemented!()
     }
 
+    /// Structs are serialized like tuples, with fields in declaration order.
     fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct> {
-        unimplemented!()
+        Ok(self)
     }
 
     fn serialize_struct_variant(
@@ -338,6 +359,24 @@ impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
     }
 }
 
+/// Structs, like tuples, simply concatenate the serialized fields.
+impl ser::SerializeStruct for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
 /// Deserializes keys from byte slices into a given type. The format is not
 /// self-describing, so the caller must provide a concrete type to deserialize
 /// into.
@@ -412,8 +451,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         unimplemented!()
     }
 
-    fn deserialize_i32<V: de::Visitor<'de>>(self, _: V) -> Result<V::Value> {
-        unimplemented!()
+    fn deserialize_i32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut bytes = self.take_bytes(4)?.to_vec();
+        bytes[0] ^= 1 << 7; // flip sign bit
+        visitor.visit_i32(i32::from_be_bytes(bytes.as_slice().try_into()?))
     }
 
     fn deserialize_i64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
@@ -422,8 +463,14 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_i64(i64::from_be_bytes(bytes.as_slice().try_into()?))
     }
 
-    fn deserialize_u8<V: de::Visitor<'de>>(self, _: V) -> Result<V::Value> {
-        unimplemented!()
+    fn deserialize_i128<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut bytes = self.take_bytes(16)?.to_vec();
+        bytes[0] ^= 1 << 7; // flip sign bit
+        visitor.visit_i128(i128::from_be_bytes(bytes.as_slice().try_into()?))
+    }
+
+    fn deserialize_u8<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(self.take_bytes(1)?[0])
     }
 
     fn deserialize_u16<V: de::Visitor<'de>>(self, _: V) -> Result<V::Value> {
@@ -525,9 +572,9 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self,
         _: &'static str,
         _: &'static [&'static str],
-        _: V,
+        visitor: V,
     ) -> Result<V::Value> {
-        unimplemented!()
+        visitor.visit_seq(self)
     }
 
     fn deserialize_enum<V: de::Visitor<'de>>(
@@ -1,9 +1,12 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use super::session::Session;
 use crate::{
     errinput,
     error::Result,
     types::{
         expression::Expression,
+        function::FunctionRegistry,
         schema::Table,
         value::{Row, Rows, Value},
     },
@@ -24,17 +27,33 @@ pub trait Transaction {
     fn commit(self) -> Result<()>;
     fn rollback(self) -> Result<()>;
     fn insert(&self, table: &str, rows: Vec<Row>) -> Result<()>;
+    /// Updates rows by primary key. The primary key may change.
+    fn update(&self, table: &str, rows: BTreeMap<Value, Row>) -> Result<()>;
+    /// Deletes rows by primary key, ignoring missing keys.
+    fn delete(&self, table: &str, ids: &[Value]) -> Result<()>;
     fn get(&self, table: &str, ids: &[Value]) -> Result<Vec<Row>>;
+    /// Looks up the primary keys of rows with the given secondary index values.
+    fn lookup_index(&self, table: &str, column: &str, values: &[Value]) -> Result<BTreeSet<Value>>;
     fn scan(&self, table: &str, filter: Option<Expression>) -> Result<Rows>;
 }
 
 pub trait Catalog {
     fn create_table(&self, table: Table) -> Result<()>;
+    /// Drops a table and all of its rows and indexes. Returns false if the
+    /// table did not exist and if_exists is set, otherwise errors.
+    fn drop_table(&self, table: &str, if_exists: bool) -> Result<bool>;
     fn get_table(&self, table: &str) -> Result<Option<Table>>;
     fn list_tables(&self) -> Result<Vec<Table>>;
+    /// Creates a secondary index on the given column, indexing existing rows.
+    fn create_index(&self, table: &str, column: &str) -> Result<()>;
 
     fn must_get_table(&self, table: &str) -> Result<Table> {
         self.get_table(table)?
             .ok_or_else(|| errinput!("table {table} does not exist"))
     }
+
+    /// Returns the user-defined functions, if any.
+    fn functions(&self) -> FunctionRegistry {
+        FunctionRegistry::default()
+    }
 }
@@ -1,11 +1,15 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
+use itertools::Itertools as _;
 use serde::{Deserialize, Serialize};
 
 use super::Catalog;
 use crate::{
     encoding::{self, Key as _, Value as _},
-    errinput,
+    errdata, errinput,
     error::Result,
     storage::{
         self,
@@ -13,37 +17,59 @@ use crate::{
     },
     types::{
         expression::Expression,
-        schema::Table,
+        function::FunctionRegistry,
+        schema::{DataType, Table},
         value::{Row, Rows, Value},
     },
 };
 
 pub struct Local<E: storage::Engine + 'static> {
     pub mvcc: storage::MVCC<E>,
+    functions: FunctionRegistry,
 }
 
 impl<E: storage::Engine> Local<E> {
     pub fn new(engine: E) -> Self {
         Self {
             mvcc: storage::MVCC::new(engine),
+            functions: FunctionRegistry::default(),
         }
     }
+
+    /// Registers a user-defined scalar function, callable from SQL as
+    /// name(args). See FunctionRegistry::register().
+    pub fn register_function(
+        &mut self,
+        name: &str,
+        arity: usize,
+        return_type: DataType,
+        function: impl Fn(&[Value]) -> Result<Value> + Send + Sync + 'static,
+    ) -> Result<()> {
+        self.functions.register(name, arity, return_type, function)
+    }
 }
 
 impl<'a, E: storage::Engine> super::Engine<'a> for Local<E> {
     type Transaction = Transaction<E>;
 
     fn begin(&'a self) -> Result<Self::Transaction> {
-        Ok(Self::Transaction::new(self.mvcc.begin()?))
+        Ok(Self::Transaction::new(
+            self.mvcc.begin()?,
+            self.functions.clone(),
+        ))
     }
 
     fn begin_read_only(&'a self) -> Result<Self::Transaction> {
-        Ok(Self::Transaction::new(self.mvcc.begin_read_only()?))
+        Ok(Self::Transaction::new(
+            self.mvcc.begin_read_only()?,
+            self.functions.clone(),
+        ))
     }
 }
 
 pub struct Transaction<E: storage::Engine + 'static> {
     txn: mvcc::TransactionInner<E>,
+    functions: FunctionRegistry,
 }
 
 impl<E: storage::Engine> Catalog for Transaction<E> {
@@ -52,10 +78,47 @@ impl<E: storage::Engine> Catalog for Transaction<E> {
         if self.get_table(&table.name)?.is_some() {
             return errinput!("table {} already exists", table.name);
         }
+        table.validate(self)?;
         self.txn
             .set(&Key::Table((&table.name).into()).encode(), table.encode())
     }
 
+    fn drop_table(&self, table_name: &str, if_exists: bool) -> Result<bool> {
+        let table = match self.get_table(table_name)? {
+            Some(table) => table,
+            None if if_exists => return Ok(false),
+            None => return errinput!("table {table_name} does not exist"),
+        };
+        if let Some((source, refs)) = self.table_references(&table.name, false)?.first() {
+            return errinput!(
+                "table {} is referenced from {}.{}",
+                table.name,
+                source.name,
+                source.columns[refs[0]].name
+            );
+        }
+
+        // Delete the table's rows and index entries, collecting the keys
+        // before deleting them to avoid writing while scanning.
+        let mut prefixes = vec![KeyPrefix::Row((&table.name).into()).encode()];
+        for column in table.columns.iter().filter(|c| c.index) {
+            prefixes.push(KeyPrefix::Index((&table.name).into(), (&column.name).into()).encode());
+        }
+        for prefix in prefixes {
+            let keys: Vec<_> = self
+                .txn
+                .scan_prefix(&prefix)
+                .map_ok(|(k, _)| k)
+                .try_collect()?;
+            for key in keys {
+                self.txn.delete(&key)?;
+            }
+        }
+        self.txn
+            .delete(&Key::Table((&table.name).into()).encode())?;
+        Ok(true)
+    }
+
     fn get_table(&self, table: &str) -> Result<Option<Table>> {
         self.txn
             .get(&Key::Table(table.into()).encode())?
@@ -64,20 +127,179 @@ impl<E: storage::Engine> Catalog for Transaction<E> {
     }
 
     fn list_tables(&self) -> Result<Vec<Table>> {
-        errinput!("list_tables not implemented")
+        self.txn
+            .scan_prefix(&KeyPrefix::Table.encode())
+            .map(|result| result.and_then(|(_, value)| Table::decode(&value)))
+            .collect()
+    }
+
+    fn functions(&self) -> FunctionRegistry {
+        self.functions.clone()
+    }
+
+    fn create_index(&self, table_name: &str, column_name: &str) -> Result<()> {
+        let mut table = self.must_get_table(table_name)?;
+        let Some(index) = table.columns.iter().position(|c| c.name == column_name) else {
+            return errinput!("unknown column {column_name} in table {table_name}");
+        };
+        if index == table.primary_key {
+            return errinput!("can't index primary key column {column_name}");
+        }
+        if table.columns[index].index {
+            return errinput!("index already exists on {table_name}.{column_name}");
+        }
+        table.columns[index].index = true;
+        self.txn
+            .set(&Key::Table((&table.name).into()).encode(), table.encode())?;
+
+        // Index existing rows. Collect the entries before writing them, to
+        // avoid writing while scanning.
+        let mut entries: BTreeMap<Value, BTreeSet<Value>> = BTreeMap::new();
+        for result in self
+            .txn
+            .scan_prefix(&KeyPrefix::Row((&table.name).into()).encode())
+        {
+            let row = Row::decode(&result?.1)?;
+            let id = row[table.primary_key].clone();
+            entries.entry(row[index].clone()).or_default().insert(id);
+        }
+        for (value, ids) in entries {
+            self.set_index(&table.name, column_name, &value, ids)?;
+        }
+        Ok(())
     }
 }
 
 impl<E: storage::Engine> Transaction<E> {
-    pub fn new(txn: mvcc::TransactionInner<E>) -> Self {
-        Self { txn }
+    pub fn new(txn: mvcc::TransactionInner<E>, functions: FunctionRegistry) -> Self {
+        Self { txn, functions }
     }
 
     /// Fetch a single row by primary key, or not if it doesn't exist.
     /// the key must already be normalized.
-    fn get_row(&self, _table: &str, id: &Value) -> Result<Option<Row>> {
+    fn get_row(&self, table: &str, id: &Value) -> Result<Option<Row>> {
         debug_assert!(id.is_normalized(), "value not normalized");
-        errinput!("get_row not implemented")
+        self.txn
+            .get(&Key::Row(table.into(), id.into()).encode())?
+            .map(|v| Row::decode(&v))
+            .transpose()
+    }
+
+    /// Returns the tables with foreign key references to the given table, along
+    /// with the indexes of the referencing columns.
+    fn table_references(&self, table: &str, with_self: bool) -> Result<Vec<(Table, Vec<usize>)>> {
+        Ok(self
+            .list_tables()?
+            .into_iter()
+            .filter(|source| with_self || source.name != table)
+            .map(|source| {
+                let refs = (0..source.columns.len())
+                    .filter(|i| source.columns[*i].references.as_deref() == Some(table))
+                    .collect_vec();
+                (source, refs)
+            })
+            .filter(|(_, refs)| !refs.is_empty())
+            .collect())
+    }
+
+    /// Errors if any of the given primary keys of a table are referenced by
+    /// foreign keys in other rows. References from rows that are being
+    /// deleted along with them are ignored.
+    fn check_references(&self, table: &Table, ids: &BTreeSet<Value>) -> Result<()> {
+        for (source, refs) in self.table_references(&table.name, true)? {
+            let self_reference = source.name == table.name;
+            for i in refs {
+                let column = &source.columns[i];
+                let values = ids.iter().cloned().collect_vec();
+                let mut source_ids = if column.index {
+                    super::Transaction::lookup_index(self, &source.name, &column.name, &values)?
+                } else {
+                    let mut source_ids = BTreeSet::new();
+                    for id in values {
+                        let filter = Expression::Equal(
+                            Expression::Column(i).into(),
+                            Expression::Constant(id).into(),
+                        );
+                        for row in super::Transaction::scan(self, &source.name, Some(filter))? {
+                            source_ids.insert(row?[source.primary_key].clone());
+                        }
+                    }
+                    source_ids
+                };
+                if self_reference {
+                    source_ids.retain(|id| !ids.contains(id));
+                }
+                if let Some(source_id) = source_ids.first() {
+                    let row = self
+                        .get_row(&source.name, source_id)?
+                        .expect("row not found");
+                    return errinput!(
+                        "row {} in table {} is referenced by {}.{} of row {source_id}",
+                        row[i],
+                        table.name,
+                        source.name,
+                        column.name,
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetches the primary keys for a secondary index value. The value must
+    /// already be normalized.
+    fn get_index(&self, table: &str, column: &str, value: &Value) -> Result<BTreeSet<Value>> {
+        debug_assert!(value.is_normalized(), "value not normalized");
+        Ok(self
+            .txn
+            .get(&Key::Index(table.into(), column.into(), value.into()).encode())?
+            .map(|v| BTreeSet::decode(&v))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    /// Stores the primary keys for a secondary index value, removing the
+    /// index entry if there are none.
+    fn set_index(
+        &self,
+        table: &str,
+        column: &str,
+        value: &Value,
+        ids: BTreeSet<Value>,
+    ) -> Result<()> {
+        let key = Key::Index(table.into(), column.into(), value.into()).encode();
+        match ids.is_empty() {
+            true => self.txn.delete(&key),
+            false => self.txn.set(&key, ids.encode()),
+        }
+    }
+
+    /// Updates the secondary index entries of a row with the given primary
+    /// key, from its old version (if any) to its new version (if any).
+    fn update_index(
+        &self,
+        table: &Table,
+        id: &Value,
+        old: Option<&Row>,
+        new: Option<&Row>,
+    ) -> Result<()> {
+        for (i, column) in table.columns.iter().enumerate().filter(|(_, c)| c.index) {
+            let (old, new) = (old.map(|r| &r[i]), new.map(|r| &r[i]));
+            if old == new {
+                continue;
+            }
+            if let Some(old) = old {
+                let mut ids = self.get_index(&table.name, &column.name, old)?;
+                ids.remove(id);
+                self.set_index(&table.name, &column.name, old, ids)?;
+            }
+            if let Some(new) = new {
+                let mut ids = self.get_index(&table.name, &column.name, new)?;
+                ids.insert(id.clone());
+                self.set_index(&table.name, &column.name, new, ids)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -93,12 +315,65 @@ impl<E: storage::Engine> super::Transaction for Transaction<E> {
     fn insert(&self, table_name: &str, rows: Vec<Row>) -> Result<()> {
         let table = self.must_get_table(table_name)?;
         for mut row in rows {
+            table.validate_row(&mut row, false, self)?;
             row.iter_mut().for_each(|v| v.normalize());
             let id = &row[table.primary_key];
+            self.update_index(&table, id, None, Some(&row))?;
             let key = Key::Row((&table.name).into(), id.into()).encode();
             self.txn.set(&key, row.encode())?;
+        }
+        Ok(())
+    }
+
+    fn update(&self, table_name: &str, rows: BTreeMap<Value, Row>) -> Result<()> {
+        let table = self.must_get_table(table_name)?;
+        let mut moved = Vec::new();
+        for (mut id, mut row) in rows {
+            id.normalize();
+            // If the primary key changes, the row is moved below.
+            if row[table.primary_key] != id {
+                moved.push((id, row));
+                continue;
+            }
+            let Some(old) = self.get_row(&table.name, &id)? else {
+                return errdata!("row {id} not found in table {}", table.name);
+            };
+            table.validate_row(&mut row, true, self)?;
+            row.iter_mut().for_each(|v| v.normalize());
+            self.update_index(&table, &id, Some(&old), Some(&row))?;
+            let key = Key::Row((&table.name).into(), (&id).into()).encode();
+            self.txn.set(&key, row.encode())?;
+        }
 
-            // todo: update secondary indexes
+        // Moved rows are all deleted before any are inserted under their new
+        // primary keys, such that keys can be shifted, e.g. SET id = id + 1.
+        // The new keys must not already exist.
+        let ids = moved.iter().map(|(id, _)| id.clone()).collect_vec();
+        self.delete(&table.name, &ids)?;
+        for (_, row) in moved {
+            let id = row[table.primary_key].normalize_ref();
+            if self.get_row(&table.name, &id)?.is_some() {
+                return errinput!("primary key {id} already exists");
+            }
+            self.insert(&table.name, vec![row])?;
+        }
+        Ok(())
+    }
+
+    fn delete(&self, table_name: &str, ids: &[Value]) -> Result<()> {
+        let table = self.must_get_table(table_name)?;
+        let ids: BTreeSet<Value> = ids
+            .iter()
+            .map(|id| id.normalize_ref().into_owned())
+            .collect();
+        self.check_references(&table, &ids)?;
+        for id in ids {
+            let Some(row) = self.get_row(&table.name, &id)? else {
+                continue;
+            };
+            self.update_index(&table, &id, Some(&row), None)?;
+            self.txn
+                .delete(&Key::Row((&table.name).into(), (&id).into()).encode())?;
         }
         Ok(())
     }
@@ -109,6 +384,14 @@ impl<E: storage::Engine> super::Transaction for Transaction<E> {
             .collect()
     }
 
+    fn lookup_index(&self, table: &str, column: &str, values: &[Value]) -> Result<BTreeSet<Value>> {
+        let mut ids = BTreeSet::new();
+        for value in values {
+            ids.extend(self.get_index(table, column, &value.normalize_ref())?);
+        }
+        Ok(ids)
+    }
+
     fn scan(&self, table: &str, filter: Option<Expression>) -> Result<Rows> {
         let key = KeyPrefix::Row(table.into()).encode();
         let rows = self
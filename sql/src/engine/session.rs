@@ -1,13 +1,16 @@
 use itertools::Itertools as _;
 
-use super::{Transaction, engine::Engine};
+use super::{Catalog as _, Transaction, engine::Engine};
 use crate::{
-    Parser,
+    Parser, errinput,
     error::{Error, Result},
     execution::execute::ExecutionResult,
     parser::ast,
     planner::Plan,
-    types::value::{Label, Row},
+    types::{
+        schema::{ColumnType, Table},
+        value::{Label, Row, Value},
+    },
 };
 
 pub struct Session<'a, E: Engine<'a>> {
@@ -21,14 +24,100 @@ impl<'a, E: Engine<'a>> Session<'a, E> {
     }
 
     pub fn execute(&mut self, statement: &str) -> Result<StatementResult> {
-        Ok(match Parser::new(statement).parse()? {
+        let statement = Parser::new(statement).parse()?;
+        self.execute_statement(statement)
+    }
+
+    /// Executes a script of semicolon-separated statements, e.g. a migration
+    /// file. The whole script is parsed before any statements are executed.
+    /// Each statement runs in its own transaction, and execution stops at the
+    /// first error.
+    pub fn execute_script(&mut self, script: &str) -> Result<Vec<StatementResult>> {
+        Parser::new(script)
+            .parse_script()?
+            .into_iter()
+            .map(|statement| self.execute_statement(statement))
+            .collect()
+    }
+
+    /// Prepares a statement for repeated execution via execute_prepared(),
+    /// parsing and planning it once. The statement may contain parameter
+    /// placeholders, written as ? or $1, which are bound to values on each
+    /// execution. The plan is built against the current schema, and is built
+    /// again on execution if any of the tables it references have changed.
+    pub fn prepare(&mut self, statement: &str) -> Result<PreparedStatement> {
+        let mut parser = Parser::new(statement);
+        let statement = parser.parse()?;
+        let parameters = parser.parameters();
+        if matches!(
+            statement,
+            ast::Statement::Explain(_) | ast::Statement::ShowTables | ast::Statement::ShowTable(_)
+        ) {
+            return errinput!("can't prepare EXPLAIN or SHOW statements");
+        }
+        let read_only = is_read_only(&statement);
+        let plan = self.with_txn(true, |txn| Plan::build(statement.clone(), txn))?;
+        let tables = plan.tables();
+        Ok(PreparedStatement {
+            statement,
+            plan,
+            tables,
+            parameters,
+            read_only,
+        })
+    }
+
+    /// Executes a prepared statement with the given parameter values, which
+    /// are type checked against the statement before it's optimized.
+    pub fn execute_prepared(
+        &mut self,
+        statement: &PreparedStatement,
+        parameters: &[Value],
+    ) -> Result<StatementResult> {
+        if parameters.len() != statement.parameters {
+            return errinput!(
+                "expected {} parameters, found {}",
+                statement.parameters,
+                parameters.len()
+            );
+        }
+        self.with_txn(statement.read_only, |txn| {
+            // Plan the statement again if a referenced table has changed, e.g.
+            // been recreated with different columns.
+            let mut plan = statement.plan.clone();
+            for table in &statement.tables {
+                if txn.get_table(&table.name)?.as_ref() != Some(table) {
+                    plan = Plan::build(statement.statement.clone(), txn)?;
+                    break;
+                }
+            }
+            plan.bind(parameters)?.optimize()?.execute(txn)?.try_into()
+        })
+    }
+
+    fn execute_statement(&mut self, statement: ast::Statement) -> Result<StatementResult> {
+        Ok(match statement {
             ast::Statement::Explain(statement) => self.with_txn(true, |txn| {
-                Ok(StatementResult::Explain(Plan::build(*statement, txn)?))
+                Ok(StatementResult::Explain(
+                    Plan::build(*statement, txn)?.optimize()?,
+                ))
+            })?,
+            ast::Statement::ShowTables => self.with_txn(true, |txn| {
+                let tables = txn.list_tables()?.into_iter().map(|t| t.name).collect();
+                Ok(StatementResult::ShowTables { tables })
+            })?,
+            ast::Statement::ShowTable(name) => self.with_txn(true, |txn| {
+                Ok(StatementResult::ShowTable {
+                    table: txn.must_get_table(&name)?,
+                })
             })?,
             statement => {
-                let read_only = matches!(statement, ast::Statement::Select { .. });
+                let read_only = is_read_only(&statement);
                 self.with_txn(read_only, |txn| {
-                    Plan::build(statement, txn)?.execute(txn)?.try_into()
+                    Plan::build(statement, txn)?
+                        .optimize()?
+                        .execute(txn)?
+                        .try_into()
                 })?
             }
         })
@@ -54,6 +143,14 @@ impl<'a, E: Engine<'a>> Session<'a, E> {
     }
 }
 
+/// Returns true if the statement only reads data.
+fn is_read_only(statement: &ast::Statement) -> bool {
+    matches!(
+        statement,
+        ast::Statement::Select { .. } | ast::Statement::SetOperation { .. }
+    )
+}
+
 impl<'a, E: Engine<'a>> Drop for Session<'a, E> {
     fn drop(&mut self) {
         if let Some(txn) = self.txn.take() {
@@ -64,15 +161,58 @@ impl<'a, E: Engine<'a>> Drop for Session<'a, E> {
     }
 }
 
+/// A prepared statement, planned once and executed any number of times with
+/// different parameter values, see Session::prepare().
+#[derive(Clone, Debug)]
+pub struct PreparedStatement {
+    statement: ast::Statement,
+    plan: Plan,
+    /// The schemas of the tables referenced by the plan when it was built.
+    tables: Vec<Table>,
+    parameters: usize,
+    read_only: bool,
+}
+
+impl PreparedStatement {
+    /// Returns the number of parameters the statement takes.
+    pub fn parameters(&self) -> usize {
+        self.parameters
+    }
+}
+
 #[derive(Debug)]
 pub enum StatementResult {
     Explain(Plan),
-    CreateTable { name: String },
-    DropTable { name: String },
-    Delete { count: u64 },
-    Insert { count: u64 },
-    Update { count: u64 },
-    Select { columns: Vec<Label>, rows: Vec<Row> },
+    CreateTable {
+        name: String,
+    },
+    CreateIndex {
+        table: String,
+        column: String,
+    },
+    DropTable {
+        name: String,
+    },
+    Delete {
+        count: u64,
+    },
+    Insert {
+        count: u64,
+    },
+    Update {
+        count: u64,
+    },
+    Select {
+        columns: Vec<Label>,
+        types: Vec<ColumnType>,
+        rows: Vec<Row>,
+    },
+    ShowTables {
+        tables: Vec<String>,
+    },
+    ShowTable {
+        table: Table,
+    },
 }
 
 impl TryFrom<ExecutionResult> for StatementResult {
@@ -81,12 +221,18 @@ impl TryFrom<ExecutionResult> for StatementResult {
     fn try_from(result: ExecutionResult) -> Result<Self> {
         Ok(match result {
             ExecutionResult::CreateTable { name } => Self::CreateTable { name },
+            ExecutionResult::CreateIndex { table, column } => Self::CreateIndex { table, column },
             ExecutionResult::DropTable { name, .. } => Self::DropTable { name },
             ExecutionResult::Delete { count } => Self::Delete { count },
             ExecutionResult::Insert { count } => Self::Insert { count },
             ExecutionResult::Update { count } => Self::Update { count },
-            ExecutionResult::Select { columns, rows } => Self::Select {
+            ExecutionResult::Select {
+                columns,
+                types,
+                rows,
+            } => Self::Select {
                 columns,
+                types,
                 rows: rows.try_collect()?,
             },
         })
@@ -0,0 +1,117 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    errinput,
+    error::Result,
+    planner::Aggregate,
+    types::{
+        expression::Expression,
+        value::{Row, Rows, Value},
+    },
+};
+
+/// Aggregates the source rows by the given GROUP BY expressions, emitting one
+/// row per group with the group values followed by the aggregate values, in
+/// group order. Without GROUP BY, a single row is always emitted, even for
+/// empty sources.
+pub fn aggregate(
+    mut source: Rows,
+    group_by: Vec<Expression>,
+    aggregates: Vec<Aggregate>,
+) -> Result<Rows> {
+    let mut groups: BTreeMap<Vec<Value>, Vec<Accumulator>> = BTreeMap::new();
+    if group_by.is_empty() {
+        groups.insert(
+            Vec::new(),
+            aggregates.iter().map(Accumulator::new).collect(),
+        );
+    }
+    while let Some(row) = source.next().transpose()? {
+        let key: Vec<Value> = group_by
+            .iter()
+            .map(|expr| expr.evaluate(Some(&row)))
+            .collect::<Result<_>>()?;
+        let accumulators = groups
+            .entry(key)
+            .or_insert_with(|| aggregates.iter().map(Accumulator::new).collect());
+        for (accumulator, aggregate) in accumulators.iter_mut().zip(&aggregates) {
+            accumulator.add(aggregate.expression().evaluate(Some(&row))?)?;
+        }
+    }
+    let rows: Vec<Row> = groups
+        .into_iter()
+        .map(|(key, accumulators)| {
+            let values = accumulators.into_iter().map(Accumulator::value);
+            key.into_iter().map(Ok).chain(values).collect()
+        })
+        .collect::<Result<_>>()?;
+    Ok(Box::new(rows.into_iter().map(Ok)))
+}
+
+/// Accumulates aggregate values for a group. NULL values are ignored.
+enum Accumulator {
+    Average { count: i64, sum: Value },
+    Count(i64),
+    CountDistinct(HashSet<Value>),
+    Max(Value),
+    Min(Value),
+    Sum(Value),
+}
+
+impl Accumulator {
+    fn new(aggregate: &Aggregate) -> Self {
+        match aggregate {
+            Aggregate::Average(_) => Self::Average {
+                count: 0,
+                sum: Value::Null,
+            },
+            Aggregate::Count(_) => Self::Count(0),
+            Aggregate::CountDistinct(_) => Self::CountDistinct(HashSet::new()),
+            Aggregate::Max(_) => Self::Max(Value::Null),
+            Aggregate::Min(_) => Self::Min(Value::Null),
+            Aggregate::Sum(_) => Self::Sum(Value::Null),
+        }
+    }
+
+    fn add(&mut self, value: Value) -> Result<()> {
+        if value == Value::Null {
+            return Ok(());
+        }
+        match self {
+            Self::Average { sum, .. } | Self::Sum(sum) => {
+                *sum = match (&*sum, value) {
+                    (
+                        Value::Null,
+                        value @ (Value::Integer(_) | Value::Float(_) | Value::Decimal(_)),
+                    ) => value,
+                    (Value::Null, value) => return errinput!("can't sum {value}"),
+                    (sum, value) => sum.checked_add(&value)?,
+                };
+                if let Self::Average { count, .. } = self {
+                    *count += 1;
+                }
+            }
+            Self::Count(count) => *count += 1,
+            Self::CountDistinct(values) => {
+                values.insert(value);
+            }
+            Self::Max(max) if *max == Value::Null || value > *max => *max = value,
+            Self::Min(min) if *min == Value::Null || value < *min => *min = value,
+            Self::Max(_) | Self::Min(_) => {}
+        }
+        Ok(())
+    }
+
+    fn value(self) -> Result<Value> {
+        Ok(match self {
+            Self::Average { count: 0, .. } => Value::Null,
+            Self::Average { count, sum } => match sum {
+                Value::Integer(sum) => Value::Float(sum as f64 / count as f64),
+                sum => sum.checked_div(&Value::Float(count as f64))?,
+            },
+            Self::Count(count) => Value::Integer(count),
+            Self::CountDistinct(values) => Value::Integer(values.len() as i64),
+            Self::Max(value) | Self::Min(value) | Self::Sum(value) => value,
+        })
+    }
+}
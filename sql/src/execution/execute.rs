@@ -1,10 +1,12 @@
-use super::{source, transform, write};
+use super::{aggregate, join, source, subquery, transform, write};
 use crate::{
     engine::{Catalog, Transaction},
-    errinput,
     error::Result,
     planner::{Node, Plan},
-    types::value::{Label, Rows},
+    types::{
+        schema::ColumnType,
+        value::{Label, Rows},
+    },
 };
 
 pub fn execute_plan(
@@ -18,28 +20,108 @@ pub fn execute_plan(
             catalog.create_table(schema)?;
             ExecutionResult::CreateTable { name }
         }
+        Plan::DropTable { table, if_exists } => {
+            let existed = catalog.drop_table(&table, if_exists)?;
+            ExecutionResult::DropTable {
+                name: table,
+                existed,
+            }
+        }
+        Plan::CreateIndex { table, column } => {
+            catalog.create_index(&table, &column)?;
+            ExecutionResult::CreateIndex { table, column }
+        }
         Plan::Insert {
             table,
             column_map,
             source,
+            on_conflict,
+            returning,
+        } => {
+            let source = execute(source, txn)?;
+            let rows = write::insert(txn, &table, column_map, source, on_conflict)?;
+            match returning.is_empty() {
+                true => ExecutionResult::Insert {
+                    count: rows.len() as u64,
+                },
+                false => write::returning(&table, rows, returning)?,
+            }
+        }
+        Plan::Update {
+            table,
+            source,
+            mut expressions,
+            returning,
+        } => {
+            let mut source = execute(source, txn)?;
+            if expressions.iter().any(|(_, expr)| expr.has_subqueries()) {
+                let size = table.columns.len();
+                let exprs = expressions.iter_mut().map(|(_, expr)| expr);
+                source = subquery::append(source, size, exprs, txn)?;
+            }
+            let rows = write::update(txn, &table, source, expressions)?;
+            match returning.is_empty() {
+                true => ExecutionResult::Update {
+                    count: rows.len() as u64,
+                },
+                false => write::returning(&table, rows, returning)?,
+            }
+        }
+        Plan::Delete {
+            table,
+            source,
+            returning,
         } => {
             let source = execute(source, txn)?;
-            let count = write::insert(txn, table, column_map, source)?;
-            ExecutionResult::Insert { count }
+            let rows = write::delete(txn, &table, source)?;
+            match returning.is_empty() {
+                true => ExecutionResult::Delete {
+                    count: rows.len() as u64,
+                },
+                false => write::returning(&table, rows, returning)?,
+            }
         }
         Plan::Select(root) => {
             let columns = (0..root.columns()).map(|i| root.column_label(i)).collect();
+            let types = root.column_types()?;
             let rows = execute(root, txn)?;
-            ExecutionResult::Select { rows, columns }
+            ExecutionResult::Select {
+                rows,
+                columns,
+                types,
+            }
         }
     })
 }
 
 pub fn execute(node: Node, txn: &impl Transaction) -> Result<Rows> {
     Ok(match node {
+        Node::Aggregate {
+            source,
+            mut group_by,
+            mut aggregates,
+        } => {
+            let size = source.columns();
+            let mut source = execute(*source, txn)?;
+            let subqueries = group_by
+                .iter()
+                .chain(aggregates.iter().map(|agg| agg.expression()))
+                .any(|expr| expr.has_subqueries());
+            if subqueries {
+                source = subquery::aggregate(source, size, &mut group_by, &mut aggregates, txn)?;
+            }
+            aggregate::aggregate(source, group_by, aggregates)?
+        }
         Node::Filter { source, predicate } => {
             let source = execute(*source, txn)?;
-            transform::filter(source, predicate)
+            match predicate.has_subqueries() {
+                true => subquery::filter(source, predicate, txn)?,
+                false => transform::filter(source, predicate),
+            }
+        }
+        Node::Distinct { source, on } => {
+            let source = execute(*source, txn)?;
+            transform::distinct(source, on)
         }
         Node::Offset { source, offset } => {
             let source = execute(*source, txn)?;
@@ -49,37 +131,104 @@ pub fn execute(node: Node, txn: &impl Transaction) -> Result<Rows> {
             source,
             key: orders,
         } => {
+            let size = source.columns();
             let source = execute(*source, txn)?;
-            transform::order(source, orders)?
+            match orders.iter().any(|(expr, _)| expr.has_subqueries()) {
+                true => subquery::order(source, size, orders, txn)?,
+                false => transform::order(source, orders)?,
+            }
         }
         Node::Limit { source, limit } => {
             let source = execute(*source, txn)?;
             transform::limit(source, limit)
         }
         Node::Values { rows } => source::values(rows),
-        Node::Scan { table, filter } => source::scan(txn, table, filter)?,
+        Node::Cte { source, .. } => execute(*source, txn)?,
+        Node::Union { left, right, all } => {
+            transform::union(execute(*left, txn)?, execute(*right, txn)?, all)
+        }
+        Node::Intersect { left, right, all } => {
+            transform::intersect(execute(*left, txn)?, execute(*right, txn)?, all)?
+        }
+        Node::Except { left, right, all } => {
+            transform::except(execute(*left, txn)?, execute(*right, txn)?, all)?
+        }
+        Node::NestedLoopJoin {
+            left,
+            right,
+            predicate,
+            outer,
+        } => {
+            let right_size = right.columns();
+            let left = execute(*left, txn)?;
+            let right = execute(*right, txn)?;
+            match predicate {
+                Some(predicate) if predicate.has_subqueries() => {
+                    subquery::nested_loop(left, right, right_size, predicate, outer, txn)?
+                }
+                predicate => join::nested_loop(left, right, right_size, predicate, outer),
+            }
+        }
+        Node::HashJoin {
+            left,
+            left_column,
+            right,
+            right_column,
+            outer,
+        } => {
+            let right_size = right.columns();
+            let left = execute(*left, txn)?;
+            let right = execute(*right, txn)?;
+            join::hash(left, left_column, right, right_column, right_size, outer)?
+        }
+        Node::Scan { table, filter, .. } => source::scan(txn, table, filter)?,
+        Node::KeyLookup { table, keys, .. } => source::lookup_key(txn, table, keys)?,
+        Node::IndexLookup {
+            table,
+            column,
+            values,
+            ..
+        } => source::lookup_index(txn, table, column, values)?,
         Node::Projection {
             source,
             expressions,
             ..
         } => {
             let source = execute(*source, txn)?;
-            transform::projection(source, expressions)
+            match expressions.iter().any(|expr| expr.has_subqueries()) {
+                true => subquery::projection(source, expressions, txn)?,
+                false => transform::projection(source, expressions),
+            }
         }
         Node::Nothing { .. } => source::nothing(),
-
-        _ => {
-            return errinput!("not support this node:{node:?}");
-        }
     })
 }
 
 /// A plan execution result.
 pub enum ExecutionResult {
-    CreateTable { name: String },
-    DropTable { name: String, existed: bool },
-    Delete { count: u64 },
-    Insert { count: u64 },
-    Update { count: u64 },
-    Select { rows: Rows, columns: Vec<Label> },
+    CreateTable {
+        name: String,
+    },
+    CreateIndex {
+        table: String,
+        column: String,
+    },
+    DropTable {
+        name: String,
+        existed: bool,
+    },
+    Delete {
+        count: u64,
+    },
+    Insert {
+        count: u64,
+    },
+    Update {
+        count: u64,
+    },
+    Select {
+        rows: Rows,
+        columns: Vec<Label>,
+        types: Vec<ColumnType>,
+    },
 }
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use crate::{
+    errinput,
+    error::Result,
+    types::{
+        decimal::Decimal,
+        expression::Expression,
+        value::{Row, Rows, Value},
+    },
+};
+
+/// Joins the left and right sources by iterating over the right source for
+/// every left row, emitting the combined rows that match the predicate. For
+/// outer joins, unmatched left rows are emitted with right_size NULLs.
+pub fn nested_loop(
+    left: Rows,
+    right: Rows,
+    right_size: usize,
+    predicate: Option<Expression>,
+    outer: bool,
+) -> Rows {
+    Box::new(NestedLoopIterator {
+        left,
+        left_row: None,
+        right: right.clone(),
+        right_source: right,
+        right_size,
+        matched: false,
+        predicate,
+        outer,
+    })
+}
+
+#[derive(Clone)]
+struct NestedLoopIterator {
+    /// The left source.
+    left: Rows,
+    /// The current left row, if any.
+    left_row: Option<Row>,
+    /// The remaining right rows for the current left row.
+    right: Rows,
+    /// The original right source, cloned for each left row.
+    right_source: Rows,
+    /// The number of right columns, for NULL padding.
+    right_size: usize,
+    /// Whether the current left row has matched any right rows.
+    matched: bool,
+    predicate: Option<Expression>,
+    outer: bool,
+}
+
+impl NestedLoopIterator {
+    fn try_next(&mut self) -> Result<Option<Row>> {
+        loop {
+            let Some(left_row) = &self.left_row else {
+                let Some(row) = self.left.next().transpose()? else {
+                    return Ok(None);
+                };
+                self.left_row = Some(row);
+                self.right = self.right_source.clone();
+                self.matched = false;
+                continue;
+            };
+
+            while let Some(right_row) = self.right.next().transpose()? {
+                let row: Row = left_row.iter().cloned().chain(right_row).collect();
+                let matches = match &self.predicate {
+                    Some(predicate) => match predicate.evaluate(Some(&row))? {
+                        Value::Boolean(b) => b,
+                        Value::Null => false,
+                        value => {
+                            return errinput!("join predicate returned {value}, expected boolean");
+                        }
+                    },
+                    None => true,
+                };
+                if matches {
+                    self.matched = true;
+                    return Ok(Some(row));
+                }
+            }
+
+            let left_row = self.left_row.take().expect("no left row");
+            if self.outer && !self.matched {
+                let nulls = std::iter::repeat_n(Value::Null, self.right_size);
+                return Ok(Some(left_row.into_iter().chain(nulls).collect()));
+            }
+        }
+    }
+}
+
+impl Iterator for NestedLoopIterator {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().transpose()
+    }
+}
+
+/// Joins the left and right sources on equality of the given columns, by
+/// building a hash table of the right rows keyed by the right column and
+/// looking up each left row in it. For outer joins, unmatched left rows are
+/// emitted with right_size NULLs.
+pub fn hash(
+    left: Rows,
+    left_column: usize,
+    right: Rows,
+    right_column: usize,
+    right_size: usize,
+    outer: bool,
+) -> Result<Rows> {
+    let mut table: HashMap<Value, Vec<Row>> = HashMap::new();
+    for row in right {
+        let row = row?;
+        if let Some(key) = hash_key(&row[right_column]) {
+            table.entry(key).or_default().push(row);
+        }
+    }
+    Ok(Box::new(left.flat_map(move |result| -> Vec<Result<Row>> {
+        let row = match result {
+            Ok(row) => row,
+            Err(error) => return vec![Err(error)],
+        };
+        match hash_key(&row[left_column]).and_then(|key| table.get(&key)) {
+            Some(matches) => matches
+                .iter()
+                .map(|right| Ok(row.iter().chain(right).cloned().collect()))
+                .collect(),
+            None if outer => {
+                let nulls = std::iter::repeat_n(Value::Null, right_size);
+                vec![Ok(row.into_iter().chain(nulls).collect())]
+            }
+            None => Vec::new(),
+        }
+    })))
+}
+
+/// Returns the hash table key for a join value, or None if the value can't
+/// equal any other value (i.e. NULL or NaN). Integers compare equal to floats
+/// and decimals of the same value, so integral floats and decimals that fit
+/// in an integer are converted to integers. Integers are kept exact, since
+/// converting them to floats would lose precision above 2^53.
+fn hash_key(value: &Value) -> Option<Value> {
+    const MAX: f64 = 9_223_372_036_854_775_808.0; // 2^63
+    match value {
+        Value::Null => None,
+        Value::Float(f) if f.is_nan() => None,
+        Value::Float(f) if f.fract() == 0.0 && (-MAX..MAX).contains(f) => {
+            Some(Value::Integer(*f as i64))
+        }
+        Value::Decimal(d) => match d.to_i64() {
+            Some(i) if Decimal::from(i) == *d => Some(Value::Integer(i)),
+            _ => Some(value.clone()),
+        },
+        value => Some(value.normalize_ref().into_owned()),
+    }
+}
@@ -1,5 +1,8 @@
+mod aggregate;
 pub mod execute;
+mod join;
 mod source;
+mod subquery;
 mod transform;
 mod write;
 
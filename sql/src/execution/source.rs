@@ -1,12 +1,34 @@
 use crate::{
     engine::Transaction,
     error::Result,
-    types::{expression::Expression, schema::Table, value::Rows},
+    types::{
+        expression::Expression,
+        schema::Table,
+        value::{Rows, Value},
+    },
 };
 
 pub fn scan(txn: &impl Transaction, table: Table, filter: Option<Expression>) -> Result<Rows> {
     Ok(Box::new(txn.scan(&table.name, filter)?))
 }
+pub fn lookup_key(txn: &impl Transaction, table: Table, keys: Vec<Value>) -> Result<Rows> {
+    Ok(Box::new(txn.get(&table.name, &keys)?.into_iter().map(Ok)))
+}
+
+pub fn lookup_index(
+    txn: &impl Transaction,
+    table: Table,
+    column: usize,
+    values: Vec<Value>,
+) -> Result<Rows> {
+    let column = &table.columns[column].name;
+    let ids: Vec<_> = txn
+        .lookup_index(&table.name, column, &values)?
+        .into_iter()
+        .collect();
+    Ok(Box::new(txn.get(&table.name, &ids)?.into_iter().map(Ok)))
+}
+
 pub fn nothing() -> Rows {
     Box::new(std::iter::empty())
 }
@@ -0,0 +1,241 @@
+//! Executes expressions containing subqueries. Subqueries can be used in
+//! WHERE, HAVING, SELECT, ORDER BY, GROUP BY, aggregate arguments, JOIN ON
+//! (except RIGHT JOIN), and UPDATE SET and WHERE expressions. Correlated
+//! subqueries can only reference the immediately enclosing query.
+//!
+//! Subqueries are only executed when evaluation reaches them, e.g. not for
+//! untaken CASE branches. Uncorrelated subqueries are executed once on first
+//! use, while correlated subqueries are executed for each outer row with the
+//! outer values bound. Source rows are processed eagerly, since subqueries
+//! must be executed while the transaction is borrowed.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+use itertools::Itertools as _;
+
+use super::{execute::execute, transform};
+use crate::{
+    engine::Transaction,
+    errinput,
+    error::Result,
+    planner::{Aggregate, Direction, Node, OPTIMIZERS},
+    types::{
+        expression::Expression,
+        value::{Row, Rows, Value},
+    },
+};
+
+/// Filters the source rows by a predicate containing subqueries.
+pub fn filter(source: Rows, predicate: Expression, txn: &impl Transaction) -> Result<Rows> {
+    let executor = Executor::new(txn);
+    let mut rows = Vec::new();
+    for row in source {
+        let row = row?;
+        match executor.evaluate(&predicate, &row)? {
+            Value::Boolean(true) => rows.push(row),
+            Value::Boolean(false) | Value::Null => {}
+            value => return errinput!("filter returned {value}, expected boolean"),
+        }
+    }
+    Ok(Box::new(rows.into_iter().map(Ok)))
+}
+
+/// Projects the source rows with expressions containing subqueries.
+pub fn projection(
+    source: Rows,
+    expressions: Vec<Expression>,
+    txn: &impl Transaction,
+) -> Result<Rows> {
+    let executor = Executor::new(txn);
+    let rows: Vec<Row> = source
+        .map(|row| {
+            let row = row?;
+            expressions
+                .iter()
+                .map(|expr| executor.evaluate(expr, &row))
+                .collect()
+        })
+        .try_collect()?;
+    Ok(Box::new(rows.into_iter().map(Ok)))
+}
+
+/// Orders the source rows by keys containing subqueries. The subquery keys
+/// are evaluated via append(), and removed again after ordering.
+pub fn order(
+    source: Rows,
+    size: usize,
+    mut order: Vec<(Expression, Direction)>,
+    txn: &impl Transaction,
+) -> Result<Rows> {
+    let source = append(source, size, order.iter_mut().map(|(expr, _)| expr), txn)?;
+    let rows = transform::order(source, order)?;
+    Ok(Box::new(rows.map_ok(move |mut row| {
+        row.truncate(size);
+        row
+    })))
+}
+
+/// Prepares an aggregation with group or aggregate expressions containing
+/// subqueries, by evaluating them via append(). Returns the extended source.
+pub fn aggregate(
+    source: Rows,
+    size: usize,
+    group_by: &mut [Expression],
+    aggregates: &mut [Aggregate],
+    txn: &impl Transaction,
+) -> Result<Rows> {
+    let expressions = group_by.iter_mut().chain(
+        aggregates
+            .iter_mut()
+            .map(|aggregate| aggregate.expression_mut()),
+    );
+    append(source, size, expressions, txn)
+}
+
+/// Joins the left and right source rows with a predicate containing
+/// subqueries, like join::nested_loop().
+pub fn nested_loop(
+    left: Rows,
+    right: Rows,
+    right_size: usize,
+    predicate: Expression,
+    outer: bool,
+    txn: &impl Transaction,
+) -> Result<Rows> {
+    let executor = Executor::new(txn);
+    let right: Vec<Row> = right.try_collect()?;
+    let mut rows = Vec::new();
+    for left_row in left {
+        let left_row = left_row?;
+        let mut matched = false;
+        for right_row in &right {
+            let row: Row = left_row.iter().chain(right_row).cloned().collect();
+            match executor.evaluate(&predicate, &row)? {
+                Value::Boolean(true) => {
+                    matched = true;
+                    rows.push(row);
+                }
+                Value::Boolean(false) | Value::Null => {}
+                value => return errinput!("join predicate returned {value}, expected boolean"),
+            }
+        }
+        if outer && !matched {
+            let nulls = std::iter::repeat_n(Value::Null, right_size);
+            rows.push(left_row.into_iter().chain(nulls).collect());
+        }
+    }
+    Ok(Box::new(rows.into_iter().map(Ok)))
+}
+
+/// Evaluates the expressions containing subqueries for each source row, and
+/// appends their values to the row. The expressions are replaced by column
+/// references to the appended values, such that nodes which can't execute
+/// subqueries themselves can evaluate them.
+pub fn append<'a>(
+    source: Rows,
+    size: usize,
+    expressions: impl IntoIterator<Item = &'a mut Expression>,
+    txn: &impl Transaction,
+) -> Result<Rows> {
+    let mut appended = Vec::new();
+    for expr in expressions {
+        if expr.has_subqueries() {
+            let column = Expression::Column(size + appended.len());
+            appended.push(std::mem::replace(expr, column));
+        }
+    }
+    let executor = Executor::new(txn);
+    let rows: Vec<Row> = source
+        .map(|row| -> Result<Row> {
+            let mut row = row?;
+            for expr in &appended {
+                let value = executor.evaluate(expr, &row)?;
+                row.push(value);
+            }
+            Ok(row)
+        })
+        .try_collect()?;
+    Ok(Box::new(rows.into_iter().map(Ok)))
+}
+
+/// Executes subqueries during expression evaluation.
+struct Executor<'a, T: Transaction> {
+    txn: &'a T,
+    /// Subquery results by node address, which is stable while evaluating the
+    /// expressions that own them. Uncorrelated subqueries have their rows
+    /// cached, while correlated subqueries are None and always executed.
+    cache: RefCell<HashMap<*const Node, Option<Vec<Row>>>>,
+}
+
+impl<'a, T: Transaction> Executor<'a, T> {
+    fn new(txn: &'a T) -> Self {
+        Self {
+            txn,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Evaluates an expression for the given row, executing any subqueries
+    /// with the row's values bound to their outer column references.
+    fn evaluate(&self, expr: &Expression, row: &Row) -> Result<Value> {
+        expr.evaluate_with(Some(row), &|node| self.execute(node, row))
+    }
+
+    /// Executes a subquery for the given outer row.
+    fn execute(&self, node: &Node, row: &Row) -> Result<Rows> {
+        let key = std::ptr::from_ref(node);
+        let cached = self.cache.borrow().get(&key).cloned();
+        let rows = match cached {
+            Some(Some(rows)) => rows,
+            Some(None) => return execute(bind(node.clone(), row)?, self.txn),
+            None if is_correlated(node)? => {
+                self.cache.borrow_mut().insert(key, None);
+                return execute(bind(node.clone(), row)?, self.txn);
+            }
+            None => {
+                let rows: Vec<Row> = execute(node.clone(), self.txn)?.try_collect()?;
+                self.cache.borrow_mut().insert(key, Some(rows.clone()));
+                rows
+            }
+        };
+        Ok(Box::new(rows.into_iter().map(Ok)))
+    }
+}
+
+/// Binds outer column references in the subquery node to the given row's
+/// values, and re-optimizes it e.g. to use primary key lookups for them.
+/// Nested subqueries have their own outer row, and aren't bound.
+fn bind(node: Node, row: &Row) -> Result<Node> {
+    let node = node.transform(
+        &|node| {
+            node.transform_expressions(&Ok, &|expr| match expr {
+                Expression::OuterColumn(index, _) => Ok(Expression::Constant(row[index].clone())),
+                expr => Ok(expr),
+            })
+        },
+        &Ok,
+    )?;
+    OPTIMIZERS
+        .iter()
+        .try_fold(node, |node, (_, optimizer)| optimizer(node))
+}
+
+/// Returns true if the subquery node references outer columns.
+fn is_correlated(node: &Node) -> Result<bool> {
+    let correlated = Cell::new(false);
+    node.clone().transform(
+        &|node| {
+            node.transform_expressions(&Ok, &|expr| {
+                if matches!(expr, Expression::OuterColumn(..)) {
+                    correlated.set(true);
+                }
+                Ok(expr)
+            })
+        },
+        &Ok,
+    )?;
+    Ok(correlated.get())
+}
@@ -1,3 +1,5 @@
+use std::collections::{HashMap, HashSet};
+
 use itertools::{Itertools as _, izip};
 
 use crate::{
@@ -6,7 +8,7 @@ use crate::{
     planner::Direction,
     types::{
         expression::{self, Expression},
-        value::{Rows, Value},
+        value::{Row, Rows, Value},
     },
 };
 
@@ -33,6 +35,76 @@ pub fn projection(source: Rows, expression: Vec<Expression>) -> Rows {
     }))
 }
 
+/// Emits the left rows followed by the right rows, removing duplicates
+/// unless all is true.
+pub fn union(left: Rows, right: Rows, all: bool) -> Rows {
+    let rows = Box::new(left.chain(right));
+    match all {
+        true => rows,
+        false => distinct(rows, Vec::new()),
+    }
+}
+
+/// Removes duplicate rows, keeping the first one, using a hash set of seen
+/// rows. If on is given, the on expressions are compared instead.
+pub fn distinct(source: Rows, on: Vec<Expression>) -> Rows {
+    let mut seen = HashSet::new();
+    Box::new(source.filter_map(move |r| {
+        r.and_then(|row| {
+            let key: Row = match on.is_empty() {
+                true => row.clone(),
+                false => on
+                    .iter()
+                    .map(|expr| expr.evaluate(Some(&row)))
+                    .try_collect()?,
+            };
+            Ok(seen.insert(key).then_some(row))
+        })
+        .transpose()
+    }))
+}
+
+/// Emits the left rows that exist in the right rows, hashing the right rows.
+/// With all, rows are emitted as many times as they occur in both, otherwise
+/// once.
+pub fn intersect(left: Rows, right: Rows, all: bool) -> Result<Rows> {
+    let mut counts: HashMap<Row, usize> = HashMap::new();
+    for row in right {
+        *counts.entry(row?).or_default() += 1;
+    }
+    let mut rows = Vec::new();
+    for row in left {
+        let row = row?;
+        if let Some(count) = counts.get_mut(&row).filter(|count| **count > 0) {
+            *count = if all { *count - 1 } else { 0 };
+            rows.push(row);
+        }
+    }
+    Ok(Box::new(rows.into_iter().map(Ok)))
+}
+
+/// Emits the left rows that don't exist in the right rows, hashing the right
+/// rows. With all, each right row removes a single matching left row,
+/// otherwise duplicates are removed.
+pub fn except(left: Rows, right: Rows, all: bool) -> Result<Rows> {
+    let mut counts: HashMap<Row, usize> = HashMap::new();
+    for row in right {
+        *counts.entry(row?).or_default() += 1;
+    }
+    let mut seen = HashSet::new();
+    let mut rows = Vec::new();
+    for row in left {
+        let row = row?;
+        match counts.get_mut(&row) {
+            Some(count) if all && *count > 0 => *count -= 1,
+            Some(_) if !all => {}
+            _ if all || seen.insert(row.clone()) => rows.push(row),
+            _ => {}
+        }
+    }
+    Ok(Box::new(rows.into_iter().map(Ok)))
+}
+
 pub fn limit(source: Rows, limit: usize) -> Rows {
     Box::new(source.take(limit))
 }
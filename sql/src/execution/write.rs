@@ -1,21 +1,34 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
+use itertools::Itertools as _;
+
+use super::{execute::ExecutionResult, transform};
 use crate::{
     engine::Transaction,
     errinput,
     error::Result,
-    types::{schema::Table, value::Rows},
+    planner::{Node, OnConflict, Returning},
+    types::{
+        expression::Expression,
+        schema::Table,
+        value::{Label, Row, Rows, Value},
+    },
 };
 
+/// Inserts the source rows into the table, returning the inserted rows. Rows
+/// whose primary key already exists are handled by the ON CONFLICT action if
+/// given, otherwise they error.
 pub fn insert(
     txn: &impl Transaction,
-    table: Table,
+    table: &Table,
     column_map: Option<HashMap<usize, usize>>,
     mut source: Rows,
-) -> Result<u64> {
+    on_conflict: Option<OnConflict>,
+) -> Result<Vec<Row>> {
     let mut rows = Vec::new();
-    while let Some(values) = source.next().transpose()? {
+    while let Some(mut values) = source.next().transpose()? {
         if values.len() == table.columns.len() && column_map.is_none() {
+            coerce_row(table, &mut values)?;
             rows.push(values);
             continue;
         }
@@ -27,9 +40,15 @@ pub fn insert(
                 return errinput!("column_map length does not match values length");
             }
         }
+        // Build the row from the given values, by column map (column index to
+        // value index) or position, and fill in defaults for the rest.
         let mut row = Vec::with_capacity(table.columns.len());
         for (i, column) in table.columns.iter().enumerate() {
-            if let Some(vi) = column_map.as_ref().and_then(|c| c.get(&i)).copied() {
+            let vi = match &column_map {
+                Some(column_map) => column_map.get(&i).copied(),
+                None => Some(i).filter(|i| *i < values.len()),
+            };
+            if let Some(vi) = vi {
                 row.push(values[vi].clone());
             } else if let Some(default) = &column.default {
                 row.push(default.clone());
@@ -37,8 +56,121 @@ pub fn insert(
                 return errinput!("no value given for column {} with no default", column.name);
             }
         }
+        coerce_row(table, &mut row)?;
+        rows.push(row);
+    }
+    let Some(on_conflict) = on_conflict else {
+        txn.insert(&table.name, rows.clone())?;
+        return Ok(rows);
+    };
+
+    // Look up and insert rows one at a time, such that conflicts between the
+    // inserted rows are also handled. Only inserted or updated rows are
+    // returned. Like PostgreSQL, DO UPDATE can't affect the same row twice,
+    // since the result would depend on the order of the inserted rows.
+    let mut affected = Vec::with_capacity(rows.len());
+    let mut affected_ids = HashSet::new();
+    for row in rows {
+        let id = row[table.primary_key].normalize_ref().into_owned();
+        let Some(existing) = txn.get(&table.name, &[id.clone()])?.into_iter().next() else {
+            txn.insert(&table.name, vec![row.clone()])?;
+            affected.push(row);
+            affected_ids.insert(id);
+            continue;
+        };
+        let OnConflict::Update(expressions) = &on_conflict else {
+            continue;
+        };
+        if !affected_ids.insert(id.clone()) {
+            return errinput!("ON CONFLICT DO UPDATE can't affect row {id} twice");
+        }
+        let combined: Row = existing.iter().chain(row.iter()).cloned().collect();
+        let mut update = existing.clone();
+        for (index, expr) in expressions {
+            update[*index] = expr.evaluate(Some(&combined))?;
+        }
+        coerce_row(table, &mut update)?;
+        let id = existing
+            .into_iter()
+            .nth(table.primary_key)
+            .expect("short row");
+        txn.update(&table.name, BTreeMap::from([(id, update.clone())]))?;
+        affected.push(update);
+    }
+    Ok(affected)
+}
+
+/// Updates the source rows in the table, returning the updated rows.
+pub fn update(
+    txn: &impl Transaction,
+    table: &Table,
+    mut source: Rows,
+    expressions: Vec<(usize, Expression)>,
+) -> Result<Vec<Row>> {
+    let mut updates = BTreeMap::new();
+    while let Some(row) = source.next().transpose()? {
+        let mut update = row.clone();
+        for (index, expr) in &expressions {
+            update[*index] = expr.evaluate(Some(&row))?;
+        }
+        // Drop any subquery values appended by subquery::append().
+        update.truncate(table.columns.len());
+        coerce_row(table, &mut update)?;
+        let id = row.into_iter().nth(table.primary_key).expect("short row");
+        updates.insert(id, update);
+    }
+    let rows = updates.values().cloned().collect();
+    txn.update(&table.name, updates)?;
+    Ok(rows)
+}
+
+/// Converts the row's values to the table's column types, e.g. integers to
+/// floats, such that returned rows match the stored rows.
+fn coerce_row(table: &Table, row: &mut Row) -> Result<()> {
+    for (column, value) in table.columns.iter().zip(row.iter_mut()) {
+        *value = column
+            .data_type
+            .coerce(std::mem::replace(value, Value::Null))?;
     }
-    let count = rows.len() as u64;
-    txn.insert(&table.name, rows)?;
-    Ok(count)
+    Ok(())
+}
+
+/// Deletes the source rows from the table, returning the deleted rows.
+pub fn delete(txn: &impl Transaction, table: &Table, source: Rows) -> Result<Vec<Row>> {
+    let rows: Vec<Row> = source.try_collect()?;
+    let ids: Vec<_> = rows
+        .iter()
+        .map(|row| row[table.primary_key].clone())
+        .collect();
+    txn.delete(&table.name, &ids)?;
+    Ok(rows)
+}
+
+/// Evaluates RETURNING expressions for the affected rows of a table, as a
+/// select result. Column references are labeled by the table column.
+pub fn returning(table: &Table, rows: Vec<Row>, returning: Returning) -> Result<ExecutionResult> {
+    let scan = Node::Scan {
+        table: table.clone(),
+        alias: None,
+        filter: None,
+    };
+    let (expressions, labels): (Vec<_>, Vec<_>) = returning.into_iter().unzip();
+    let columns = expressions
+        .iter()
+        .zip(labels)
+        .map(|(expr, label)| match (expr, label) {
+            (Expression::Column(index), Label::None) => scan.column_label(*index),
+            (_, label) => label,
+        })
+        .collect();
+    let types = expressions
+        .iter()
+        .map(|expr| expr.data_type(&|i| scan.column_type(i)))
+        .try_collect()?;
+    let rows = transform::projection(Box::new(rows.into_iter().map(Ok)), expressions);
+    Ok(ExecutionResult::Select {
+        rows,
+        columns,
+        types,
+    })
 }
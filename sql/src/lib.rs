@@ -7,6 +7,11 @@ mod planner;
 pub mod storage;
 mod types;
 
+pub use error::{Error, Result};
 pub use parser::Parser;
 pub use planner::{OPTIMIZERS, Plan, Planner, Scope};
 pub use storage::BitCask;
+pub use types::{
+    schema::{ColumnType, DataType},
+    value::Value,
+};
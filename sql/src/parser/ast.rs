@@ -4,14 +4,38 @@ pub type TableName = String;
 pub type ColumnName = String;
 pub type FunctionName = String;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Statement {
     Explain(Box<Statement>),
     Select {
+        /// Common table expressions, by name. Later ones can reference
+        /// earlier ones.
+        with: Vec<(TableName, Statement)>,
+        /// SELECT DISTINCT removes duplicate rows. With DISTINCT ON (exprs),
+        /// only the first row for each distinct value of exprs is kept.
+        /// Plain DISTINCT is given as an empty vector.
+        distinct: Option<Vec<Expression>>,
         select: Vec<(Expression, Option<String>)>,
         from: Vec<From>,
         r#where: Option<Expression>,
+        group_by: Vec<Expression>,
+        having: Option<Expression>,
+        order_by: Vec<(Expression, Direction)>,
+        limit: Option<Expression>,
+        offset: Option<Expression>,
+    },
+    /// A set operation between two queries, e.g. a UNION b. The WITH, ORDER
+    /// BY, LIMIT and OFFSET clauses apply to the combined result.
+    SetOperation {
+        with: Vec<(TableName, Statement)>,
+        operator: SetOperator,
+        /// Whether to keep duplicate rows, e.g. UNION ALL.
+        all: bool,
+        left: Box<Statement>,
+        right: Box<Statement>,
+        order_by: Vec<(Expression, Direction)>,
         limit: Option<Expression>,
+        offset: Option<Expression>,
     },
     CrateTable {
         table_name: TableName,
@@ -21,19 +45,48 @@ pub enum Statement {
         table_name: TableName,
         if_exists: bool,
     },
+    /// CREATE INDEX ON table (column): adds a secondary index on a column.
+    CreateIndex {
+        table_name: TableName,
+        column_name: ColumnName,
+    },
     Insert {
         table_name: TableName,
         columns: Option<Vec<ColumnName>>,
-        values: Vec<Vec<Expression>>,
+        source: InsertSource,
+        on_conflict: Option<OnConflict>,
+        returning: Vec<(Expression, Option<String>)>,
+    },
+    Update {
+        table_name: TableName,
+        set: Vec<(ColumnName, Expression)>,
+        r#where: Option<Expression>,
+        returning: Vec<(Expression, Option<String>)>,
     },
+    Delete {
+        table_name: TableName,
+        r#where: Option<Expression>,
+        /// RETURNING expressions and aliases, evaluated for the affected
+        /// rows. Empty if not given.
+        returning: Vec<(Expression, Option<String>)>,
+    },
+    /// SHOW TABLES: lists the names of all tables.
+    ShowTables,
+    /// SHOW TABLE name or DESCRIBE name: shows a table's schema.
+    ShowTable(TableName),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum From {
     Table {
         name: String,
         alias: Option<String>,
     },
+    /// A subquery in the FROM clause, which must have an alias.
+    Subquery {
+        query: Box<Statement>,
+        alias: String,
+    },
     Join {
         left: Box<From>,
         right: Box<From>,
@@ -42,7 +95,39 @@ pub enum From {
     },
 }
 
-#[derive(Debug, PartialEq)]
+/// The rows to insert: VALUES (...), ... or a SELECT query.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum InsertSource {
+    Values(Vec<Vec<Expression>>),
+    Select(Box<Statement>),
+}
+
+/// The action for INSERT rows whose primary key already exists, given by
+/// ON CONFLICT (column) DO NOTHING or DO UPDATE SET column = expr, ....
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OnConflict {
+    /// The conflict target column, if given.
+    pub target: Option<ColumnName>,
+    /// The columns to update. Empty for DO NOTHING.
+    pub set: Vec<(ColumnName, Expression)>,
+}
+
+/// Set operators between queries.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum SetOperator {
+    Union,
+    Intersect,
+    Except,
+}
+
+/// ORDER BY direction.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Direction {
+    Ascending,
+    Descending,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum JoinType {
     Cross,
     Inner,
@@ -55,9 +140,32 @@ pub enum Expression {
     All,
     Column(Option<TableName>, ColumnName),
     Literal(Literal),
+    /// A query parameter placeholder, by zero-based index: ? or $1.
+    Parameter(usize),
     Operator(Operator),
-    /// A function call (name and parameters).
-    Function(FunctionName, Vec<Expression>),
+    /// A function call (name and parameters). For aggregate functions,
+    /// distinct is true if only distinct values should be aggregated, as in
+    /// count(DISTINCT x).
+    Function {
+        name: FunctionName,
+        args: Vec<Expression>,
+        distinct: bool,
+    },
+    /// A scalar subquery, returning a single value: (SELECT ...).
+    Subquery(Box<Statement>),
+    /// EXISTS (SELECT ...): true if the subquery returns any rows.
+    Exists(Box<Statement>),
+    /// a IN (SELECT ...): true if the subquery returns a.
+    InSubquery(Box<Expression>, Box<Statement>),
+    /// CASE [operand] WHEN a THEN b ... [ELSE c] END. With an operand, the
+    /// WHEN values are compared with it, otherwise they are conditions.
+    Case {
+        operand: Option<Box<Expression>>,
+        when: Vec<(Expression, Expression)>,
+        r#else: Option<Box<Expression>>,
+    },
+    /// CAST(a AS type) or a::type.
+    Cast(Box<Expression>, DataType),
 }
 
 #[derive(Debug, Clone)]
@@ -67,6 +175,7 @@ pub enum Literal {
     Integer(i64),
     Float(f64),
     String(String),
+    Bytes(Vec<u8>),
 }
 
 impl std::cmp::Eq for Literal {}
@@ -78,6 +187,7 @@ impl std::hash::Hash for Literal {
             Literal::Integer(i) => i.hash(state),
             Literal::Float(f) => f.to_bits().hash(state),
             Literal::String(s) => s.hash(state),
+            Literal::Bytes(b) => b.hash(state),
         }
     }
 }
@@ -90,6 +200,7 @@ impl PartialEq for Literal {
             (Literal::Integer(a), Literal::Integer(b)) => a == b,
             (Literal::Float(a), Literal::Float(b)) => a == b,
             (Literal::String(a), Literal::String(b)) => a == b,
+            (Literal::Bytes(a), Literal::Bytes(b)) => a == b,
             _ => false,
         }
     }
@@ -108,6 +219,8 @@ pub enum Operator {
     GreaterThanOrEqual(Box<Expression>, Box<Expression>), // a >= b
     LessThanOrEqual(Box<Expression>, Box<Expression>), // a <= b
     Is(Box<Expression>, Literal),                  // a IS NULL
+    Between(Box<Expression>, Box<Expression>, Box<Expression>), // a BETWEEN b AND c
+    InList(Box<Expression>, Vec<Expression>),      // a IN (b, c)
 
     Add(Box<Expression>, Box<Expression>),         // a + b
     Subtract(Box<Expression>, Box<Expression>),    // a - b
@@ -117,8 +230,12 @@ pub enum Operator {
     Negate(Box<Expression>),                       // -a
     Identity(Box<Expression>),                     // +a
     Exponential(Box<Expression>, Box<Expression>), // a ^ b
+    JsonGet(Box<Expression>, Box<Expression>),     // a -> b
+    JsonGetText(Box<Expression>, Box<Expression>), // a ->> b
 
-    Like(Box<Expression>, Box<Expression>), // a LIKE b
+    Like(Box<Expression>, Box<Expression>, Option<Box<Expression>>), // a LIKE b [ESCAPE c]
+    ILike(Box<Expression>, Box<Expression>, Option<Box<Expression>>), // a ILIKE b [ESCAPE c]
+    Regexp(Box<Expression>, Box<Expression>),                        // a ~ b, a REGEXP b
 }
 
 impl core::convert::From<Operator> for Expression {
@@ -134,7 +251,7 @@ impl core::convert::From<Literal> for Expression {
 }
 
 impl Expression {
-    pub fn walk(&self, visitor: &mut impl FnMut(&Expression) -> bool) -> bool {
+    pub fn walk<'a>(&'a self, visitor: &mut impl FnMut(&'a Expression) -> bool) -> bool {
         use Operator::*;
         if !visitor(self) {
             return false;
@@ -154,19 +271,51 @@ impl Expression {
             | Self::Operator(Divide(lhs, rhs))
             | Self::Operator(Remainder(lhs, rhs))
             | Self::Operator(Exponential(lhs, rhs))
-            | Self::Operator(Like(lhs, rhs)) => lhs.walk(visitor) && rhs.walk(visitor),
+            | Self::Operator(JsonGet(lhs, rhs))
+            | Self::Operator(JsonGetText(lhs, rhs))
+            | Self::Operator(Regexp(lhs, rhs)) => lhs.walk(visitor) && rhs.walk(visitor),
+            Self::Operator(Like(lhs, rhs, escape)) | Self::Operator(ILike(lhs, rhs, escape)) => {
+                lhs.walk(visitor)
+                    && rhs.walk(visitor)
+                    && escape.iter().all(|expr| expr.walk(visitor))
+            }
+            Self::Operator(Between(expr, low, high)) => {
+                expr.walk(visitor) && low.walk(visitor) && high.walk(visitor)
+            }
+            Self::Operator(InList(expr, list)) => {
+                expr.walk(visitor) && list.iter().all(|expr| expr.walk(visitor))
+            }
             Self::Operator(Not(expr))
             | Self::Operator(Negate(expr))
             | Self::Operator(Is(expr, _))
-            | Self::Operator(Identity(expr)) => expr.walk(visitor),
-            Self::Function(_, exprs) => exprs.iter().any(|expr| expr.walk(visitor)),
-            Self::All | Self::Column(..) | Self::Literal(_) => true,
+            | Self::Operator(Identity(expr))
+            | Self::InSubquery(expr, _) => expr.walk(visitor),
+            Self::Function { args, .. } => args.iter().all(|expr| expr.walk(visitor)),
+            Self::Case {
+                operand,
+                when,
+                r#else,
+            } => {
+                operand.iter().all(|expr| expr.walk(visitor))
+                    && when
+                        .iter()
+                        .all(|(cond, then)| cond.walk(visitor) && then.walk(visitor))
+                    && r#else.iter().all(|expr| expr.walk(visitor))
+            }
+            Self::Cast(expr, _) => expr.walk(visitor),
+            // Subqueries are separate statements, and aren't walked.
+            Self::All
+            | Self::Column(..)
+            | Self::Literal(_)
+            | Self::Parameter(_)
+            | Self::Subquery(_)
+            | Self::Exists(_) => true,
         }
     }
 }
 
 /// A CREATE TABLE column definition.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Column {
     pub name: String,
     pub datatype: DataType,
@@ -174,4 +323,6 @@ pub struct Column {
     pub nullable: Option<bool>,
     pub default: Option<Expression>,
     pub unique: bool,
+    pub index: bool,
+    pub references: Option<TableName>,
 }
@@ -3,7 +3,81 @@ use std::fmt::Display;
 use crate::error::{Error, Result};
 
 pub struct Lexer<'a> {
+    input: &'a str,
     chars: std::iter::Peekable<std::str::Chars<'a>>,
+    /// The location of the next character.
+    location: Location,
+}
+
+/// A location in the input string, as 1-based line and column numbers. Columns
+/// count characters, not bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Self { line: 1, column: 1 }
+    }
+}
+
+/// The span of a token in the input string, from the start location
+/// (inclusive) to the end location (exclusive).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Span {
+    /// Returns an empty span at the end of the input string.
+    pub fn end_of(input: &str) -> Self {
+        let mut location = Location::default();
+        for c in input.chars() {
+            location.advance(c);
+        }
+        Self {
+            start: location,
+            end: location,
+        }
+    }
+
+    /// Annotates an error message with the span location and an excerpt of
+    /// the input line, with carets under the span, e.g.:
+    ///
+    /// expected token ), found FROM at line 1, column 15:
+    /// SELECT (1 + 2 FROM t
+    ///               ^^^^
+    pub fn annotate(&self, input: &str, message: &str) -> String {
+        let Location { line, column } = self.start;
+        let text = input.lines().nth(line - 1).unwrap_or("");
+        // Retain tabs in the indentation, such that the carets line up.
+        let indent: String = text
+            .chars()
+            .take(column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let width = match self.end.line == line {
+            true => self.end.column.saturating_sub(column),
+            false => text.chars().count().saturating_sub(column - 1),
+        };
+        let carets = "^".repeat(width.max(1));
+        format!("{message} at line {line}, column {column}:\n{text}\n{indent}{carets}")
+    }
+}
+
+impl Location {
+    fn advance(&mut self, c: char) {
+        match c {
+            '\n' => {
+                self.line += 1;
+                self.column = 1;
+            }
+            _ => self.column += 1,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -13,10 +87,14 @@ pub enum Token {
     Number(String),
     /// A Unicode string, with quotes stripped and escape sequences resolved.
     String(String),
+    /// A byte string, written as hex digits like X'0aff'.
+    Bytes(Vec<u8>),
     /// An identifier, with any quotes stripped.
     Ident(String),
     /// A SQL keyword.
     Keyword(Keyword),
+    /// A numbered query parameter placeholder like $1.
+    Parameter(usize),
 
     Equal,              // =
     NotEqual,           // !=
@@ -31,6 +109,10 @@ pub enum Token {
     Slash,       // /
     Percent,     // %
     Exponential, // ^
+    Tilde,       // ~
+    Question,    // ?
+    Arrow,       // ->
+    LongArrow,   // ->>
 
     Comma,       // ,
     CloseParen,  // )
@@ -38,6 +120,8 @@ pub enum Token {
     OpenParen,   // (
     Period,      // .
     Semicolon,   // ;
+    Colon,       // :
+    DoubleColon, // ::
 }
 
 impl Display for Token {
@@ -47,6 +131,12 @@ impl Display for Token {
             Self::String(s) => s,
             Self::Ident(s) => s,
             Self::Keyword(k) => return k.fmt(f),
+            Self::Bytes(bytes) => {
+                f.write_str("X'")?;
+                bytes.iter().try_for_each(|b| write!(f, "{b:02x}"))?;
+                return f.write_str("'");
+            }
+            Self::Parameter(n) => return write!(f, "${n}"),
             Self::Period => ".",
 
             Self::Equal => "=",
@@ -62,9 +152,15 @@ impl Display for Token {
             Self::Slash => "/",
             Self::Percent => "%",
             Self::Exponential => "^",
+            Self::Tilde => "~",
+            Self::Question => "?",
+            Self::Arrow => "->",
+            Self::LongArrow => "->>",
 
             Self::Comma => ",",
             Self::Semicolon => ";",
+            Self::Colon => ":",
+            Self::DoubleColon => "::",
             Self::OpenParen => "(",
             Self::CloseParen => ")",
             Self::Exclamation => "!",
@@ -73,31 +169,50 @@ impl Display for Token {
 }
 
 impl<'a> Iterator for Lexer<'a> {
-    type Item = Result<Token>;
+    type Item = Result<(Token, Span)>;
     fn next(&mut self) -> Option<Self::Item> {
-        match self.scan() {
-            Ok(Some(token)) => Some(Ok(token)),
-            Ok(None) => self
-                .chars
-                .peek()
-                .map(|c| Err(Error::InvalidInput(format!("Unexpected character:{}", c)))),
-            Err(e) => Some(Err(e)),
-        }
+        self.skip_whitespace();
+        let start = self.location;
+        let result = match self.scan() {
+            Ok(Some(token)) => Ok(token),
+            Ok(None) => {
+                let c = *self.chars.peek()?;
+                self.next_char();
+                Err(format!("Unexpected character:{c}"))
+            }
+            Err(message) => Err(message),
+        };
+        let span = Span {
+            start,
+            end: self.location,
+        };
+        Some(match result {
+            Ok(token) => Ok((token, span)),
+            Err(message) => Err(Error::InvalidInput(span.annotate(self.input, &message))),
+        })
     }
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
         Self {
+            input,
             chars: input.chars().peekable(),
+            location: Location::default(),
         }
     }
 
-    fn scan(&mut self) -> Result<Option<Token>> {
-        self.skip_whitespace();
+    /// Scans the next token, if any. Errors are returned as plain messages,
+    /// which are annotated with the token span by the caller.
+    fn scan(&mut self) -> std::result::Result<Option<Token>, String> {
+        let mut lookahead = self.chars.clone();
+        if let (Some('x' | 'X'), Some('\'')) = (lookahead.next(), lookahead.next()) {
+            return self.scan_bytes();
+        }
         match self.chars.peek() {
             Some('\'') => self.scan_string(),
             Some('"') => self.scan_ident_quoted(),
+            Some('$') => self.scan_parameter(),
             Some(c) if c.is_ascii_digit() => Ok(self.scan_number()),
             Some(c) if c.is_ascii_alphabetic() => Ok(self.scan_ident_or_keyword()),
             Some(_) => Ok(self.scan_symbol()),
@@ -119,8 +234,11 @@ impl<'a> Lexer<'a> {
                 '/' => Token::Slash,
                 '%' => Token::Percent,
                 '^' => Token::Exponential,
+                '~' => Token::Tilde,
+                '?' => Token::Question,
 
                 ';' => Token::Semicolon,
+                ':' => Token::Colon,
                 '.' => Token::Period,
                 ',' => Token::Comma,
                 '(' => Token::OpenParen,
@@ -133,47 +251,94 @@ impl<'a> Lexer<'a> {
             Token::Exclamation if self.next_is('=') => Token::NotEqual,
             Token::LessThan if self.next_is('=') => Token::LessThanOrEqual,
             Token::GreaterThan if self.next_is('=') => Token::GreaterThanOrEqual,
+            Token::Colon if self.next_is(':') => Token::DoubleColon,
+            Token::Minus if self.next_is('>') => match self.next_is('>') {
+                true => Token::LongArrow,
+                false => Token::Arrow,
+            },
             token => token,
         };
         Some(token)
     }
 
-    fn scan_string(&mut self) -> Result<Option<Token>> {
+    fn scan_string(&mut self) -> std::result::Result<Option<Token>, String> {
         if !self.next_is('\'') {
             return Ok(None);
         }
 
         let mut string = String::new();
         loop {
-            match self.chars.next() {
+            match self.next_char() {
                 Some('\'') => break,
                 Some(c) => string.push(c),
-                None => return Error::InvalidInput("Unterminated string".to_owned()).into(),
+                None => return Err("Unterminated string".to_owned()),
             }
         }
 
         Ok(Some(Token::String(string)))
     }
 
-    fn scan_ident_quoted(&mut self) -> Result<Option<Token>> {
+    fn scan_bytes(&mut self) -> std::result::Result<Option<Token>, String> {
+        if self.next_if(|c| c == 'x' || c == 'X').is_none() || !self.next_is('\'') {
+            return Ok(None);
+        }
+
+        let mut digits = Vec::new();
+        loop {
+            match self.next_char() {
+                Some('\'') => break,
+                Some(c) => match c.to_digit(16) {
+                    Some(digit) => digits.push(digit as u8),
+                    None => return Err(format!("Invalid hex digit {c}")),
+                },
+                None => return Err("Unterminated byte string".to_owned()),
+            }
+        }
+        if digits.len() % 2 != 0 {
+            return Err("Byte string must have an even number of hex digits".to_owned());
+        }
+
+        let bytes = digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect();
+        Ok(Some(Token::Bytes(bytes)))
+    }
+
+    fn scan_ident_quoted(&mut self) -> std::result::Result<Option<Token>, String> {
         if !self.next_is('"') {
             return Ok(None);
         }
 
         let mut ident = String::new();
         loop {
-            match self.chars.next() {
+            match self.next_char() {
+                // A doubled quote is an escaped quote.
+                Some('"') if self.next_is('"') => ident.push('"'),
                 Some('"') => break,
                 Some(c) => ident.push(c),
-                None => {
-                    return Error::InvalidInput("Unterminated quoted identifier".to_owned()).into();
-                }
+                None => return Err("Unterminated quoted identifier".to_owned()),
             }
         }
 
         Ok(Some(Token::Ident(ident)))
     }
 
+    fn scan_parameter(&mut self) -> std::result::Result<Option<Token>, String> {
+        if !self.next_is('$') {
+            return Ok(None);
+        }
+
+        let mut digits = String::new();
+        while let Some(c) = self.next_if(|c| c.is_ascii_digit()) {
+            digits.push(c);
+        }
+        match digits.parse() {
+            Ok(0) | Err(_) => Err(format!("Invalid parameter ${digits}")),
+            Ok(n) => Ok(Some(Token::Parameter(n))),
+        }
+    }
+
     fn scan_number(&mut self) -> Option<Token> {
         let mut number = self.next_if(|c| c.is_ascii_digit())?.to_string();
         while let Some(c) = self.next_if(|c| c.is_ascii_digit()) {
@@ -222,13 +387,20 @@ impl<'a> Lexer<'a> {
 
     fn next_if(&mut self, predicate: impl Fn(char) -> bool) -> Option<char> {
         self.chars.peek().filter(|&&c| predicate(c))?;
-        self.chars.next()
+        self.next_char()
     }
 
     fn next_if_map<T>(&mut self, map: impl Fn(char) -> Option<T>) -> Option<T> {
-        let val = self.chars.peek().and_then(|&c| map(c));
-        self.chars.next();
-        val
+        let val = self.chars.peek().and_then(|&c| map(c))?;
+        self.next_char();
+        Some(val)
+    }
+
+    /// Consumes the next character, tracking its location.
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.location.advance(c);
+        Some(c)
     }
 }
 
@@ -248,6 +420,13 @@ pub enum Keyword {
     Text,
     Float,
     Double,
+    Decimal,
+    Numeric,
+    Date,
+    Timestamp,
+    Interval,
+    Bytes,
+    Json,
 
     /// DDL keywords.
     Primary,
@@ -274,6 +453,83 @@ pub enum Keyword {
     Infinity,
     Like,
     As,
+    Show,
+    Tables,
+    Describe,
+    On,
+    Update,
+    Set,
+    Delete,
+    References,
+    If,
+    Exists,
+    Join,
+    Inner,
+    Left,
+    Right,
+    Outer,
+    Cross,
+    Explain,
+    Group,
+    By,
+    Having,
+    Distinct,
+    Order,
+    Asc,
+    Desc,
+    In,
+    With,
+    Union,
+    Intersect,
+    Except,
+    All,
+    Returning,
+    Conflict,
+    Do,
+    Nothing,
+    Is,
+    Between,
+    Offset,
+    Case,
+    When,
+    Then,
+    Else,
+    End,
+    Cast,
+    ILike,
+    Escape,
+    Regexp,
+}
+
+/// Formats an identifier for use in SQL. Identifiers that would not lex back
+/// to the same name unquoted, i.e. keywords and names with uppercase or
+/// special characters, are quoted, with quotes escaped by doubling them.
+pub fn quote_ident(ident: &str) -> String {
+    let mut chars = ident.chars();
+    let unquoted = chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && Keyword::try_from(ident).is_err();
+    match unquoted {
+        true => ident.to_string(),
+        false => format!("\"{}\"", ident.replace('"', "\"\"")),
+    }
+}
+
+impl Keyword {
+    /// Returns true for type names that can also be used as identifiers,
+    /// e.g. a column named date, since they're common column names.
+    pub fn is_type_name(&self) -> bool {
+        matches!(
+            self,
+            Self::Decimal
+                | Self::Numeric
+                | Self::Date
+                | Self::Timestamp
+                | Self::Interval
+                | Self::Bytes
+                | Self::Json
+        )
+    }
 }
 
 impl From<Keyword> for Token {
@@ -316,6 +572,13 @@ impl TryFrom<&str> for Keyword {
             "double" => Self::Double,
             "string" => Self::String,
             "text" => Self::Text,
+            "decimal" => Self::Decimal,
+            "numeric" => Self::Numeric,
+            "date" => Self::Date,
+            "timestamp" => Self::Timestamp,
+            "interval" => Self::Interval,
+            "bytes" => Self::Bytes,
+            "json" => Self::Json,
 
             "primary" => Self::Primary,
             "key" => Self::Key,
@@ -323,6 +586,52 @@ impl TryFrom<&str> for Keyword {
             "default" => Self::Default,
             "index" => Self::Index,
             "as" => Self::As,
+            "show" => Self::Show,
+            "tables" => Self::Tables,
+            "describe" => Self::Describe,
+            "on" => Self::On,
+            "update" => Self::Update,
+            "set" => Self::Set,
+            "delete" => Self::Delete,
+            "references" => Self::References,
+            "if" => Self::If,
+            "exists" => Self::Exists,
+            "join" => Self::Join,
+            "inner" => Self::Inner,
+            "left" => Self::Left,
+            "right" => Self::Right,
+            "outer" => Self::Outer,
+            "cross" => Self::Cross,
+            "explain" => Self::Explain,
+            "group" => Self::Group,
+            "by" => Self::By,
+            "having" => Self::Having,
+            "distinct" => Self::Distinct,
+            "order" => Self::Order,
+            "asc" => Self::Asc,
+            "desc" => Self::Desc,
+            "in" => Self::In,
+            "with" => Self::With,
+            "union" => Self::Union,
+            "intersect" => Self::Intersect,
+            "except" => Self::Except,
+            "all" => Self::All,
+            "returning" => Self::Returning,
+            "conflict" => Self::Conflict,
+            "do" => Self::Do,
+            "nothing" => Self::Nothing,
+            "is" => Self::Is,
+            "between" => Self::Between,
+            "offset" => Self::Offset,
+            "case" => Self::Case,
+            "when" => Self::When,
+            "then" => Self::Then,
+            "else" => Self::Else,
+            "end" => Self::End,
+            "cast" => Self::Cast,
+            "ilike" => Self::ILike,
+            "escape" => Self::Escape,
+            "regexp" => Self::Regexp,
 
             _ => return Err("cannot convert to keyword"),
         })
@@ -362,12 +671,65 @@ impl std::fmt::Display for Keyword {
             Self::Double => "DOUBLE",
             Self::String => "STRING",
             Self::Text => "TEXT",
+            Self::Decimal => "DECIMAL",
+            Self::Numeric => "NUMERIC",
+            Self::Date => "DATE",
+            Self::Timestamp => "TIMESTAMP",
+            Self::Interval => "INTERVAL",
+            Self::Bytes => "BYTES",
+            Self::Json => "JSON",
 
             Self::Primary => "PRIMARY",
             Self::Unique => "UNIQUE",
             Self::Default => "DEFAULT",
             Self::Index => "INDEX",
             Self::As => "AS",
+            Self::Show => "SHOW",
+            Self::Tables => "TABLES",
+            Self::Describe => "DESCRIBE",
+            Self::On => "ON",
+            Self::Update => "UPDATE",
+            Self::Set => "SET",
+            Self::Delete => "DELETE",
+            Self::References => "REFERENCES",
+            Self::If => "IF",
+            Self::Exists => "EXISTS",
+            Self::Join => "JOIN",
+            Self::Inner => "INNER",
+            Self::Left => "LEFT",
+            Self::Right => "RIGHT",
+            Self::Outer => "OUTER",
+            Self::Cross => "CROSS",
+            Self::Explain => "EXPLAIN",
+            Self::Group => "GROUP",
+            Self::By => "BY",
+            Self::Having => "HAVING",
+            Self::Distinct => "DISTINCT",
+            Self::Order => "ORDER",
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+            Self::In => "IN",
+            Self::With => "WITH",
+            Self::Union => "UNION",
+            Self::Intersect => "INTERSECT",
+            Self::Except => "EXCEPT",
+            Self::All => "ALL",
+            Self::Returning => "RETURNING",
+            Self::Conflict => "CONFLICT",
+            Self::Do => "DO",
+            Self::Nothing => "NOTHING",
+            Self::Is => "IS",
+            Self::Between => "BETWEEN",
+            Self::Offset => "OFFSET",
+            Self::Case => "CASE",
+            Self::When => "WHEN",
+            Self::Then => "THEN",
+            Self::Else => "ELSE",
+            Self::End => "END",
+            Self::Cast => "CAST",
+            Self::ILike => "ILIKE",
+            Self::Escape => "ESCAPE",
+            Self::Regexp => "REGEXP",
         })
     }
 }
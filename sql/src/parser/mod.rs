@@ -3,5 +3,5 @@ mod lexer;
 pub mod parser;
 mod sql_parser;
 
-pub use lexer::Lexer;
+pub use lexer::{Lexer, quote_ident};
 pub use parser::Parser;
@@ -1,14 +1,26 @@
 use super::{
     Lexer, ast,
-    lexer::{Keyword, Token},
+    lexer::{Keyword, Span, Token},
 };
 use crate::{
     errinput,
     error::{Error, Result},
+    types::schema::DataType,
 };
 
 pub struct Parser<'a> {
+    input: &'a str,
     pub lexer: std::iter::Peekable<Lexer<'a>>,
+    /// The span of the last consumed token, used to locate errors.
+    span: Option<Span>,
+    /// Whether the lexer errored. Lexer errors are already located.
+    lexer_errored: bool,
+    /// The highest parameter number seen so far. A ? placeholder takes the
+    /// next number after it.
+    parameters: usize,
+    /// Whether parameters are numbered ($n) rather than positional (?), once
+    /// any are seen. The two styles can't be mixed.
+    numbered: Option<bool>,
 }
 
 type Precedence = u8;
@@ -16,24 +28,83 @@ type Precedence = u8;
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Self {
         Self {
+            input,
             lexer: Lexer::new(input).peekable(),
+            span: None,
+            lexer_errored: false,
+            parameters: 0,
+            numbered: None,
         }
     }
 
+    /// Returns the number of parameters in the parsed input, i.e. the
+    /// highest parameter number used.
+    pub fn parameters(&self) -> usize {
+        self.parameters
+    }
+
+    /// Parses a single statement, with an optional trailing semicolon.
     pub fn parse(&mut self) -> Result<ast::Statement> {
+        let result = self.parse_single_statement();
+        result.map_err(|err| self.locate(err))
+    }
+
+    /// Parses a script of semicolon-separated statements, e.g. a migration
+    /// file. Empty statements are ignored.
+    pub fn parse_script(&mut self) -> Result<Vec<ast::Statement>> {
+        let result = self.parse_statements();
+        result.map_err(|err| self.locate(err))
+    }
+
+    fn parse_single_statement(&mut self) -> Result<ast::Statement> {
         let statement = self.parse_statement()?;
-        if self.next_is(Token::Semicolon) {
-            return errinput!("parse met unexpected end of input: ;");
-        }
-        if let Some(token) = self.lexer.next().transpose()? {
-            return errinput!("parse met end of input:{token:?}");
+        self.next_is(Token::Semicolon);
+        if self.peek()?.is_some() {
+            let token = self.next()?;
+            return errinput!("unexpected token {token} after statement");
         }
         Ok(statement)
     }
 
+    fn parse_statements(&mut self) -> Result<Vec<ast::Statement>> {
+        let mut statements = Vec::new();
+        loop {
+            while self.next_is(Token::Semicolon) {}
+            if self.peek()?.is_none() {
+                return Ok(statements);
+            }
+            statements.push(self.parse_statement()?);
+            if self.peek()?.is_some() {
+                self.expect(Token::Semicolon)?;
+            }
+        }
+    }
+
+    /// Annotates an input error with the location of the offending token,
+    /// i.e. the last consumed token, or the next token if none.
+    fn locate(&mut self, err: Error) -> Error {
+        let Error::InvalidInput(message) = err else {
+            return err;
+        };
+        if self.lexer_errored {
+            return Error::InvalidInput(message);
+        }
+        let span = match self.span {
+            Some(span) => span,
+            None => match self.lexer.peek() {
+                Some(Ok((_, span))) => *span,
+                _ => Span::end_of(self.input),
+            },
+        };
+        Error::InvalidInput(span.annotate(self.input, &message))
+    }
+
     pub fn next_ident(&mut self) -> Result<String> {
         match self.next()? {
             Token::Ident(ident) => Ok(ident),
+            Token::Keyword(keyword) if keyword.is_type_name() => {
+                Ok(keyword.to_string().to_lowercase())
+            }
             token => {
                 Error::InvalidInput(format!("next ident met unexpected token:{:?}", token)).into()
             }
@@ -52,26 +123,115 @@ impl<'a> Parser<'a> {
             self.parse_expression_atom()?
         };
 
-        // while let Some(postfix)=self.parse_postfix_operator(min_precedence)?{
-        //     lhs=postfix.build(lhs);
-        // }
-        // Apply any binary infix operators, parsing the right-hand operand.
-        while let Some(infix) = self.parse_infix_operator(min_precedence) {
-            let at_precedence = infix.precedence() + infix.associativity();
-            let rhs = self.parse_expression_at(at_precedence)?;
-            lhs = infix.build(lhs, rhs);
+        // Apply any postfix and binary infix operators, parsing the
+        // right-hand operand of the latter. Postfix operators may also follow
+        // a binary operator, consider e.g. 1 + 2 IN (SELECT ...).
+        loop {
+            if let Some(postfix) = self.parse_postfix_operator(min_precedence)? {
+                lhs = postfix.build(lhs);
+            } else if let Some(infix) = self.parse_infix_operator(min_precedence) {
+                let at_precedence = infix.precedence() + infix.associativity();
+                let rhs = self.parse_expression_at(at_precedence)?;
+                lhs = infix.build(lhs, rhs);
+            } else {
+                break;
+            }
         }
-        // Apply any postfix operators after the binary operator. Consider e.g.
-        // 1 + NULL IS NULL.
-        // while let Some(postfix) = self.parse_postfix_operator(min_precedence)? {
-        //     lhs = postfix.build(lhs)
-        // }
         Ok(lhs)
     }
 
-    // fn parse_postfix_operator(&mut self,min_precedence:Precedence) ->
-    // Result<Option<PostfixOperator>>{    Ok(None)
-    // }
+    /// Parses a postfix operator, if any: ::type, IS [NOT] NULL|NAN,
+    /// [NOT] BETWEEN, [NOT] IN (list) or (SELECT ...), [NOT] LIKE|ILIKE with
+    /// optional ESCAPE, and [NOT] ~|REGEXP.
+    fn parse_postfix_operator(
+        &mut self,
+        min_precedence: Precedence,
+    ) -> Result<Option<PostfixOperator>> {
+        if PostfixOperator::CAST_PRECEDENCE >= min_precedence && self.next_is(Token::DoubleColon) {
+            return Ok(Some(PostfixOperator::Cast(self.parse_data_type()?)));
+        }
+        if PostfixOperator::PRECEDENCE < min_precedence
+            || !matches!(
+                self.peek()?,
+                Some(
+                    Token::Keyword(
+                        Keyword::Is
+                            | Keyword::Between
+                            | Keyword::In
+                            | Keyword::Not
+                            | Keyword::Like
+                            | Keyword::ILike
+                            | Keyword::Regexp
+                    ) | Token::Tilde
+                )
+            )
+        {
+            return Ok(None);
+        }
+        if self.next_is(Keyword::Is.into()) {
+            let not = self.next_is(Keyword::Not.into());
+            let value = match self.next()? {
+                Token::Keyword(Keyword::Null) => ast::Literal::Null,
+                Token::Keyword(Keyword::Nan) => ast::Literal::Float(f64::NAN),
+                token => return errinput!("expected NULL or NAN after IS, found {token}"),
+            };
+            return Ok(Some(PostfixOperator::Is { value, not }));
+        }
+        let not = self.next_is(Keyword::Not.into());
+        if self.next_is(Keyword::Between.into()) {
+            // The bounds bind tighter than AND, e.g. a BETWEEN 1 AND 2 + 1.
+            let at_precedence = PostfixOperator::PRECEDENCE + LEFT_ASSOCIATIVE;
+            let low = self.parse_expression_at(at_precedence)?;
+            self.expect(Keyword::And.into())?;
+            let high = self.parse_expression_at(at_precedence)?;
+            return Ok(Some(PostfixOperator::Between { low, high, not }));
+        }
+        match self.next()? {
+            Token::Keyword(Keyword::In) => {}
+            Token::Keyword(keyword @ (Keyword::Like | Keyword::ILike)) => {
+                // The pattern binds tighter than AND, like BETWEEN bounds.
+                let at_precedence = PostfixOperator::PRECEDENCE + LEFT_ASSOCIATIVE;
+                let pattern = self.parse_expression_at(at_precedence)?;
+                let mut escape = None;
+                if self.next_is(Keyword::Escape.into()) {
+                    escape = Some(self.parse_expression_at(at_precedence)?);
+                }
+                let case_insensitive = keyword == Keyword::ILike;
+                return Ok(Some(PostfixOperator::Like {
+                    pattern,
+                    escape,
+                    case_insensitive,
+                    not,
+                }));
+            }
+            Token::Keyword(Keyword::Regexp) | Token::Tilde => {
+                let at_precedence = PostfixOperator::PRECEDENCE + LEFT_ASSOCIATIVE;
+                let pattern = self.parse_expression_at(at_precedence)?;
+                return Ok(Some(PostfixOperator::Regexp { pattern, not }));
+            }
+            token if not => {
+                return errinput!(
+                    "expected IN, BETWEEN, LIKE, ILIKE or REGEXP after NOT, found {token}"
+                );
+            }
+            token => return errinput!("expected IN, found {token}"),
+        }
+        self.expect(Token::OpenParen)?;
+        if let Some(Token::Keyword(Keyword::Select | Keyword::With)) = self.peek()? {
+            let query = self.parse_select()?;
+            self.expect(Token::CloseParen)?;
+            return Ok(Some(PostfixOperator::InSubquery { query, not }));
+        }
+        let mut list = Vec::new();
+        loop {
+            list.push(self.parse_expression()?);
+            if !self.next_is(Token::Comma) {
+                break;
+            }
+        }
+        self.expect(Token::CloseParen)?;
+        Ok(Some(PostfixOperator::InList { list, not }))
+    }
 
     fn parse_infix_operator(&mut self, min_precedence: Precedence) -> Option<InfixOperator> {
         self.next_if_map(|token| {
@@ -92,7 +252,8 @@ impl<'a> Parser<'a> {
                 Token::Minus => InfixOperator::Subtract,
                 Token::Percent => InfixOperator::Remainder,
                 Token::Exponential => InfixOperator::Exponential,
-                Token::Keyword(Keyword::Like) => InfixOperator::Like,
+                Token::Arrow => InfixOperator::JsonGet,
+                Token::LongArrow => InfixOperator::JsonGetText,
                 _ => return None,
             };
             Some(operator).filter(|op| op.precedence() >= min_precedence)
@@ -112,14 +273,54 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_expression_atom(&mut self) -> Result<ast::Expression> {
-        let token = match self.next()? {
+        // Type names are column references, unless they're typed literals.
+        let next = match self.next()? {
+            Token::Keyword(keyword)
+                if keyword.is_type_name() && !matches!(self.peek()?, Some(Token::String(_))) =>
+            {
+                Token::Ident(keyword.to_string().to_lowercase())
+            }
+            token => token,
+        };
+        let token = match next {
             Token::Asterisk => ast::Expression::All,
             Token::Number(str) if str.chars().all(|c| c.is_ascii_digit()) => {
                 ast::Literal::Integer(str.parse()?).into()
             }
             Token::Number(str) => ast::Literal::Float(str.parse()?).into(),
             Token::String(s) => ast::Literal::String(s).into(),
-            Token::Ident(function_name) if self.next_is(Token::OpenParen) => {
+            Token::Bytes(b) => ast::Literal::Bytes(b).into(),
+            Token::Question => {
+                if *self.numbered.get_or_insert(false) {
+                    return errinput!("can't mix ? and $n parameters");
+                }
+                self.parameters += 1;
+                ast::Expression::Parameter(self.parameters - 1)
+            }
+            Token::Parameter(n) => {
+                if !*self.numbered.get_or_insert(true) {
+                    return errinput!("can't mix ? and $n parameters");
+                }
+                self.parameters = self.parameters.max(n);
+                ast::Expression::Parameter(n - 1)
+            }
+            // Typed literals like DATE '2024-01-01' are casts of the string.
+            Token::Keyword(
+                keyword @ (Keyword::Date | Keyword::Timestamp | Keyword::Interval | Keyword::Json),
+            ) if matches!(self.peek()?, Some(Token::String(_))) => {
+                let Token::String(s) = self.next()? else {
+                    unreachable!()
+                };
+                let data_type = match keyword {
+                    Keyword::Date => DataType::Date,
+                    Keyword::Timestamp => DataType::Timestamp,
+                    Keyword::Interval => DataType::Interval,
+                    _ => DataType::Json,
+                };
+                ast::Expression::Cast(Box::new(ast::Literal::String(s).into()), data_type)
+            }
+            Token::Ident(name) if self.next_is(Token::OpenParen) => {
+                let distinct = self.next_is(Keyword::Distinct.into());
                 let mut args = Vec::new();
                 while !self.next_is(Token::CloseParen) {
                     if !args.is_empty() {
@@ -127,7 +328,11 @@ impl<'a> Parser<'a> {
                     }
                     args.push(self.parse_expression()?);
                 }
-                ast::Expression::Function(function_name, args).into()
+                ast::Expression::Function {
+                    name,
+                    args,
+                    distinct,
+                }
             }
             Token::Ident(table) if self.next_is(Token::Period) => {
                 let column = self.next_ident()?;
@@ -140,6 +345,55 @@ impl<'a> Parser<'a> {
             Token::Keyword(Keyword::False) => ast::Literal::Boolean(false).into(),
             Token::Keyword(Keyword::Infinity) => ast::Literal::Float(f64::INFINITY).into(),
             Token::Keyword(Keyword::Nan) => ast::Literal::Float(f64::NAN).into(),
+            Token::OpenParen
+                if matches!(
+                    self.peek()?,
+                    Some(Token::Keyword(Keyword::Select | Keyword::With))
+                ) =>
+            {
+                let query = self.parse_select()?;
+                self.expect(Token::CloseParen)?;
+                ast::Expression::Subquery(Box::new(query))
+            }
+            Token::Keyword(Keyword::Case) => {
+                let mut operand = None;
+                if !self.peek()?.is_some_and(|t| *t == Keyword::When.into()) {
+                    operand = Some(Box::new(self.parse_expression()?));
+                }
+                let mut when = Vec::new();
+                while self.next_is(Keyword::When.into()) {
+                    let condition = self.parse_expression()?;
+                    self.expect(Keyword::Then.into())?;
+                    when.push((condition, self.parse_expression()?));
+                }
+                if when.is_empty() {
+                    return errinput!("expected WHEN, found {}", self.next()?);
+                }
+                let mut r#else = None;
+                if self.next_is(Keyword::Else.into()) {
+                    r#else = Some(Box::new(self.parse_expression()?));
+                }
+                self.expect(Keyword::End.into())?;
+                ast::Expression::Case {
+                    operand,
+                    when,
+                    r#else,
+                }
+            }
+            Token::Keyword(Keyword::Cast) => {
+                self.expect(Token::OpenParen)?;
+                let expr = self.parse_expression()?;
+                self.expect(Keyword::As.into())?;
+                let data_type = self.parse_data_type()?;
+                self.expect(Token::CloseParen)?;
+                ast::Expression::Cast(Box::new(expr), data_type)
+            }
+            Token::Keyword(Keyword::Exists) => {
+                self.expect(Token::OpenParen)?;
+                let query = self.parse_select()?;
+                self.expect(Token::CloseParen)?;
+                ast::Expression::Exists(Box::new(query))
+            }
             Token::OpenParen => {
                 let expr = self.parse_expression()?;
                 self.expect(Token::CloseParen)?;
@@ -183,17 +437,31 @@ impl<'a> Parser<'a> {
     }
 
     pub fn next(&mut self) -> Result<Token> {
-        self.lexer
-            .next()
-            .transpose()?
-            .ok_or_else(|| errinput!("unexpected end of input"))
+        match self.lexer.next() {
+            Some(Ok((token, span))) => {
+                self.span = Some(span);
+                Ok(token)
+            }
+            Some(Err(err)) => {
+                self.lexer_errored = true;
+                Err(err)
+            }
+            None => {
+                self.span = Some(Span::end_of(self.input));
+                errinput!("unexpected end of input")
+            }
+        }
     }
 
     pub fn peek(&mut self) -> Result<Option<&Token>> {
-        self.lexer
-            .peek()
-            .map(|r| r.as_ref().map_err(|err| err.clone()))
-            .transpose()
+        match self.lexer.peek() {
+            Some(Ok((token, _))) => Ok(Some(token)),
+            Some(Err(err)) => {
+                self.lexer_errored = true;
+                Err(err.clone())
+            }
+            None => Ok(None),
+        }
     }
 }
 
@@ -229,6 +497,81 @@ impl PrefixOperator {
     }
 }
 
+/// Postfix operators. NOT variants are built as NOT of the operator.
+enum PostfixOperator {
+    Cast(DataType), // a::type
+    Is {
+        value: ast::Literal,
+        not: bool,
+    }, // a IS [NOT] NULL|NAN
+    Between {
+        low: ast::Expression,
+        high: ast::Expression,
+        not: bool,
+    }, // a [NOT] BETWEEN b AND c
+    InList {
+        list: Vec<ast::Expression>,
+        not: bool,
+    }, // a [NOT] IN (b, c)
+    InSubquery {
+        query: ast::Statement,
+        not: bool,
+    }, // a [NOT] IN (SELECT ...)
+    Like {
+        pattern: ast::Expression,
+        escape: Option<ast::Expression>,
+        case_insensitive: bool,
+        not: bool,
+    }, // a [NOT] [I]LIKE b [ESCAPE c]
+    Regexp {
+        pattern: ast::Expression,
+        not: bool,
+    }, // a [NOT] ~ b
+}
+
+impl PostfixOperator {
+    /// The precedence of all postfix operators, same as for equality.
+    const PRECEDENCE: Precedence = 4;
+    /// The precedence of ::, which binds tighter than any other operator.
+    const CAST_PRECEDENCE: Precedence = 11;
+
+    fn build(self, lhs: ast::Expression) -> ast::Expression {
+        let lhs = Box::new(lhs);
+        let (expr, not) = match self {
+            Self::Cast(data_type) => (ast::Expression::Cast(lhs, data_type), false),
+            Self::Is { value, not } => (ast::Operator::Is(lhs, value).into(), not),
+            Self::Between { low, high, not } => (
+                ast::Operator::Between(lhs, Box::new(low), Box::new(high)).into(),
+                not,
+            ),
+            Self::InList { list, not } => (ast::Operator::InList(lhs, list).into(), not),
+            Self::InSubquery { query, not } => {
+                (ast::Expression::InSubquery(lhs, Box::new(query)), not)
+            }
+            Self::Like {
+                pattern,
+                escape,
+                case_insensitive,
+                not,
+            } => {
+                let (pattern, escape) = (Box::new(pattern), escape.map(Box::new));
+                let expr = match case_insensitive {
+                    true => ast::Operator::ILike(lhs, pattern, escape),
+                    false => ast::Operator::Like(lhs, pattern, escape),
+                };
+                (expr.into(), not)
+            }
+            Self::Regexp { pattern, not } => {
+                (ast::Operator::Regexp(lhs, Box::new(pattern)).into(), not)
+            }
+        };
+        match not {
+            true => ast::Operator::Not(Box::new(expr)).into(),
+            false => expr,
+        }
+    }
+}
+
 enum InfixOperator {
     And,                // a AND b
     Or,                 // a OR b
@@ -244,8 +587,8 @@ enum InfixOperator {
     Divide,             // a / b
     Remainder,          // a % b
     Exponential,        // a ^ b
-
-    Like, // a LIKE b
+    JsonGet,            // a -> b
+    JsonGetText,        // a ->> b
 }
 
 impl InfixOperator {
@@ -253,7 +596,7 @@ impl InfixOperator {
         match self {
             Self::Or => 1,
             Self::And => 2,
-            Self::Equal | Self::NotEqual | Self::Like => 4,
+            Self::Equal | Self::NotEqual => 4,
             Self::GreaterThan
             | Self::LessThan
             | Self::GreaterThanOrEqual
@@ -261,6 +604,7 @@ impl InfixOperator {
             Self::Add | Self::Subtract => 6,
             Self::Multiply | Self::Divide | Self::Remainder => 7,
             Self::Exponential => 8,
+            Self::JsonGet | Self::JsonGetText => 9,
         }
     }
 
@@ -288,8 +632,8 @@ impl InfixOperator {
             Self::Divide => ast::Operator::Divide(Box::new(lhs), Box::new(rhs)).into(),
             Self::Remainder => ast::Operator::Remainder(Box::new(lhs), Box::new(rhs)).into(),
             Self::Exponential => ast::Operator::Exponential(Box::new(lhs), Box::new(rhs)).into(),
-
-            Self::Like => ast::Operator::Like(Box::new(lhs), Box::new(rhs)).into(),
+            Self::JsonGet => ast::Operator::JsonGet(Box::new(lhs), Box::new(rhs)).into(),
+            Self::JsonGetText => ast::Operator::JsonGetText(Box::new(lhs), Box::new(rhs)).into(),
         }
     }
 }
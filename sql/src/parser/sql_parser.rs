@@ -3,7 +3,11 @@ use super::{
     ast::{self, Column},
     lexer::{Keyword, Token},
 };
-use crate::{errinput, error::Result, types::schema::DataType};
+use crate::{
+    errinput,
+    error::Result,
+    types::{decimal::MAX_PRECISION, schema::DataType},
+};
 
 impl<'a> Parser<'a> {
     pub fn parse_statement(&mut self) -> Result<ast::Statement> {
@@ -11,25 +15,175 @@ impl<'a> Parser<'a> {
             return errinput!("Unexpected end of input");
         };
         match token {
-            Token::Keyword(Keyword::Create) => self.parse_create_table(),
-            Token::Keyword(Keyword::Select) => self.parse_select(),
+            Token::Keyword(Keyword::Explain) => self.parse_explain(),
+            Token::Keyword(Keyword::Create) => self.parse_create(),
+            Token::Keyword(Keyword::Drop) => self.parse_drop_table(),
+            Token::Keyword(Keyword::Select | Keyword::With) => self.parse_select(),
             Token::Keyword(Keyword::Insert) => self.parse_insert(),
-            token => errinput!("parse statement met unexpected end of input:{token:?}"),
+            Token::Keyword(Keyword::Update) => self.parse_update(),
+            Token::Keyword(Keyword::Delete) => self.parse_delete(),
+            Token::Keyword(Keyword::Show) => self.parse_show(),
+            Token::Keyword(Keyword::Describe) => self.parse_describe(),
+            _ => {
+                let token = self.next()?;
+                errinput!("expected statement, found {token}")
+            }
+        }
+    }
+
+    /// Parses a SELECT query, which may combine several SELECT blocks with
+    /// set operators. The WITH, ORDER BY, LIMIT and OFFSET clauses apply to
+    /// the entire query.
+    pub(super) fn parse_select(&mut self) -> Result<ast::Statement> {
+        let with_clause = self.parse_with_clause()?;
+        let mut query = self.parse_set_operation(0)?;
+        let (ast::Statement::Select {
+            with,
+            order_by,
+            limit,
+            offset,
+            ..
+        }
+        | ast::Statement::SetOperation {
+            with,
+            order_by,
+            limit,
+            offset,
+            ..
+        }) = &mut query
+        else {
+            unreachable!("unexpected query {query:?}");
+        };
+        *with = with_clause;
+        *order_by = self.parse_order_by_clause()?;
+        *limit = self
+            .next_is(Keyword::Limit.into())
+            .then(|| self.parse_expression())
+            .transpose()?;
+        *offset = self
+            .next_is(Keyword::Offset.into())
+            .then(|| self.parse_expression())
+            .transpose()?;
+        Ok(query)
+    }
+
+    /// Parses SELECT blocks combined with set operators, using precedence
+    /// climbing like for expressions. INTERSECT binds tighter than UNION and
+    /// EXCEPT, which are left-associative.
+    fn parse_set_operation(&mut self, min_precedence: u8) -> Result<ast::Statement> {
+        let mut lhs = self.parse_select_block()?;
+        while let Some((operator, all)) = self.parse_set_operator(min_precedence)? {
+            let precedence = Self::set_operator_precedence(&operator);
+            let rhs = self.parse_set_operation(precedence + 1)?;
+            lhs = ast::Statement::SetOperation {
+                with: Vec::new(),
+                operator,
+                all,
+                left: Box::new(lhs),
+                right: Box::new(rhs),
+                order_by: Vec::new(),
+                limit: None,
+                offset: None,
+            };
+        }
+        Ok(lhs)
+    }
+
+    /// Parses a set operator with at least the given precedence, if any,
+    /// followed by an optional ALL or DISTINCT.
+    fn parse_set_operator(
+        &mut self,
+        min_precedence: u8,
+    ) -> Result<Option<(ast::SetOperator, bool)>> {
+        let operator = match self.peek()? {
+            Some(Token::Keyword(Keyword::Union)) => ast::SetOperator::Union,
+            Some(Token::Keyword(Keyword::Intersect)) => ast::SetOperator::Intersect,
+            Some(Token::Keyword(Keyword::Except)) => ast::SetOperator::Except,
+            _ => return Ok(None),
+        };
+        if Self::set_operator_precedence(&operator) < min_precedence {
+            return Ok(None);
+        }
+        let keyword = self.next()?;
+        let all = self.next_is(Keyword::All.into());
+        if !all {
+            self.next_is(Keyword::Distinct.into());
+        }
+        let Some(Token::Keyword(Keyword::Select)) = self.peek()? else {
+            return errinput!("expected SELECT after {keyword}");
+        };
+        Ok(Some((operator, all)))
+    }
+
+    fn set_operator_precedence(operator: &ast::SetOperator) -> u8 {
+        match operator {
+            ast::SetOperator::Union | ast::SetOperator::Except => 1,
+            ast::SetOperator::Intersect => 2,
         }
     }
 
-    fn parse_select(&mut self) -> Result<ast::Statement> {
+    /// Parses a single SELECT block, without WITH, ORDER BY, LIMIT or OFFSET.
+    fn parse_select_block(&mut self) -> Result<ast::Statement> {
+        let (distinct, select) = self.parse_select_clause()?;
         Ok(ast::Statement::Select {
-            select: self.parse_select_clause()?,
+            with: Vec::new(),
+            distinct,
+            select,
             from: self.parse_from_clause()?,
             r#where: self.parse_where_clause()?,
-            limit: self
-                .next_is(Keyword::Limit.into())
-                .then(|| self.parse_expression())
-                .transpose()?,
+            group_by: self.parse_group_by_clause()?,
+            having: self.parse_having_clause()?,
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
         })
     }
 
+    fn parse_explain(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Explain.into())?;
+        if self.next_is(Keyword::Explain.into()) {
+            return errinput!("can't nest EXPLAIN statements");
+        }
+        Ok(ast::Statement::Explain(Box::new(self.parse_statement()?)))
+    }
+
+    fn parse_show(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Show.into())?;
+        match self.next()? {
+            Token::Keyword(Keyword::Tables) => Ok(ast::Statement::ShowTables),
+            Token::Keyword(Keyword::Table) => Ok(ast::Statement::ShowTable(self.next_ident()?)),
+            token => errinput!("expected TABLES or TABLE after SHOW, found {token}"),
+        }
+    }
+
+    fn parse_describe(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Describe.into())?;
+        Ok(ast::Statement::ShowTable(self.next_ident()?))
+    }
+
+    /// Parses a WITH clause of common table expressions, if any, which must be
+    /// followed by a SELECT clause.
+    fn parse_with_clause(&mut self) -> Result<Vec<(ast::TableName, ast::Statement)>> {
+        if !self.next_is(Keyword::With.into()) {
+            return Ok(Vec::new());
+        }
+        let mut with = Vec::new();
+        loop {
+            let name = self.next_ident()?;
+            self.expect(Keyword::As.into())?;
+            self.expect(Token::OpenParen)?;
+            with.push((name, self.parse_select()?));
+            self.expect(Token::CloseParen)?;
+            if !self.next_is(Token::Comma) {
+                break;
+            }
+        }
+        let Some(Token::Keyword(Keyword::Select)) = self.peek()? else {
+            return errinput!("expected SELECT after WITH clause");
+        };
+        Ok(with)
+    }
+
     fn parse_where_clause(&mut self) -> Result<Option<ast::Expression>> {
         if !self.next_is(Keyword::Where.into()) {
             return Ok(None);
@@ -37,14 +191,73 @@ impl<'a> Parser<'a> {
         Ok(Some(self.parse_expression()?))
     }
 
+    fn parse_group_by_clause(&mut self) -> Result<Vec<ast::Expression>> {
+        if !self.next_is(Keyword::Group.into()) {
+            return Ok(Vec::new());
+        }
+        self.expect(Keyword::By.into())?;
+        let mut group_by = Vec::new();
+        loop {
+            group_by.push(self.parse_expression()?);
+            if !self.next_is(Token::Comma) {
+                break;
+            }
+        }
+        Ok(group_by)
+    }
+
+    fn parse_having_clause(&mut self) -> Result<Option<ast::Expression>> {
+        if !self.next_is(Keyword::Having.into()) {
+            return Ok(None);
+        }
+        Ok(Some(self.parse_expression()?))
+    }
+
+    fn parse_order_by_clause(&mut self) -> Result<Vec<(ast::Expression, ast::Direction)>> {
+        if !self.next_is(Keyword::Order.into()) {
+            return Ok(Vec::new());
+        }
+        self.expect(Keyword::By.into())?;
+        let mut order_by = Vec::new();
+        loop {
+            let expr = self.parse_expression()?;
+            let direction = match self.next_is(Keyword::Desc.into()) {
+                true => ast::Direction::Descending,
+                false => {
+                    self.next_is(Keyword::Asc.into());
+                    ast::Direction::Ascending
+                }
+            };
+            order_by.push((expr, direction));
+            if !self.next_is(Token::Comma) {
+                break;
+            }
+        }
+        Ok(order_by)
+    }
+
     fn parse_from_clause(&mut self) -> Result<Vec<ast::From>> {
         if !self.next_is(Keyword::From.into()) {
             return Ok(Vec::new());
         }
         let mut from = Vec::new();
         loop {
-            let table = self.parse_from_table()?;
-            from.push(table);
+            let mut item = self.parse_from_table()?;
+            while let Some(r#type) = self.parse_from_join()? {
+                let right = self.parse_from_table()?;
+                let mut predicate = None;
+                if r#type != ast::JoinType::Cross {
+                    self.expect(Keyword::On.into())?;
+                    predicate = Some(self.parse_expression()?);
+                }
+                item = ast::From::Join {
+                    left: Box::new(item),
+                    right: Box::new(right),
+                    r#type,
+                    predicate,
+                };
+            }
+            from.push(item);
             if !self.next_is(Token::Comma) {
                 break;
             }
@@ -52,7 +265,39 @@ impl<'a> Parser<'a> {
         Ok(from)
     }
 
+    /// Parses a join keyword sequence, if any: CROSS JOIN, [INNER] JOIN,
+    /// LEFT [OUTER] JOIN or RIGHT [OUTER] JOIN.
+    fn parse_from_join(&mut self) -> Result<Option<ast::JoinType>> {
+        let r#type = if self.next_is(Keyword::Join.into()) {
+            return Ok(Some(ast::JoinType::Inner));
+        } else if self.next_is(Keyword::Cross.into()) {
+            ast::JoinType::Cross
+        } else if self.next_is(Keyword::Inner.into()) {
+            ast::JoinType::Inner
+        } else if self.next_is(Keyword::Left.into()) {
+            self.next_is(Keyword::Outer.into());
+            ast::JoinType::Left
+        } else if self.next_is(Keyword::Right.into()) {
+            self.next_is(Keyword::Outer.into());
+            ast::JoinType::Right
+        } else {
+            return Ok(None);
+        };
+        self.expect(Keyword::Join.into())?;
+        Ok(Some(r#type))
+    }
+
     fn parse_from_table(&mut self) -> Result<ast::From> {
+        if self.next_is(Token::OpenParen) {
+            let query = Box::new(self.parse_select()?);
+            self.expect(Token::CloseParen)?;
+            self.next_is(Keyword::As.into());
+            let Some(Token::Ident(_)) = self.peek()? else {
+                return errinput!("subquery in FROM must have an alias");
+            };
+            let alias = self.next_ident()?;
+            return Ok(ast::From::Subquery { query, alias });
+        }
         let name = self.next_ident()?;
         let mut alias = None;
         if self.next_is(Keyword::As.into()) || matches!(self.peek()?, Some(Token::Ident(_))) {
@@ -61,10 +306,40 @@ impl<'a> Parser<'a> {
         Ok(ast::From::Table { name, alias })
     }
 
-    fn parse_select_clause(&mut self) -> Result<Vec<(ast::Expression, Option<String>)>> {
+    /// Parses the SELECT clause, returning any DISTINCT [ON] expressions and
+    /// the selected expressions with their aliases.
+    #[allow(clippy::type_complexity)]
+    fn parse_select_clause(
+        &mut self,
+    ) -> Result<(
+        Option<Vec<ast::Expression>>,
+        Vec<(ast::Expression, Option<String>)>,
+    )> {
         if !self.next_is(Keyword::Select.into()) {
-            return Ok(Vec::new());
+            return Ok((None, Vec::new()));
+        }
+        let mut distinct = None;
+        if self.next_is(Keyword::Distinct.into()) {
+            let on = distinct.insert(Vec::new());
+            if self.next_is(Keyword::On.into()) {
+                self.expect(Token::OpenParen)?;
+                loop {
+                    on.push(self.parse_expression()?);
+                    if !self.next_is(Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(Token::CloseParen)?;
+            }
+        } else {
+            self.next_is(Keyword::All.into());
         }
+        Ok((distinct, self.parse_select_list()?))
+    }
+
+    /// Parses a list of expressions with optional aliases, for SELECT and
+    /// RETURNING clauses.
+    fn parse_select_list(&mut self) -> Result<Vec<(ast::Expression, Option<String>)>> {
         let mut select = vec![];
         loop {
             let expr = self.parse_expression()?;
@@ -83,6 +358,13 @@ impl<'a> Parser<'a> {
         Ok(select)
     }
 
+    fn parse_returning_clause(&mut self) -> Result<Vec<(ast::Expression, Option<String>)>> {
+        if !self.next_is(Keyword::Returning.into()) {
+            return Ok(Vec::new());
+        }
+        self.parse_select_list()
+    }
+
     fn parse_insert(&mut self) -> Result<ast::Statement> {
         self.expect(Keyword::Insert.into())?;
         self.expect(Keyword::Into.into())?;
@@ -99,8 +381,23 @@ impl<'a> Parser<'a> {
             self.expect(Token::CloseParen.into())?;
         }
 
-        self.expect(Keyword::Values.into())?;
+        let source = if let Some(Token::Keyword(Keyword::Select | Keyword::With)) = self.peek()? {
+            ast::InsertSource::Select(Box::new(self.parse_select()?))
+        } else {
+            self.expect(Keyword::Values.into())?;
+            ast::InsertSource::Values(self.parse_values()?)
+        };
 
+        Ok(ast::Statement::Insert {
+            table_name,
+            columns,
+            source,
+            on_conflict: self.parse_on_conflict_clause()?,
+            returning: self.parse_returning_clause()?,
+        })
+    }
+
+    fn parse_values(&mut self) -> Result<Vec<Vec<ast::Expression>>> {
         let mut values = Vec::new();
         loop {
             let mut rows = Vec::new();
@@ -117,17 +414,112 @@ impl<'a> Parser<'a> {
                 break;
             }
         }
+        Ok(values)
+    }
 
-        Ok(ast::Statement::Insert {
+    fn parse_on_conflict_clause(&mut self) -> Result<Option<ast::OnConflict>> {
+        if !self.next_is(Keyword::On.into()) {
+            return Ok(None);
+        }
+        self.expect(Keyword::Conflict.into())?;
+        let mut target = None;
+        if self.next_is(Token::OpenParen) {
+            target = Some(self.next_ident()?);
+            self.expect(Token::CloseParen)?;
+        }
+        self.expect(Keyword::Do.into())?;
+        if self.next_is(Keyword::Nothing.into()) {
+            return Ok(Some(ast::OnConflict {
+                target,
+                set: Vec::new(),
+            }));
+        }
+        self.expect(Keyword::Update.into())?;
+        if target.is_none() {
+            return errinput!("ON CONFLICT DO UPDATE requires a conflict target column");
+        }
+        Ok(Some(ast::OnConflict {
+            target,
+            set: self.parse_set_clause()?,
+        }))
+    }
+
+    fn parse_update(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Update.into())?;
+        let table_name = self.next_ident()?;
+        Ok(ast::Statement::Update {
             table_name,
-            columns,
-            values,
+            set: self.parse_set_clause()?,
+            r#where: self.parse_where_clause()?,
+            returning: self.parse_returning_clause()?,
         })
     }
 
-    fn parse_create_table(&mut self) -> Result<ast::Statement> {
+    fn parse_set_clause(&mut self) -> Result<Vec<(ast::ColumnName, ast::Expression)>> {
+        self.expect(Keyword::Set.into())?;
+        let mut set = Vec::new();
+        loop {
+            let column = self.next_ident()?;
+            self.expect(Token::Equal)?;
+            set.push((column, self.parse_expression()?));
+            if !self.next_is(Token::Comma) {
+                break;
+            }
+        }
+        Ok(set)
+    }
+
+    fn parse_delete(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Delete.into())?;
+        self.expect(Keyword::From.into())?;
+        Ok(ast::Statement::Delete {
+            table_name: self.next_ident()?,
+            r#where: self.parse_where_clause()?,
+            returning: self.parse_returning_clause()?,
+        })
+    }
+
+    fn parse_create(&mut self) -> Result<ast::Statement> {
         self.expect(Keyword::Create.into())?;
+        match self.next()? {
+            Token::Keyword(Keyword::Table) => self.parse_create_table(),
+            Token::Keyword(Keyword::Index) => self.parse_create_index(),
+            token => errinput!("expected TABLE or INDEX after CREATE, found {token}"),
+        }
+    }
+
+    fn parse_drop_table(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Drop.into())?;
         self.expect(Keyword::Table.into())?;
+        let mut if_exists = false;
+        if self.next_is(Keyword::If.into()) {
+            self.expect(Keyword::Exists.into())?;
+            if_exists = true;
+        }
+        Ok(ast::Statement::DropTable {
+            table_name: self.next_ident()?,
+            if_exists,
+        })
+    }
+
+    fn parse_create_index(&mut self) -> Result<ast::Statement> {
+        // An index name is allowed but ignored, since indexes are identified
+        // by their column.
+        if !self.next_is(Keyword::On.into()) {
+            self.next_ident()?;
+            self.expect(Keyword::On.into())?;
+        }
+        let table_name = self.next_ident()?;
+        self.expect(Token::OpenParen)?;
+        let column_name = self.next_ident()?;
+        self.expect(Token::CloseParen)?;
+        Ok(ast::Statement::CreateIndex {
+            table_name,
+            column_name,
+        })
+    }
+
+    fn parse_create_table(&mut self) -> Result<ast::Statement> {
         let table_name = self.next_ident()?;
         self.expect(Token::OpenParen)?;
         let mut columns = Vec::new();
@@ -145,22 +537,66 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn parse_column(&mut self) -> Result<Column> {
-        let column_name = self.next_ident()?;
-        let datatype = match self.next()? {
+    /// Parses a data type, e.g. for a column definition or CAST.
+    pub(super) fn parse_data_type(&mut self) -> Result<DataType> {
+        Ok(match self.next()? {
             Token::Keyword(Keyword::Bool | Keyword::Boolean) => DataType::Boolean,
             Token::Keyword(Keyword::Int | Keyword::Integer) => DataType::Integer,
             Token::Keyword(Keyword::Float | Keyword::Double) => DataType::Float,
-            Token::Keyword(Keyword::String) => DataType::String,
-            token => return errinput!("parse column met unexpected token:{token:?}"),
-        };
+            Token::Keyword(Keyword::String | Keyword::Text) => DataType::String,
+            Token::Keyword(Keyword::Date) => DataType::Date,
+            Token::Keyword(Keyword::Timestamp) => DataType::Timestamp,
+            Token::Keyword(Keyword::Interval) => DataType::Interval,
+            Token::Keyword(Keyword::Bytes) => DataType::Bytes,
+            Token::Keyword(Keyword::Json) => DataType::Json,
+            Token::Keyword(Keyword::Decimal | Keyword::Numeric) => {
+                let (mut precision, mut scale) = (MAX_PRECISION, 0);
+                if self.next_is(Token::OpenParen) {
+                    precision = self.parse_decimal_param()?;
+                    if self.next_is(Token::Comma) {
+                        scale = self.parse_decimal_param()?;
+                    }
+                    self.expect(Token::CloseParen)?;
+                }
+                if !(1..=MAX_PRECISION).contains(&precision) {
+                    return errinput!(
+                        "DECIMAL precision must be between 1 and {MAX_PRECISION}, found {precision}"
+                    );
+                }
+                if scale > precision {
+                    return errinput!(
+                        "DECIMAL scale must be at most the precision {precision}, found {scale}"
+                    );
+                }
+                DataType::Decimal(precision, scale)
+            }
+            token => return errinput!("expected data type, found {token}"),
+        })
+    }
+
+    /// Parses a DECIMAL precision or scale.
+    fn parse_decimal_param(&mut self) -> Result<u8> {
+        match self.next()? {
+            Token::Number(n) => match n.parse() {
+                Ok(n) => Ok(n),
+                Err(_) => errinput!("invalid DECIMAL parameter {n}"),
+            },
+            token => errinput!("expected number, found {token}"),
+        }
+    }
+
+    fn parse_column(&mut self) -> Result<Column> {
+        let column_name = self.next_ident()?;
+        let datatype = self.parse_data_type()?;
         let mut column = Column {
             name: column_name,
             datatype,
             nullable: None,
             default: None,
             unique: false,
+            index: false,
             primary_key: false,
+            references: None,
         };
         while let Some(keyword) = self.next_if_keyword() {
             match keyword {
@@ -177,6 +613,12 @@ impl<'a> Parser<'a> {
                 Keyword::Unique => {
                     column.unique = true;
                 }
+                Keyword::Index => {
+                    column.index = true;
+                }
+                Keyword::References => {
+                    column.references = Some(self.next_ident()?);
+                }
                 Keyword::Not => {
                     self.expect(Keyword::Null.into())?;
                     if column.nullable.is_some() {
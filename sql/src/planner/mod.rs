@@ -1,7 +1,9 @@
 mod optimizer;
 mod plan;
 mod planner;
+mod typecheck;
 
 pub use optimizer::OPTIMIZERS;
-pub use plan::{Direction, Node, Plan};
+pub use plan::{Aggregate, Direction, Node, OnConflict, Plan, Returning};
 pub use planner::{Planner, Scope};
+pub use typecheck::check_node;
@@ -1,5 +1,6 @@
 use super::Node;
 use crate::{
+    errinput,
     error::Result,
     types::{expression::Expression, value::Value},
 };
@@ -9,19 +10,48 @@ pub type Optimizer = fn(Node) -> Result<Node>;
 pub static OPTIMIZERS: &[(&str, Optimizer)] = &[
     ("Constant folding", fold_constants),
     ("Filter pushdown", push_filter),
+    ("Index lookup", index_lookup),
+    ("Join type", join_type),
     ("Short circuit", short_circuit),
 ];
 
+/// Optimizes the node by applying all optimizers in order. Subqueries are
+/// optimized first, as separate nodes.
+pub fn optimize(node: Node) -> Result<Node> {
+    let node = node.transform(
+        &|node| {
+            node.transform_expressions(
+                &|expr| {
+                    Ok(match expr {
+                        Expression::Subquery(node) => Expression::Subquery(optimize(*node)?.into()),
+                        Expression::Exists(node) => Expression::Exists(optimize(*node)?.into()),
+                        Expression::InSubquery(expr, node) => {
+                            Expression::InSubquery(expr, optimize(*node)?.into())
+                        }
+                        expr => expr,
+                    })
+                },
+                &Ok,
+            )
+        },
+        &Ok,
+    )?;
+    OPTIMIZERS
+        .iter()
+        .try_fold(node, |node, (_, optimizer)| optimizer(node))
+}
+
 pub fn short_circuit(node: Node) -> Result<Node> {
     use Expression::*;
     use Value::*;
-    fn nothing(node: &Node) -> Node {
+    fn nothing(node: &Node) -> Result<Node> {
         let columns = (0..node.columns()).map(|i| node.column_label(i)).collect();
-        Node::Nothing { columns }
+        let types = node.column_types()?;
+        Ok(Node::Nothing { columns, types })
     }
 
     let xform = |node| {
-        match node {
+        Ok(match node {
             Node::Filter {
                 source,
                 predicate: Constant(Boolean(true)),
@@ -30,34 +60,101 @@ pub fn short_circuit(node: Node) -> Result<Node> {
             // select * from t where true
             Node::Scan {
                 table,
+                alias,
                 filter: Some(Constant(Boolean(true))),
             } => Node::Scan {
                 table,
+                alias,
                 filter: None,
             },
+
+            Node::NestedLoopJoin {
+                left,
+                right,
+                predicate: Some(Constant(Boolean(true))),
+                outer,
+            } => Node::NestedLoopJoin {
+                left,
+                right,
+                predicate: None,
+                outer,
+            },
             ref node @ Node::Filter {
                 predicate: Constant(Boolean(false) | Null),
                 ..
-            } => nothing(node),
+            } => nothing(node)?,
             // select * from t limit 0
-            ref node @ Node::Limit { limit: 0, .. } => nothing(node),
+            ref node @ Node::Limit { limit: 0, .. } => nothing(node)?,
             // select * from t where false
             ref node @ Node::Scan {
                 filter: Some(Constant(Boolean(false) | Null)),
                 ..
-            } => nothing(node),
+            } => nothing(node)?,
+            ref node @ Node::KeyLookup { ref keys, .. } if keys.is_empty() => nothing(node)?,
+            ref node @ Node::IndexLookup { ref values, .. } if values.is_empty() => nothing(node)?,
             node => node,
-        }
+        })
     };
-    node.transform(&Ok, &|node| Ok(xform(node)))
+    node.transform(&Ok, &xform)
 }
 
 pub fn fold_constants(node: Node) -> Result<Node> {
     use Expression::*;
     use Value::*;
 
+    // Outer columns and subqueries are only known during execution, and
+    // parameters once bound.
+    let is_constant = |expr: &Expression| {
+        !expr.contains(&|expr| {
+            matches!(
+                expr,
+                Column(_)
+                    | OuterColumn(..)
+                    | Parameter(_)
+                    | Subquery(_)
+                    | Exists(_)
+                    | InSubquery(..)
+            )
+        })
+    };
+
+    // Prunes leading CASE branches with constant conditions, before folding
+    // the branches themselves, such that results that are never chosen aren't
+    // evaluated, e.g. CASE WHEN FALSE THEN 1 / 0 ELSE a END.
+    let prune = |expr: Expression| {
+        let Case(operand, when, r#else) = expr else {
+            return Ok(expr);
+        };
+        let operand = match operand {
+            Some(operand) if !is_constant(&operand) => {
+                return Ok(Case(Some(operand), when, r#else));
+            }
+            Some(operand) => Some(operand.evaluate(None)?),
+            None => None,
+        };
+        let mut when = when.into_iter().peekable();
+        while let Some((condition, result)) = when.next_if(|(condition, _)| is_constant(condition))
+        {
+            let value = condition.evaluate(None)?;
+            let matched = match &operand {
+                Some(operand) => operand.checked_eq(&value)?,
+                None => value,
+            };
+            match matched {
+                Boolean(true) => return Ok(result),
+                Boolean(false) | Null => {}
+                value => return errinput!("CASE condition must be boolean, found {value}"),
+            }
+        }
+        let when: Vec<_> = when.collect();
+        if when.is_empty() {
+            return Ok(*r#else);
+        }
+        Ok(Case(operand.map(|v| Box::new(Constant(v))), when, r#else))
+    };
+
     let xform = |mut expr: Expression| {
-        if !expr.contains(&|expr| matches!(expr, Column(_))) {
+        if is_constant(&expr) {
             return expr.evaluate(None).map(Constant);
         }
         expr = match expr {
@@ -75,13 +172,19 @@ pub fn fold_constants(node: Node) -> Result<Node> {
                 (Constant(Boolean(false)), expr) | (expr, Constant(Boolean(false))) => expr,
                 (lhs, rhs) => Or(lhs.into(), rhs.into()),
             },
+            // Compile patterns that were folded into constants.
+            Match(lhs, rhs, kind, None) if matches!(*rhs, Constant(String(_))) => {
+                Expression::pattern_match(*lhs, *rhs, kind)?
+            }
             _ => expr,
         };
         Ok(expr)
     };
-    node.transform(&|node| node.transform_expressions(&Ok, &xform), &Ok)
+    node.transform(&|node| node.transform_expressions(&prune, &xform), &Ok)
 }
 
+/// Pushes filter predicates down into scans and inner join predicates, and
+/// join predicate clauses that only reference one side down into that side.
 pub fn push_filter(node: Node) -> Result<Node> {
     fn push_into(expr: Expression, target: &mut Node) -> Option<Expression> {
         match target {
@@ -91,6 +194,18 @@ pub fn push_filter(node: Node) -> Result<Node> {
                     None => Some(expr),
                 }
             }
+            // Filters can't be pushed into outer joins, since they must also
+            // apply to the NULL-padded rows.
+            Node::NestedLoopJoin {
+                predicate,
+                outer: false,
+                ..
+            } => {
+                *predicate = match predicate.take() {
+                    Some(predicate) => Some(Expression::And(expr.into(), predicate.into())),
+                    None => Some(expr),
+                }
+            }
             _ => return Some(expr),
         }
         None
@@ -104,13 +219,67 @@ pub fn push_filter(node: Node) -> Result<Node> {
         else {
             return node;
         };
-        if let Some(predicate) = push_into(predicate, &mut source) {
+        // Clauses with subqueries are executed by the filter, and must remain.
+        let (subqueries, cnf): (Vec<_>, Vec<_>) = predicate
+            .into_cnf_vec()
+            .into_iter()
+            .partition(|expr| expr.has_subqueries());
+        let mut remaining = subqueries;
+        if let Some(expr) = Expression::and_vec(cnf) {
+            remaining.extend(push_into(expr, &mut source));
+        }
+        if let Some(predicate) = Expression::and_vec(remaining) {
             return Node::Filter { source, predicate };
         }
         xform(*source)
     }
     fn push_join(node: Node) -> Node {
-        node
+        let Node::NestedLoopJoin {
+            mut left,
+            mut right,
+            predicate: Some(predicate),
+            outer,
+        } = node
+        else {
+            return node;
+        };
+        // Partition the predicate clauses into those referencing only the
+        // left or right side. Left clauses can't be pushed down for outer
+        // joins, since unmatched left rows must still be emitted.
+        let left_size = left.columns();
+        let (mut left_cnf, mut right_cnf, mut cnf) = (Vec::new(), Vec::new(), Vec::new());
+        for expr in predicate.into_cnf_vec() {
+            // Clauses with subqueries are executed by the join.
+            if expr.has_subqueries() {
+                cnf.push(expr);
+                continue;
+            }
+            let references_left =
+                expr.contains(&|e| matches!(e, Expression::Column(i) if *i < left_size));
+            let references_right =
+                expr.contains(&|e| matches!(e, Expression::Column(i) if *i >= left_size));
+            match (references_left, references_right) {
+                (true, false) if !outer => left_cnf.push(expr),
+                (false, true) => right_cnf.push(expr.map_columns(|i| i - left_size)),
+                _ => cnf.push(expr),
+            }
+        }
+        if let Some(expr) = Expression::and_vec(left_cnf) {
+            if let Some(expr) = push_into(expr, &mut left) {
+                cnf.push(expr);
+            }
+        }
+        if let Some(expr) = Expression::and_vec(right_cnf) {
+            if let Some(expr) = push_into(expr, &mut right) {
+                cnf.push(expr.map_columns(|i| i + left_size));
+            }
+        }
+        Node::NestedLoopJoin {
+            left,
+            right,
+            predicate: Expression::and_vec(cnf),
+            outer,
+        }
     }
     fn xform(node: Node) -> Node {
         push_join(push_filter(node))
@@ -118,3 +287,136 @@ pub fn push_filter(node: Node) -> Result<Node> {
 
     node.transform(&|node| Ok(xform(node)), &Ok)
 }
+
+/// Rewrites scans with a primary key or secondary index filter, e.g. WHERE id
+/// = 1 OR id = 2, into direct key or index lookups. Primary key lookups are
+/// preferred. Any remaining filter clauses are applied to the looked up rows.
+pub fn index_lookup(node: Node) -> Result<Node> {
+    fn xform(node: Node) -> Node {
+        let Node::Scan {
+            table,
+            alias,
+            filter: Some(filter),
+        } = node
+        else {
+            return node;
+        };
+        let mut cnf = filter.clone().into_cnf_vec();
+        let columns = std::iter::once(table.primary_key)
+            .chain((0..table.columns.len()).filter(|i| table.columns[*i].index));
+        let lookup = columns
+            .flat_map(|column| {
+                cnf.iter()
+                    .enumerate()
+                    .map(move |(i, expr)| (column, i, expr))
+            })
+            .find_map(|(column, i, expr)| {
+                // Values of a different type than the column (e.g. id = 1.0)
+                // can still compare equal, but won't match the stored key.
+                // They're converted to the column type where possible (e.g.
+                // 1.5 to DECIMAL(5,2)), as long as they still compare equal.
+                let data_type = &table.columns[column].data_type;
+                let values = expr
+                    .lookup_values(column)?
+                    .into_iter()
+                    .map(|value| {
+                        let key = data_type.coerce(value.clone()).ok()?;
+                        let equal = key.checked_eq(&value).ok()? == Value::Boolean(true);
+                        (equal && data_type.accepts(&key)).then_some(key)
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Some((column, i, values))
+            });
+        let Some((column, i, mut values)) = lookup else {
+            return Node::Scan {
+                table,
+                alias,
+                filter: Some(filter),
+            };
+        };
+        cnf.remove(i);
+        values.sort();
+        values.dedup();
+        let node = match column == table.primary_key {
+            true => Node::KeyLookup {
+                table,
+                alias,
+                keys: values,
+            },
+            false => Node::IndexLookup {
+                table,
+                alias,
+                column,
+                values,
+            },
+        };
+        match Expression::and_vec(cnf) {
+            Some(predicate) => Node::Filter {
+                source: Box::new(node),
+                predicate,
+            },
+            None => node,
+        }
+    }
+    node.transform(&Ok, &|node| Ok(xform(node)))
+}
+
+/// Rewrites nested loop joins on an equality predicate between a left and a
+/// right column, e.g. ON a.id = b.a_id, into hash joins. For inner joins, any
+/// remaining predicate clauses are applied as a filter on the joined rows.
+/// This can't be done for outer joins, since the filter would also drop the
+/// NULL-padded rows.
+pub fn join_type(node: Node) -> Result<Node> {
+    fn xform(node: Node) -> Node {
+        let Node::NestedLoopJoin {
+            left,
+            right,
+            predicate: Some(predicate),
+            outer,
+        } = node
+        else {
+            return node;
+        };
+        let left_size = left.columns();
+        let mut cnf = predicate.clone().into_cnf_vec();
+        let equijoin = cnf.iter().enumerate().find_map(|(i, expr)| {
+            let Expression::Equal(lhs, rhs) = expr else {
+                return None;
+            };
+            match (lhs.as_ref(), rhs.as_ref()) {
+                (Expression::Column(l), Expression::Column(r))
+                | (Expression::Column(r), Expression::Column(l))
+                    if *l < left_size && *r >= left_size =>
+                {
+                    Some((i, *l, *r - left_size))
+                }
+                _ => None,
+            }
+        });
+        let Some((i, left_column, right_column)) = equijoin.filter(|_| !outer || cnf.len() == 1)
+        else {
+            return Node::NestedLoopJoin {
+                left,
+                right,
+                predicate: Some(predicate),
+                outer,
+            };
+        };
+        cnf.remove(i);
+        let node = Node::HashJoin {
+            left,
+            left_column,
+            right,
+            right_column,
+            outer,
+        };
+        match Expression::and_vec(cnf) {
+            Some(predicate) => Node::Filter {
+                source: Box::new(node),
+                predicate,
+            },
+            None => node,
+        }
+    }
+    node.transform(&Ok, &|node| Ok(xform(node)))
+}
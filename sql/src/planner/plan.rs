@@ -1,26 +1,55 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
 
 use itertools::Itertools as _;
 use serde::{Deserialize, Serialize};
 
-use super::{Planner, optimizer::OPTIMIZERS};
+use super::{Planner, optimizer, typecheck};
 use crate::{
     engine::{Catalog, Transaction},
+    errinput,
     error::Result,
     execution::{self, execute::ExecutionResult},
     parser::ast,
-    types::{expression::Expression, schema::Table, value::Label},
+    types::{
+        expression::Expression,
+        schema::{ColumnType, DataType, Table},
+        value::{Label, Value},
+    },
 };
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Plan {
     CreateTable {
         schema: Table,
     },
+    DropTable {
+        table: String,
+        if_exists: bool,
+    },
+    CreateIndex {
+        table: String,
+        column: String,
+    },
     Insert {
         table: Table,
         column_map: Option<HashMap<usize, usize>>,
         source: Node,
+        on_conflict: Option<OnConflict>,
+        returning: Returning,
+    },
+    Update {
+        table: Table,
+        source: Node,
+        expressions: Vec<(usize, Expression)>,
+        returning: Returning,
+    },
+    Delete {
+        table: Table,
+        source: Node,
+        returning: Returning,
     },
     Select(Node),
 }
@@ -35,11 +64,265 @@ impl Plan {
     }
 
     pub fn optimize(self) -> Result<Self> {
-        let optimizers = |node| OPTIMIZERS.iter().try_fold(node, |node, (_, opt)| opt(node));
+        let optimizers = optimizer::optimize;
         Ok(match self {
             Self::Select(root) => Self::Select(optimizers(root)?),
-            _ => self,
+            Self::Insert {
+                table,
+                column_map,
+                source,
+                on_conflict,
+                returning,
+            } => Self::Insert {
+                table,
+                column_map,
+                source: optimizers(source)?,
+                on_conflict,
+                returning,
+            },
+            Self::Update {
+                table,
+                source,
+                expressions,
+                returning,
+            } => Self::Update {
+                table,
+                source: optimizers(source)?,
+                expressions,
+                returning,
+            },
+            Self::Delete {
+                table,
+                source,
+                returning,
+            } => Self::Delete {
+                table,
+                source: optimizers(source)?,
+                returning,
+            },
+            Self::CreateTable { .. } | Self::DropTable { .. } | Self::CreateIndex { .. } => self,
+        })
+    }
+
+    /// Returns the schemas of the tables referenced by the plan, including in
+    /// subqueries, without duplicates.
+    pub fn tables(&self) -> Vec<Table> {
+        let tables = RefCell::new(Vec::new());
+        let expressions: Vec<&Expression> = match self {
+            Self::Select(root) => {
+                collect_tables(root, &tables);
+                Vec::new()
+            }
+            Self::Insert {
+                table,
+                source,
+                on_conflict,
+                returning,
+                ..
+            } => {
+                tables.borrow_mut().push(table.clone());
+                collect_tables(source, &tables);
+                let on_conflict = match on_conflict {
+                    Some(OnConflict::Update(expressions)) => expressions.as_slice(),
+                    _ => &[],
+                };
+                on_conflict
+                    .iter()
+                    .map(|(_, expr)| expr)
+                    .chain(returning.iter().map(|(expr, _)| expr))
+                    .collect()
+            }
+            Self::Update {
+                table,
+                source,
+                expressions,
+                returning,
+            } => {
+                tables.borrow_mut().push(table.clone());
+                collect_tables(source, &tables);
+                expressions
+                    .iter()
+                    .map(|(_, expr)| expr)
+                    .chain(returning.iter().map(|(expr, _)| expr))
+                    .collect()
+            }
+            Self::Delete {
+                table,
+                source,
+                returning,
+            } => {
+                tables.borrow_mut().push(table.clone());
+                collect_tables(source, &tables);
+                returning.iter().map(|(expr, _)| expr).collect()
+            }
+            Self::CreateTable { .. } | Self::DropTable { .. } | Self::CreateIndex { .. } => {
+                Vec::new()
+            }
+        };
+        for node in expressions.into_iter().flat_map(|expr| expr.subqueries()) {
+            collect_tables(node, &tables);
+        }
+        let mut tables = tables.into_inner();
+        let mut seen = HashSet::new();
+        tables.retain(|table| seen.insert(table.name.clone()));
+        tables
+    }
+
+    /// Binds query parameters, replacing parameter placeholders with the
+    /// given values, and type checks the bound plan. This is done before
+    /// optimization, such that e.g. index lookups can use the values.
+    pub fn bind(self, parameters: &[Value]) -> Result<Self> {
+        let expr = |expr: Expression| bind_expression(expr, parameters);
+        let assignments = |expressions: Vec<(usize, Expression)>| -> Result<Vec<_>> {
+            expressions
+                .into_iter()
+                .map(|(column, e)| Ok((column, expr(e)?)))
+                .collect()
+        };
+        let returning = |returning: Returning| -> Result<Returning> {
+            returning
+                .into_iter()
+                .map(|(e, label)| Ok((expr(e)?, label)))
+                .collect()
+        };
+        let plan = match self {
+            Self::Select(root) => Self::Select(bind_node(root, parameters)?),
+            Self::Insert {
+                table,
+                column_map,
+                source,
+                on_conflict,
+                returning: r,
+            } => Self::Insert {
+                table,
+                column_map,
+                source: bind_node(source, parameters)?,
+                on_conflict: match on_conflict {
+                    Some(OnConflict::Update(expressions)) => {
+                        Some(OnConflict::Update(assignments(expressions)?))
+                    }
+                    on_conflict => on_conflict,
+                },
+                returning: returning(r)?,
+            },
+            Self::Update {
+                table,
+                source,
+                expressions,
+                returning: r,
+            } => Self::Update {
+                table,
+                source: bind_node(source, parameters)?,
+                expressions: assignments(expressions)?,
+                returning: returning(r)?,
+            },
+            Self::Delete {
+                table,
+                source,
+                returning: r,
+            } => Self::Delete {
+                table,
+                source: bind_node(source, parameters)?,
+                returning: returning(r)?,
+            },
+            Self::CreateTable { .. } | Self::DropTable { .. } | Self::CreateIndex { .. } => {
+                return Ok(self);
+            }
+        };
+        typecheck::check_plan(plan)
+    }
+}
+
+/// Collects the tables scanned by the node and its subqueries, see
+/// Plan::tables().
+fn collect_tables(node: &Node, tables: &RefCell<Vec<Table>>) {
+    let visit = |node: Node| {
+        if let Node::Scan { table, .. }
+        | Node::KeyLookup { table, .. }
+        | Node::IndexLookup { table, .. } = &node
+        {
+            tables.borrow_mut().push(table.clone());
+        }
+        node.transform_expressions(&Ok, &|expr| {
+            if let Expression::Subquery(node)
+            | Expression::Exists(node)
+            | Expression::InSubquery(_, node) = &expr
+            {
+                collect_tables(node, tables);
+            }
+            Ok(expr)
         })
+    };
+    node.clone()
+        .transform(&visit, &Ok)
+        .expect("collecting tables can't fail");
+}
+
+/// Binds query parameters in all of the node's expressions, see Plan::bind().
+fn bind_node(node: Node, parameters: &[Value]) -> Result<Node> {
+    node.transform(&Ok, &|node| {
+        node.transform_expressions(&Ok, &|expr| bind_parameter(expr, parameters))
+    })
+}
+
+/// Binds query parameters in an expression, see Plan::bind().
+fn bind_expression(expr: Expression, parameters: &[Value]) -> Result<Expression> {
+    expr.transform(&Ok, &|expr| bind_parameter(expr, parameters))
+}
+
+/// Binds a single parameter, or the parameters of a subquery node. Pattern
+/// matches are recompiled, in case the pattern was a parameter.
+fn bind_parameter(expr: Expression, parameters: &[Value]) -> Result<Expression> {
+    use Expression::*;
+    Ok(match expr {
+        Parameter(index) => match parameters.get(index) {
+            Some(value) => Constant(value.clone()),
+            None => return errinput!("missing parameter ${}", index + 1),
+        },
+        Subquery(node) => Subquery(Box::new(bind_node(*node, parameters)?)),
+        Exists(node) => Exists(Box::new(bind_node(*node, parameters)?)),
+        InSubquery(expr, node) => InSubquery(expr, Box::new(bind_node(*node, parameters)?)),
+        Match(lhs, rhs, kind, None) => Expression::pattern_match(*lhs, *rhs, kind)?,
+        expr => expr,
+    })
+}
+
+/// The action for inserted rows whose primary key already exists.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum OnConflict {
+    /// Skip the row.
+    Nothing,
+    /// Update the existing row's columns. The expressions are evaluated
+    /// against the existing row followed by the inserted (excluded) row.
+    Update(Vec<(usize, Expression)>),
+}
+
+/// RETURNING expressions and aliases for INSERT, UPDATE and DELETE, evaluated
+/// against the affected table rows. Empty if not given.
+pub type Returning = Vec<(Expression, Label)>;
+
+impl Plan {
+    fn format_returning(
+        f: &mut std::fmt::Formatter<'_>,
+        table: &Table,
+        returning: &Returning,
+    ) -> std::fmt::Result {
+        if returning.is_empty() {
+            return Ok(());
+        }
+        let scan = Node::Scan {
+            table: table.clone(),
+            alias: None,
+            filter: None,
+        };
+        let returning = returning
+            .iter()
+            .map(|(expr, label)| match label {
+                Label::None => expr.format(&scan),
+                label => format!("{} as {label}", expr.format(&scan)),
+            })
+            .join(",");
+        write!(f, " returning {returning}")
     }
 }
 
@@ -47,8 +330,71 @@ impl std::fmt::Display for Plan {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Plan::CreateTable { schema } => write!(f, "CreateTable {}", schema.name),
-            Plan::Insert { table, source, .. } => {
+            Plan::DropTable { table, .. } => write!(f, "DropTable {table}"),
+            Plan::CreateIndex { table, column } => write!(f, "CreateIndex {table}.{column}"),
+            Plan::Insert {
+                table,
+                source,
+                on_conflict,
+                returning,
+                ..
+            } => {
                 write!(f, "Insert {}", table.name)?;
+                match on_conflict {
+                    None => {}
+                    Some(OnConflict::Nothing) => write!(f, " on conflict do nothing")?,
+                    Some(OnConflict::Update(expressions)) => {
+                        // The expressions reference the existing row followed
+                        // by the excluded (inserted) row.
+                        let scan = |alias: Option<&str>| Node::Scan {
+                            table: table.clone(),
+                            alias: alias.map(String::from),
+                            filter: None,
+                        };
+                        let join = Node::NestedLoopJoin {
+                            left: Box::new(scan(None)),
+                            right: Box::new(scan(Some("excluded"))),
+                            predicate: None,
+                            outer: false,
+                        };
+                        let expressions = expressions
+                            .iter()
+                            .map(|(i, expr)| {
+                                format!("{}={}", table.columns[*i].name, expr.format(&join))
+                            })
+                            .join(",");
+                        write!(f, " on conflict do update ({expressions})")?;
+                    }
+                }
+                Self::format_returning(f, table, returning)?;
+                source.format(f, "", false, true)
+            }
+            Plan::Update {
+                table,
+                source,
+                expressions,
+                returning,
+            } => {
+                let formatted = expressions
+                    .iter()
+                    .map(|(i, expr)| format!("{}={}", table.columns[*i].name, expr.format(source)))
+                    .join(",");
+                write!(f, "Update {} ({formatted})", table.name)?;
+                Self::format_returning(f, table, returning)?;
+                for (_, expr) in expressions {
+                    for subquery in expr.subqueries() {
+                        subquery.format(f, "", false, false)?;
+                    }
+                }
+                source.format(f, "", false, true)
+            }
+            Plan::Delete {
+                table,
+                source,
+                returning,
+            } => {
+                write!(f, "Delete {}", table.name)?;
+                Self::format_returning(f, table, returning)?;
                 source.format(f, "", false, true)
             }
             Plan::Select(root) => root.format(f, "", true, true),
@@ -75,6 +421,13 @@ pub enum Node {
         predicate: Expression,
     },
 
+    /// Removes duplicate rows, keeping the first one. If on is given, rows
+    /// are duplicates if the on expressions evaluate to the same values.
+    Distinct {
+        source: Box<Node>,
+        on: Vec<Expression>,
+    },
+
     Offset {
         source: Box<Node>,
         offset: usize,
@@ -89,16 +442,84 @@ pub enum Node {
         key: Vec<(Expression, Direction)>,
     },
 
+    /// Joins the left and right sources on the given predicate by iterating
+    /// over every pair of rows. For outer joins, left rows without a match are
+    /// emitted with NULLs for the right columns.
+    NestedLoopJoin {
+        left: Box<Node>,
+        right: Box<Node>,
+        predicate: Option<Expression>,
+        outer: bool,
+    },
+    /// Joins the left and right sources on equality of the given left and
+    /// right columns, by building a hash table of the right rows. The right
+    /// column index is relative to the right source. For outer joins, left
+    /// rows without a match are emitted with NULLs for the right columns.
+    HashJoin {
+        left: Box<Node>,
+        left_column: usize,
+        right: Box<Node>,
+        right_column: usize,
+        outer: bool,
+    },
+
+    /// Emits the rows of both sources. Unless all is true, duplicate rows
+    /// are removed.
+    Union {
+        left: Box<Node>,
+        right: Box<Node>,
+        all: bool,
+    },
+    /// Emits the left rows that also exist in the right source. With all,
+    /// rows are emitted as many times as they occur in both sources,
+    /// otherwise only once.
+    Intersect {
+        left: Box<Node>,
+        right: Box<Node>,
+        all: bool,
+    },
+    /// Emits the left rows that don't exist in the right source. With all,
+    /// each right row only removes one matching left row, otherwise
+    /// duplicates are removed.
+    Except {
+        left: Box<Node>,
+        right: Box<Node>,
+        all: bool,
+    },
+
+    /// A reference to a common table expression, whose plan is inlined as
+    /// the source. Its columns are qualified by the alias or CTE name.
+    Cte {
+        name: String,
+        alias: Option<String>,
+        source: Box<Node>,
+    },
+
     Scan {
         table: Table,
+        alias: Option<String>,
         filter: Option<Expression>,
     },
+    /// Looks up rows by primary key.
+    KeyLookup {
+        table: Table,
+        alias: Option<String>,
+        keys: Vec<Value>,
+    },
+    /// Looks up rows by secondary index values of the given column.
+    IndexLookup {
+        table: Table,
+        alias: Option<String>,
+        column: usize,
+        values: Vec<Value>,
+    },
     Values {
         rows: Vec<Vec<Expression>>,
     },
 
     Nothing {
         columns: Vec<Label>,
+        types: Vec<ColumnType>,
     },
 }
 
@@ -129,12 +550,19 @@ impl Node {
                 group_by,
                 aggregates,
             } => {
-                let aggregates = group_by
+                let formatted = group_by
                     .iter()
-                    .map(|group_by| group_by.format(self))
+                    .map(|group_by| group_by.format(source))
                     .chain(aggregates.iter().map(|agg| agg.format(source)))
                     .join(",");
-                write!(f, "Aggregate: {}", aggregates)?;
+                write!(f, "Aggregate: {}", formatted)?;
+                let subqueries = group_by
+                    .iter()
+                    .chain(aggregates.iter().map(|agg| agg.expression()))
+                    .flat_map(|expr| expr.subqueries());
+                for subquery in subqueries {
+                    subquery.format(f, &prefix, false, false)?;
+                }
                 source.format(f, &prefix, false, true)?;
             }
 
@@ -143,7 +571,7 @@ impl Node {
                 expressions,
                 aliases,
             } => {
-                let expressions = expressions
+                let formatted = expressions
                     .iter()
                     .zip(aliases.iter())
                     .map(|(expr, alias)| match alias {
@@ -151,7 +579,10 @@ impl Node {
                         label => format!("{} as {}", expr.format(source), label),
                     })
                     .join(",");
-                write!(f, "Projection: {expressions}")?;
+                write!(f, "Projection: {formatted}")?;
+                for subquery in expressions.iter().flat_map(|expr| expr.subqueries()) {
+                    subquery.format(f, &prefix, false, false)?;
+                }
                 source.format(f, &prefix, false, true)?;
             }
 
@@ -159,15 +590,32 @@ impl Node {
                 source,
                 key: orders,
             } => {
-                let orders = orders
+                let formatted = orders
                     .iter()
                     .map(|(expr, dir)| format!("{} {dir}", expr.format(source)))
                     .join(",");
-                write!(f, "Order: {}", orders)?;
-                source.format(f, &prefix, root, last_child)?;
+                write!(f, "Order: {formatted}")?;
+                for subquery in orders.iter().flat_map(|(expr, _)| expr.subqueries()) {
+                    subquery.format(f, &prefix, false, false)?;
+                }
+                source.format(f, &prefix, false, true)?;
             }
             Self::Filter { source, predicate } => {
                 write!(f, "Filter: {}", predicate.format(source))?;
+                for subquery in predicate.subqueries() {
+                    subquery.format(f, &prefix, false, false)?;
+                }
+                source.format(f, &prefix, false, true)?;
+            }
+            Self::Distinct { source, on } => {
+                write!(f, "Distinct")?;
+                if !on.is_empty() {
+                    write!(
+                        f,
+                        ": on {}",
+                        on.iter().map(|expr| expr.format(source)).join(",")
+                    )?;
+                }
                 source.format(f, &prefix, false, true)?;
             }
             Self::Offset { source, offset } => {
@@ -178,12 +626,100 @@ impl Node {
                 write!(f, "Limit: {}", limit)?;
                 source.format(f, &prefix, false, true)?;
             }
-            Self::Scan { table, filter } => {
+            Self::NestedLoopJoin {
+                left,
+                right,
+                predicate,
+                outer,
+            } => {
+                write!(
+                    f,
+                    "NestedLoopJoin: {}",
+                    if *outer { "outer" } else { "inner" }
+                )?;
+                if let Some(predicate) = predicate {
+                    write!(f, " on {}", predicate.format(self))?;
+                    for subquery in predicate.subqueries() {
+                        subquery.format(f, &prefix, false, false)?;
+                    }
+                }
+                left.format(f, &prefix, false, false)?;
+                right.format(f, &prefix, false, true)?;
+            }
+            Self::HashJoin {
+                left,
+                left_column,
+                right,
+                right_column,
+                outer,
+            } => {
+                write!(
+                    f,
+                    "HashJoin: {} on {} = {}",
+                    if *outer { "outer" } else { "inner" },
+                    Expression::Column(*left_column).format(left),
+                    Expression::Column(*right_column).format(right),
+                )?;
+                left.format(f, &prefix, false, false)?;
+                right.format(f, &prefix, false, true)?;
+            }
+            Self::Union { left, right, all }
+            | Self::Intersect { left, right, all }
+            | Self::Except { left, right, all } => {
+                let name = match self {
+                    Self::Union { .. } => "Union",
+                    Self::Intersect { .. } => "Intersect",
+                    _ => "Except",
+                };
+                write!(f, "{name}: {}", if *all { "all" } else { "distinct" })?;
+                left.format(f, &prefix, false, false)?;
+                right.format(f, &prefix, false, true)?;
+            }
+            Self::Cte {
+                name,
+                alias,
+                source,
+            } => {
+                write!(f, "Cte: {name}")?;
+                if let Some(alias) = alias {
+                    write!(f, " as {alias}")?;
+                }
+                source.format(f, &prefix, false, true)?;
+            }
+            Self::Scan {
+                table,
+                alias,
+                filter,
+            } => {
                 write!(f, "Scan: {}", table.name)?;
+                if let Some(alias) = alias {
+                    write!(f, " as {alias}")?;
+                }
                 if let Some(filter) = filter {
                     write!(f, " ({})", filter.format(self))?;
                 }
             }
+            Self::KeyLookup { table, alias, keys } => {
+                write!(f, "KeyLookup: {}", table.name)?;
+                if let Some(alias) = alias {
+                    write!(f, " as {alias}")?;
+                }
+                write!(f, " ({})", keys.iter().join(", "))?;
+            }
+            Self::IndexLookup {
+                table,
+                alias,
+                column,
+                values,
+            } => {
+                let column = &table.columns[*column].name;
+                let values = values.iter().join(", ");
+                write!(f, "IndexLookup: {}.{column}", table.name)?;
+                if let Some(alias) = alias {
+                    write!(f, " as {alias}.{column}")?;
+                }
+                write!(f, " ({values})")?;
+            }
             Self::Values { rows, .. } => {
                 write!(f, "Values ")?;
                 match rows.len() {
@@ -207,18 +743,53 @@ impl Node {
                 ..
             } => aggregates.len() + group_by.len(),
             Node::Projection { expressions, .. } => expressions.len(),
-            Node::Filter { source, .. } => source.columns(),
-            Node::Scan { table, .. } => table.columns.len(),
+            Node::Union { left, .. } | Node::Intersect { left, .. } | Node::Except { left, .. } => {
+                left.columns()
+            }
+            Node::Cte { source, .. }
+            | Node::Distinct { source, .. }
+            | Node::Filter { source, .. }
+            | Node::Limit { source, .. }
+            | Node::Offset { source, .. }
+            | Node::Order { source, .. } => source.columns(),
+            Node::NestedLoopJoin { left, right, .. } | Node::HashJoin { left, right, .. } => {
+                left.columns() + right.columns()
+            }
+            Node::Scan { table, .. }
+            | Node::KeyLookup { table, .. }
+            | Node::IndexLookup { table, .. } => table.columns.len(),
             Node::Values { rows } => rows.first().map(|r| r.len()).unwrap_or_default(),
-            Node::Nothing { columns } => columns.len(),
-            _ => 0,
+            Node::Nothing { columns, .. } => columns.len(),
         }
     }
 
     pub fn column_label(&self, index: usize) -> Label {
         match self {
-            Self::Scan { table, .. } => {
-                Label::Qualified(table.name.clone(), table.columns[index].name.clone())
+            Self::Scan { table, alias, .. }
+            | Self::KeyLookup { table, alias, .. }
+            | Self::IndexLookup { table, alias, .. } => Label::Qualified(
+                alias.clone().unwrap_or_else(|| table.name.clone()),
+                table.columns[index].name.clone(),
+            ),
+            // Set operation columns are labeled by the left source.
+            Self::Union { left, .. } | Self::Intersect { left, .. } | Self::Except { left, .. } => {
+                left.column_label(index)
+            }
+            Self::Cte {
+                name,
+                alias,
+                source,
+            } => match source.column_label(index) {
+                Label::Qualified(_, column) | Label::Unqualified(column) => {
+                    Label::Qualified(alias.as_ref().unwrap_or(name).clone(), column)
+                }
+                Label::None => Label::None,
+            },
+            Self::NestedLoopJoin { left, right, .. } | Self::HashJoin { left, right, .. } => {
+                match left.columns() {
+                    size if index < size => left.column_label(index),
+                    size => right.column_label(index - size),
+                }
             }
             Self::Projection {
                 source,
@@ -231,11 +802,110 @@ impl Node {
                 },
                 Some(label) => label.clone(),
             },
-            Self::Nothing { columns } => columns.get(index).cloned().unwrap_or(Label::None),
-            _ => Label::None,
+            Self::Distinct { source, .. }
+            | Self::Filter { source, .. }
+            | Self::Limit { source, .. }
+            | Self::Offset { source, .. }
+            | Self::Order { source, .. } => source.column_label(index),
+            Self::Nothing { columns, .. } => columns.get(index).cloned().unwrap_or(Label::None),
+            // Group by columns keep their labels, aggregates have none.
+            Self::Aggregate {
+                source, group_by, ..
+            } => match group_by.get(index) {
+                Some(Expression::Column(index)) => source.column_label(*index),
+                Some(_) | None => Label::None,
+            },
+            Self::Values { .. } => Label::None,
         }
     }
 
+    /// Returns the static type of the given column. Errors if an expression
+    /// in the node is ill-typed.
+    pub fn column_type(&self, index: usize) -> Result<ColumnType> {
+        Ok(match self {
+            Self::Scan { table, .. }
+            | Self::KeyLookup { table, .. }
+            | Self::IndexLookup { table, .. } => {
+                let column = &table.columns[index];
+                ColumnType::new(column.data_type.clone(), column.nullable)
+            }
+            Self::Projection {
+                source,
+                expressions,
+                ..
+            } => expressions[index].data_type(&|i| source.column_type(i))?,
+            // Rows are the group by values followed by the aggregates.
+            Self::Aggregate {
+                source,
+                group_by,
+                aggregates,
+            } => match group_by.get(index) {
+                Some(expr) => expr.data_type(&|i| source.column_type(i))?,
+                None => match &aggregates[index - group_by.len()] {
+                    Aggregate::Count(_) | Aggregate::CountDistinct(_) => {
+                        ColumnType::new(DataType::Integer, false)
+                    }
+                    // Empty groups yield NULL for the remaining aggregates.
+                    Aggregate::Average(expr) => {
+                        expr.data_type(&|i| source.column_type(i))?;
+                        ColumnType::new(DataType::Float, true)
+                    }
+                    Aggregate::Max(expr) | Aggregate::Min(expr) | Aggregate::Sum(expr) => {
+                        ColumnType {
+                            nullable: true,
+                            ..expr.data_type(&|i| source.column_type(i))?
+                        }
+                    }
+                },
+            },
+            // Outer joins emit NULLs for unmatched right columns.
+            Self::NestedLoopJoin {
+                left, right, outer, ..
+            }
+            | Self::HashJoin {
+                left, right, outer, ..
+            } => match left.columns() {
+                size if index < size => left.column_type(index)?,
+                size => {
+                    let ty = right.column_type(index - size)?;
+                    ColumnType {
+                        nullable: ty.nullable || *outer,
+                        ..ty
+                    }
+                }
+            },
+            Self::Union { left, right, .. }
+            | Self::Intersect { left, right, .. }
+            | Self::Except { left, right, .. } => {
+                let (left, right) = (left.column_type(index)?, right.column_type(index)?);
+                ColumnType {
+                    data_type: left.data_type.or(right.data_type),
+                    nullable: left.nullable || right.nullable,
+                }
+            }
+            Self::Cte { source, .. }
+            | Self::Distinct { source, .. }
+            | Self::Filter { source, .. }
+            | Self::Limit { source, .. }
+            | Self::Offset { source, .. }
+            | Self::Order { source, .. } => source.column_type(index)?,
+            Self::Values { rows } => {
+                let types: Vec<_> = rows
+                    .iter()
+                    .filter_map(|row| row.get(index))
+                    .map(|expr| expr.data_type(&|_| Ok(ColumnType::unknown())))
+                    .try_collect()?;
+                ColumnType::common(&types).unwrap_or_else(ColumnType::unknown)
+            }
+            Self::Nothing { types, .. } => types[index].clone(),
+        })
+    }
+
+    /// Returns the static types of all columns.
+    pub fn column_types(&self) -> Result<Vec<ColumnType>> {
+        (0..self.columns()).map(|i| self.column_type(i)).collect()
+    }
+
     pub fn transform(
         mut self,
         before: &impl Fn(Self) -> Result<Self>,
@@ -255,6 +925,34 @@ impl Node {
                 source: xform(source)?,
                 limit,
             },
+            Self::Distinct { source, on } => Self::Distinct {
+                source: xform(source)?,
+                on,
+            },
+            Self::Cte {
+                name,
+                alias,
+                source,
+            } => Self::Cte {
+                name,
+                alias,
+                source: xform(source)?,
+            },
+            Self::Union { left, right, all } => Self::Union {
+                left: xform(left)?,
+                right: xform(right)?,
+                all,
+            },
+            Self::Intersect { left, right, all } => Self::Intersect {
+                left: xform(left)?,
+                right: xform(right)?,
+                all,
+            },
+            Self::Except { left, right, all } => Self::Except {
+                left: xform(left)?,
+                right: xform(right)?,
+                all,
+            },
             Self::Offset { source, offset } => Self::Offset {
                 source: xform(source)?,
                 offset,
@@ -281,7 +979,34 @@ impl Node {
                 group_by,
                 aggregates,
             },
-            Self::Scan { .. } | Self::Nothing { .. } => self,
+            Self::NestedLoopJoin {
+                left,
+                right,
+                predicate,
+                outer,
+            } => Self::NestedLoopJoin {
+                left: xform(left)?,
+                right: xform(right)?,
+                predicate,
+                outer,
+            },
+            Self::HashJoin {
+                left,
+                left_column,
+                right,
+                right_column,
+                outer,
+            } => Self::HashJoin {
+                left: xform(left)?,
+                left_column,
+                right: xform(right)?,
+                right_column,
+                outer,
+            },
+            Self::Scan { .. }
+            | Self::KeyLookup { .. }
+            | Self::IndexLookup { .. }
+            | Self::Nothing { .. } => self,
             Self::Values { .. } => self,
         };
         self = after(self)?;
@@ -302,6 +1027,14 @@ impl Node {
                 Self::Filter { source, predicate }
             }
 
+            Self::Distinct { source, on } => {
+                let on = on
+                    .into_iter()
+                    .map(|expr| expr.transform(before, after))
+                    .try_collect()?;
+                Self::Distinct { source, on }
+            }
+
             Self::Order { source, mut key } => {
                 key = key
                     .into_iter()
@@ -310,12 +1043,32 @@ impl Node {
                 Self::Order { source, key }
             }
 
+            Self::NestedLoopJoin {
+                left,
+                right,
+                predicate: Some(predicate),
+                outer,
+            } => {
+                let predicate = Some(predicate.transform(before, after)?);
+                Self::NestedLoopJoin {
+                    left,
+                    right,
+                    predicate,
+                    outer,
+                }
+            }
+
             Self::Scan {
                 table,
+                alias,
                 filter: Some(filter),
             } => {
                 let filter = Some(filter.transform(before, after)?);
-                Self::Scan { table, filter }
+                Self::Scan {
+                    table,
+                    alias,
+                    filter,
+                }
             }
 
             Self::Projection {
@@ -345,10 +1098,38 @@ impl Node {
                     .try_collect()?;
                 Self::Values { rows }
             }
-            Self::Aggregate { .. }
+            Self::Aggregate {
+                source,
+                group_by,
+                aggregates,
+            } => {
+                let group_by = group_by
+                    .into_iter()
+                    .map(|expr| expr.transform(before, after))
+                    .try_collect()?;
+                let aggregates = aggregates
+                    .into_iter()
+                    .map(|aggregate| aggregate.transform(before, after))
+                    .try_collect()?;
+                Self::Aggregate {
+                    source,
+                    group_by,
+                    aggregates,
+                }
+            }
+            Self::Cte { .. }
+            | Self::Union { .. }
+            | Self::Intersect { .. }
+            | Self::Except { .. }
             | Self::Limit { .. }
             | Self::Offset { .. }
             | Self::Nothing { .. }
+            | Self::KeyLookup { .. }
+            | Self::IndexLookup { .. }
+            | Self::HashJoin { .. }
+            | Self::NestedLoopJoin {
+                predicate: None, ..
+            }
             | Self::Scan { filter: None, .. } => self,
         })
     }
@@ -375,20 +1156,71 @@ impl std::fmt::Display for Direction {
     }
 }
 
+impl From<ast::Direction> for Direction {
+    fn from(direction: ast::Direction) -> Self {
+        match direction {
+            ast::Direction::Ascending => Self::Ascending,
+            ast::Direction::Descending => Self::Descending,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Aggregate {
     Average(Expression),
     Count(Expression),
+    CountDistinct(Expression),
     Max(Expression),
     Min(Expression),
     Sum(Expression),
 }
 
 impl Aggregate {
+    /// Returns the aggregated expression.
+    pub fn expression(&self) -> &Expression {
+        match self {
+            Self::Average(expr)
+            | Self::Count(expr)
+            | Self::CountDistinct(expr)
+            | Self::Max(expr)
+            | Self::Min(expr)
+            | Self::Sum(expr) => expr,
+        }
+    }
+
+    /// Returns a mutable reference to the aggregated expression.
+    pub fn expression_mut(&mut self) -> &mut Expression {
+        match self {
+            Self::Average(expr)
+            | Self::Count(expr)
+            | Self::CountDistinct(expr)
+            | Self::Max(expr)
+            | Self::Min(expr)
+            | Self::Sum(expr) => expr,
+        }
+    }
+
+    /// Transforms the aggregated expression, see Expression::transform().
+    pub fn transform(
+        self,
+        before: &impl Fn(Expression) -> Result<Expression>,
+        after: &impl Fn(Expression) -> Result<Expression>,
+    ) -> Result<Self> {
+        Ok(match self {
+            Self::Average(expr) => Self::Average(expr.transform(before, after)?),
+            Self::Count(expr) => Self::Count(expr.transform(before, after)?),
+            Self::CountDistinct(expr) => Self::CountDistinct(expr.transform(before, after)?),
+            Self::Max(expr) => Self::Max(expr.transform(before, after)?),
+            Self::Min(expr) => Self::Min(expr.transform(before, after)?),
+            Self::Sum(expr) => Self::Sum(expr.transform(before, after)?),
+        })
+    }
+
     pub fn format(&self, source: &Node) -> String {
         match self {
             Self::Average(expr) => format!("avg({})", expr.format(source)),
             Self::Count(expr) => format!("count({})", expr.format(source)),
+            Self::CountDistinct(expr) => format!("count(DISTINCT {})", expr.format(source)),
             Self::Max(expr) => format!("max({})", expr.format(source)),
             Self::Min(expr) => format!("min({})", expr.format(source)),
             Self::Sum(expr) => format!("sum({})", expr.format(source)),
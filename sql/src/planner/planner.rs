@@ -1,6 +1,11 @@
 use std::collections::{HashMap, HashSet};
 
-use super::plan::{Node, Plan};
+use itertools::{Either, Itertools as _};
+
+use super::{
+    plan::{Aggregate, Node, OnConflict, Plan, Returning},
+    typecheck,
+};
 use crate::{
     engine::Catalog,
     errinput,
@@ -8,7 +13,9 @@ use crate::{
     parser::ast::{self, ColumnName, TableName},
     types::{
         expression::Expression,
-        schema::{Column, Table},
+        function::{self, FunctionRegistry},
+        pattern::PatternKind,
+        schema::{Column, DataType, Table},
         value::{Label, Value},
     },
 };
@@ -23,59 +30,125 @@ impl<'a, C: Catalog> Planner<'a, C> {
         Self { catalog }
     }
 
+    /// Creates an empty top-level scope, with the catalog's user-defined
+    /// functions.
+    fn scope(&self) -> Scope {
+        let mut scope = Scope::new();
+        scope.functions = self.catalog.functions();
+        scope
+    }
+
+    /// Builds a plan for the statement, and type checks it.
     pub fn build(&mut self, statement: ast::Statement) -> Result<Plan> {
         use ast::Statement::*;
-        match statement {
+        let plan = match statement {
             CrateTable {
                 table_name,
                 columns,
             } => self.build_create_table(table_name, columns),
+            DropTable {
+                table_name,
+                if_exists,
+            } => Ok(Plan::DropTable {
+                table: table_name,
+                if_exists,
+            }),
+            CreateIndex {
+                table_name,
+                column_name,
+            } => Ok(Plan::CreateIndex {
+                table: table_name,
+                column: column_name,
+            }),
             Insert {
                 table_name,
                 columns,
-                values,
-            } => self.build_insert(table_name, columns, values),
-            Select {
+                source,
+                on_conflict,
+                returning,
+            } => self.build_insert(table_name, columns, source, on_conflict, returning),
+            Update {
+                table_name,
+                set,
+                r#where,
+                returning,
+            } => self.build_update(table_name, set, r#where, returning),
+            Delete {
+                table_name,
+                r#where,
+                returning,
+            } => self.build_delete(table_name, r#where, returning),
+            statement @ (Select { .. } | SetOperation { .. }) => {
+                Ok(Plan::Select(self.build_query(statement, self.scope())?))
+            }
+            _ => errinput!("not support this statement:{statement:?}"),
+        }?;
+        typecheck::check_plan(plan)
+    }
+
+    /// Builds a SELECT query in the given scope, which is empty except for
+    /// subqueries, where it references the outer query's scope.
+    fn build_query(&self, statement: ast::Statement, scope: Scope) -> Result<Node> {
+        match statement {
+            ast::Statement::Select {
+                with,
+                distinct,
                 select,
                 from,
                 r#where,
+                group_by,
+                having,
+                order_by,
                 limit,
-            } => self.build_select(select, from, r#where, limit),
-            _ => errinput!("not support this statement:{statement:?}"),
+                offset,
+            } => self.build_select(
+                scope, with, distinct, select, from, r#where, group_by, having, order_by, limit,
+                offset,
+            ),
+            ast::Statement::SetOperation {
+                with,
+                operator,
+                all,
+                left,
+                right,
+                order_by,
+                limit,
+                offset,
+            } => self.build_set_operation(
+                scope, with, operator, all, *left, *right, order_by, limit, offset,
+            ),
+            _ => errinput!("subqueries must be SELECT statements"),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn build_select(
         &self,
-        mut select: Vec<(ast::Expression, Option<String>)>,
+        mut scope: Scope,
+        with: Vec<(TableName, ast::Statement)>,
+        distinct: Option<Vec<ast::Expression>>,
+        select: Vec<(ast::Expression, Option<String>)>,
         from: Vec<ast::From>,
         r#where: Option<ast::Expression>,
+        group_by: Vec<ast::Expression>,
+        having: Option<ast::Expression>,
+        order_by: Vec<(ast::Expression, ast::Direction)>,
         limit: Option<ast::Expression>,
-    ) -> Result<Plan> {
-        let mut scope = Scope::new();
+        offset: Option<ast::Expression>,
+    ) -> Result<Node> {
+        self.build_ctes(with, &mut scope)?;
+
         let mut node = if !from.is_empty() {
             self.build_from_clause(from, &mut scope)?
         } else {
             Node::Values { rows: vec![vec![]] }
         };
-        if select.contains(&(ast::Expression::All, None)) {
-            if node.columns() == 0 {
-                return errinput!("no columns in the table");
-            }
-            if select.len() > 1 {
-                select = select
-                    .into_iter()
-                    .flat_map(|(expr, alias)| match expr {
-                        ast::Expression::All => itertools::Either::Left(
-                            (0..node.columns()).map(|i| (node.column_label(i).into(), None)),
-                        ),
-                        expr => itertools::Either::Right(std::iter::once((expr, alias))),
-                    })
-                    .collect();
-            }
+        if select.contains(&(ast::Expression::All, None)) && node.columns() == 0 {
+            return errinput!("no columns in the table");
         }
 
         if let Some(r#where) = r#where {
+            self.build_subqueries(&r#where, &mut scope)?;
             let predicate = Self::build_expression(r#where, &scope)?;
             node = Node::Filter {
                 source: Box::new(node),
@@ -83,11 +156,101 @@ impl<'a, C: Catalog> Planner<'a, C> {
             }
         }
 
+        // Ordering is done before the projection, so resolve ORDER BY aliases
+        // and column positions to the corresponding SELECT expressions.
+        let order_by: Vec<_> = order_by
+            .into_iter()
+            .map(|(expr, dir)| Ok((Self::resolve_order_by(expr, &select, &node)?, dir)))
+            .collect::<Result<_>>()?;
+
+        // DISTINCT deduplicates the projected rows, so ORDER BY can only
+        // reference SELECT expressions.
+        if distinct.as_ref().is_some_and(|on| on.is_empty()) {
+            for (expr, _) in &order_by {
+                if let Either::Left(expr) = expr {
+                    if !Self::is_selected(expr, &select, &scope) {
+                        return errinput!(
+                            "ORDER BY expressions must appear in the SELECT list with DISTINCT"
+                        );
+                    }
+                }
+            }
+        }
+
+        // Build the aggregation of any GROUP BY clause and aggregate functions.
+        // Later clauses then reference the group and aggregate columns. HAVING
+        // without GROUP BY aggregates the whole input as a single group.
+        let aggregates = Self::collect_aggregates(
+            select
+                .iter()
+                .map(|(expr, _)| expr)
+                .chain(having.as_ref())
+                .chain(order_by.iter().filter_map(|(expr, _)| expr.as_ref().left()))
+                .chain(distinct.iter().flatten()),
+        )?;
+        if !group_by.is_empty() || !aggregates.is_empty() || having.is_some() {
+            if select.contains(&(ast::Expression::All, None)) {
+                return errinput!("can't use * with GROUP BY or aggregate functions");
+            }
+            node = self.build_aggregate(node, group_by, aggregates, &select, &mut scope)?;
+        }
+
+        if let Some(having) = having {
+            self.build_subqueries(&having, &mut scope)?;
+            node = Node::Filter {
+                source: Box::new(node),
+                predicate: Self::build_expression(having, &scope)?,
+            }
+        }
+
+        if !order_by.is_empty() {
+            let mut key = Vec::with_capacity(order_by.len());
+            for (expr, dir) in order_by {
+                let expr = match expr {
+                    Either::Left(expr) => {
+                        self.build_subqueries(&expr, &mut scope)?;
+                        Self::build_expression(expr, &scope)?
+                    }
+                    Either::Right(expr) => expr,
+                };
+                key.push((expr, dir.into()));
+            }
+            node = Node::Order {
+                source: Box::new(node),
+                key,
+            };
+        }
+
+        // DISTINCT ON keeps the first row of each distinct value, so it must
+        // be applied after ordering, but before the projection since the
+        // expressions reference the source columns.
+        let distinct = match distinct {
+            Some(on) if !on.is_empty() => {
+                let on = on
+                    .into_iter()
+                    .map(|expr| Self::build_expression(expr, &scope))
+                    .collect::<Result<_>>()?;
+                node = Node::Distinct {
+                    source: Box::new(node),
+                    on,
+                };
+                false
+            }
+            Some(_) => true,
+            None => false,
+        };
+
         if select.as_slice() != [(ast::Expression::All, None)] {
             let child_scope = scope.project(&select);
             let mut expressions = Vec::with_capacity(select.len());
             let mut aliases = Vec::with_capacity(select.len());
             for (expression, alias) in select {
+                if expression == ast::Expression::All {
+                    expressions.extend((0..node.columns()).map(Expression::Column));
+                    aliases.extend((0..node.columns()).map(|_| Label::None));
+                    continue;
+                }
+                self.build_subqueries(&expression, &mut scope)?;
                 expressions.push(Self::build_expression(expression, &scope)?);
                 aliases.push(Label::from(alias));
             }
@@ -99,6 +262,118 @@ impl<'a, C: Catalog> Planner<'a, C> {
             }
         }
 
+        if distinct {
+            node = Node::Distinct {
+                source: Box::new(node),
+                on: Vec::new(),
+            };
+        }
+
+        Self::build_offset_limit(node, offset, limit)
+    }
+
+    /// Builds a set operation between two queries, with ORDER BY, LIMIT and
+    /// OFFSET clauses applying to the combined result.
+    #[allow(clippy::too_many_arguments)]
+    fn build_set_operation(
+        &self,
+        mut scope: Scope,
+        with: Vec<(TableName, ast::Statement)>,
+        operator: ast::SetOperator,
+        all: bool,
+        left: ast::Statement,
+        right: ast::Statement,
+        order_by: Vec<(ast::Expression, ast::Direction)>,
+        limit: Option<ast::Expression>,
+        offset: Option<ast::Expression>,
+    ) -> Result<Node> {
+        self.build_ctes(with, &mut scope)?;
+        let left = self.build_query(left, scope.clone())?;
+        let right = self.build_query(right, scope.clone())?;
+        if left.columns() != right.columns() {
+            return errinput!(
+                "set operation queries must have the same number of columns, found {} and {}",
+                left.columns(),
+                right.columns()
+            );
+        }
+        let (left, right) = (Box::new(left), Box::new(right));
+        let mut node = match operator {
+            ast::SetOperator::Union => Node::Union { left, right, all },
+            ast::SetOperator::Intersect => Node::Intersect { left, right, all },
+            ast::SetOperator::Except => Node::Except { left, right, all },
+        };
+
+        // ORDER BY references the result columns, labeled by the left query.
+        if !order_by.is_empty() {
+            let mut scope = self.scope();
+            for i in 0..node.columns() {
+                let label = node.column_label(i);
+                if let Label::Qualified(table, _) = &label {
+                    scope.tables.insert(table.clone());
+                }
+                scope.add_column(label);
+            }
+            // Positions map directly to the result columns, which may not
+            // have a label.
+            let key = order_by
+                .into_iter()
+                .map(|(expr, dir)| {
+                    let expr = match expr {
+                        ast::Expression::Literal(ast::Literal::Integer(position)) => {
+                            match usize::try_from(position) {
+                                Ok(p) if (1..=node.columns()).contains(&p) => {
+                                    Expression::Column(p - 1)
+                                }
+                                _ => {
+                                    return errinput!(
+                                        "ORDER BY position {position} is out of range"
+                                    );
+                                }
+                            }
+                        }
+                        expr => Self::build_expression(expr, &scope)?,
+                    };
+                    Ok((expr, dir.into()))
+                })
+                .collect::<Result<_>>()?;
+            node = Node::Order {
+                source: Box::new(node),
+                key,
+            };
+        }
+        Self::build_offset_limit(node, offset, limit)
+    }
+
+    /// Builds the OFFSET and LIMIT clauses, which must be constant.
+    fn build_offset_limit(
+        mut node: Node,
+        offset: Option<ast::Expression>,
+        limit: Option<ast::Expression>,
+    ) -> Result<Node> {
+        // Parameters are bound after planning, but the limit and offset must
+        // be known when planning.
+        let has_parameters = |expr: &ast::Expression| {
+            !expr.walk(&mut |expr| !matches!(expr, ast::Expression::Parameter(_)))
+        };
+        if offset.as_ref().is_some_and(has_parameters) {
+            return errinput!("OFFSET can't use parameters");
+        }
+        if limit.as_ref().is_some_and(has_parameters) {
+            return errinput!("LIMIT can't use parameters");
+        }
+
+        if let Some(offset) = offset {
+            let offset = match Self::evaluate_constant(offset)? {
+                Value::Integer(offset) if offset >= 0 => offset as usize,
+                offset => return errinput!("invalid offset:{offset}"),
+            };
+            node = Node::Offset {
+                source: Box::new(node),
+                offset,
+            }
+        }
+
         if let Some(limit) = limit {
             let limit = match Self::evaluate_constant(limit)? {
                 Value::Integer(limit) if limit >= 0 => limit as usize,
@@ -109,32 +384,360 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 limit,
             }
         }
+        Ok(node)
+    }
+
+    /// Builds common table expressions in order and adds them to the scope,
+    /// so they can reference earlier ones and shadow tables and CTEs of outer
+    /// queries.
+    fn build_ctes(&self, with: Vec<(TableName, ast::Statement)>, scope: &mut Scope) -> Result<()> {
+        let mut names = HashSet::new();
+        for (name, query) in with {
+            if !names.insert(name.clone()) {
+                return errinput!("duplicate CTE name {name}");
+            }
+            let node = self.build_query(query, scope.with_ctes())?;
+            scope.ctes.insert(name, node);
+        }
+        Ok(())
+    }
+
+    /// Builds the subqueries in the given expression and adds them to the
+    /// scope, where build_expression() looks them up. Subqueries can reference
+    /// columns in the scope, i.e. be correlated, and are only supported where
+    /// they're built like this. Subqueries in group and aggregate expressions
+    /// of the scope have already been built by the aggregation.
+    fn build_subqueries(&self, expr: &ast::Expression, scope: &mut Scope) -> Result<()> {
+        fn collect<'a>(expr: &'a ast::Expression, statements: &mut Vec<&'a ast::Statement>) {
+            expr.walk(&mut |expr| {
+                if let ast::Expression::Subquery(statement)
+                | ast::Expression::Exists(statement)
+                | ast::Expression::InSubquery(_, statement) = expr
+                {
+                    statements.push(statement.as_ref());
+                }
+                true
+            });
+        }
+        let mut aggregated = Vec::new();
+        for expr in scope.aggregates.keys() {
+            collect(expr, &mut aggregated);
+        }
+        let mut statements = Vec::new();
+        collect(expr, &mut statements);
+        for statement in statements {
+            if scope.subqueries.contains_key(statement) || aggregated.contains(&statement) {
+                continue;
+            }
+            let node = self.build_query(statement.clone(), Scope::with_outer(scope))?;
+            scope.subqueries.insert(statement.clone(), node);
+        }
+        Ok(())
+    }
+
+    /// Resolves an ORDER BY expression referencing a SELECT alias or a 1-based
+    /// SELECT column position into the corresponding SELECT expression.
+    /// Positions within a * resolve directly to the source node's column,
+    /// which may not have a label.
+    fn resolve_order_by(
+        expr: ast::Expression,
+        select: &[(ast::Expression, Option<String>)],
+        source: &Node,
+    ) -> Result<Either<ast::Expression, Expression>> {
+        match expr {
+            ast::Expression::Literal(ast::Literal::Integer(position)) => {
+                let Some(mut index) = usize::try_from(position)
+                    .ok()
+                    .and_then(|p| p.checked_sub(1))
+                else {
+                    return errinput!("ORDER BY position {position} is out of range");
+                };
+                for (expr, _) in select {
+                    match expr {
+                        ast::Expression::All if index < source.columns() => {
+                            return Ok(Either::Right(Expression::Column(index)));
+                        }
+                        ast::Expression::All => index -= source.columns(),
+                        expr if index == 0 => return Ok(Either::Left(expr.clone())),
+                        _ => index -= 1,
+                    }
+                }
+                errinput!("ORDER BY position {position} is out of range")
+            }
+            ast::Expression::Column(None, name) => {
+                match select
+                    .iter()
+                    .find(|(_, alias)| alias.as_ref() == Some(&name))
+                {
+                    Some((expr, _)) => Ok(Either::Left(expr.clone())),
+                    None => Ok(Either::Left(ast::Expression::Column(None, name))),
+                }
+            }
+            expr => Ok(Either::Left(expr)),
+        }
+    }
+
+    /// Returns true if the expression is in the SELECT list, either as one of
+    /// its expressions or as a column reference resolving to a SELECT column.
+    fn is_selected(
+        expr: &ast::Expression,
+        select: &[(ast::Expression, Option<String>)],
+        scope: &Scope,
+    ) -> bool {
+        let lookup =
+            |table: &Option<String>, name: &str| scope.lookup_column(table.as_deref(), name).ok();
+        select.iter().any(|(selected, _)| match (selected, expr) {
+            (selected, expr) if selected == expr => true,
+            (ast::Expression::All, ast::Expression::Column(table, name)) => {
+                lookup(table, name).is_some()
+            }
+            (ast::Expression::Column(lt, ln), ast::Expression::Column(rt, rn)) => {
+                lookup(lt, ln).is_some() && lookup(lt, ln) == lookup(rt, rn)
+            }
+            _ => false,
+        })
+    }
+
+    /// Builds an aggregate node for the given GROUP BY expressions and
+    /// aggregate function calls, and replaces the scope with one containing
+    /// the group columns followed by the aggregate columns.
+    fn build_aggregate(
+        &self,
+        source: Node,
+        group_by: Vec<ast::Expression>,
+        aggregates: Vec<ast::Expression>,
+        select: &[(ast::Expression, Option<String>)],
+        scope: &mut Scope,
+    ) -> Result<Node> {
+        let mut child_scope = scope.spawn();
+
+        let mut group_exprs = Vec::with_capacity(group_by.len());
+        for mut expr in group_by {
+            // GROUP BY can reference SELECT aliases, unless they shadow a
+            // column name.
+            if let ast::Expression::Column(None, name) = &expr {
+                if scope.lookup_column(None, name).is_err() {
+                    if let Some((aliased, _)) = select
+                        .iter()
+                        .find(|(_, alias)| alias.as_ref() == Some(name))
+                    {
+                        expr = aliased.clone();
+                    }
+                }
+            }
+            if !Self::collect_aggregates([&expr])?.is_empty() {
+                return errinput!("GROUP BY can't contain aggregate functions");
+            }
+            let label = match &expr {
+                ast::Expression::Column(table, name) => {
+                    scope.columns[scope.lookup_column(table.as_deref(), name)?].clone()
+                }
+                _ => Label::None,
+            };
+            self.build_subqueries(&expr, scope)?;
+            group_exprs.push(Self::build_expression(expr.clone(), scope)?);
+            let index = child_scope.add_column(label);
+            child_scope.add_aggregate(expr, index);
+        }
+
+        let mut aggregate_exprs = Vec::with_capacity(aggregates.len());
+        for expr in aggregates {
+            let ast::Expression::Function {
+                name,
+                args,
+                distinct,
+            } = expr.clone()
+            else {
+                return errinput!("invalid aggregate expression {expr:?}");
+            };
+            let Ok([arg]) = <[_; 1]>::try_from(args) else {
+                return errinput!("aggregate function {name} takes 1 argument");
+            };
+            // count(*) counts all rows, regardless of NULLs.
+            let arg = match arg {
+                ast::Expression::All if name == "count" && !distinct => {
+                    Expression::Constant(Value::Boolean(true))
+                }
+                arg => {
+                    self.build_subqueries(&arg, scope)?;
+                    Self::build_expression(arg, scope)?
+                }
+            };
+            aggregate_exprs.push(match (name.as_str(), distinct) {
+                ("avg", false) => Aggregate::Average(arg),
+                ("count", false) => Aggregate::Count(arg),
+                ("count", true) => Aggregate::CountDistinct(arg),
+                ("max", false) => Aggregate::Max(arg),
+                ("min", false) => Aggregate::Min(arg),
+                ("sum", false) => Aggregate::Sum(arg),
+                (name, true) => return errinput!("DISTINCT is not supported for {name}"),
+                (name, false) => return errinput!("unknown aggregate function {name}"),
+            });
+            let index = child_scope.add_column(Label::None);
+            child_scope.add_aggregate(expr, index);
+        }
+
+        *scope = child_scope;
+        Ok(Node::Aggregate {
+            source: Box::new(source),
+            group_by: group_exprs,
+            aggregates: aggregate_exprs,
+        })
+    }
 
-        Ok(Plan::Select(node))
+    /// Returns true if the expression is an aggregate function call.
+    fn is_aggregate(expr: &ast::Expression) -> bool {
+        matches!(expr, ast::Expression::Function { name, .. }
+            if matches!(name.as_str(), "avg" | "count" | "max" | "min" | "sum"))
+    }
+
+    /// Collects the aggregate function calls in the given expressions, without
+    /// duplicates. Errors if aggregate functions are nested.
+    fn collect_aggregates<'e>(
+        exprs: impl IntoIterator<Item = &'e ast::Expression>,
+    ) -> Result<Vec<ast::Expression>> {
+        let mut aggregates = Vec::new();
+        for expr in exprs {
+            expr.walk(&mut |expr| {
+                if Self::is_aggregate(expr) && !aggregates.contains(expr) {
+                    aggregates.push(expr.clone());
+                }
+                true
+            });
+        }
+        for expr in &aggregates {
+            let ast::Expression::Function { name, args, .. } = expr else {
+                continue;
+            };
+            if args
+                .iter()
+                .any(|arg| !arg.walk(&mut |expr| !Self::is_aggregate(expr)))
+            {
+                return errinput!("aggregate function {name} can't contain aggregate functions");
+            }
+        }
+        Ok(aggregates)
     }
 
+    /// Builds the FROM clause. Multiple items are cross joined.
     fn build_from_clause(&self, from: Vec<ast::From>, scope: &mut Scope) -> Result<Node> {
         let mut items = from.into_iter();
-        let node = match items.next() {
+        let mut node = match items.next() {
             Some(from) => self.build_from(from, scope)?,
             None => return errinput!("no from items given"),
         };
-
+        for from in items {
+            node = Node::NestedLoopJoin {
+                left: Box::new(node),
+                right: Box::new(self.build_from(from, scope)?),
+                predicate: None,
+                outer: false,
+            };
+        }
         Ok(node)
     }
 
     fn build_from(&self, from: ast::From, parent_scope: &mut Scope) -> Result<Node> {
-        let mut scope = Scope::new();
+        let mut scope = parent_scope.with_ctes();
         let node = match from {
+            // CTE names are resolved before tables.
+            ast::From::Table { name, alias } if scope.ctes.contains_key(&name) => {
+                let source = Box::new(scope.ctes[&name].clone());
+                let table = alias.clone().unwrap_or_else(|| name.clone());
+                let node = Node::Cte {
+                    name,
+                    alias,
+                    source,
+                };
+                let labels: Vec<_> = (0..node.columns()).map(|i| node.column_label(i)).collect();
+                scope.add_subquery(&table, &labels)?;
+                node
+            }
             ast::From::Table { name, alias } => {
                 let table = self.catalog.must_get_table(&name)?;
                 scope.add_table(&table, alias.as_deref())?;
                 Node::Scan {
                     table,
+                    alias,
                     filter: None,
                 }
             }
-            _ => return errinput!("not support join {from:?}"),
+            // Subquery columns are qualified by the alias via a projection.
+            ast::From::Subquery { query, alias } => {
+                let node = self.build_query(*query, scope.with_ctes())?;
+                let aliases: Vec<_> = (0..node.columns())
+                    .map(|i| match node.column_label(i) {
+                        Label::Qualified(_, name) | Label::Unqualified(name) => {
+                            Label::Qualified(alias.clone(), name)
+                        }
+                        Label::None => Label::None,
+                    })
+                    .collect();
+                scope.add_subquery(&alias, &aliases)?;
+                Node::Projection {
+                    expressions: (0..node.columns()).map(Expression::Column).collect(),
+                    source: Box::new(node),
+                    aliases,
+                }
+            }
+            ast::From::Join {
+                left,
+                right,
+                r#type,
+                predicate,
+            } => {
+                let left = self.build_from(*left, &mut scope)?;
+                let right = self.build_from(*right, &mut scope)?;
+                let (left_size, right_size) = (left.columns(), right.columns());
+                if let Some(predicate) = &predicate {
+                    self.build_subqueries(predicate, &mut scope)?;
+                }
+                let mut predicate = predicate
+                    .map(|predicate| Self::build_expression(predicate, &scope))
+                    .transpose()?;
+                match r#type {
+                    ast::JoinType::Cross | ast::JoinType::Inner | ast::JoinType::Left => {
+                        Node::NestedLoopJoin {
+                            left: Box::new(left),
+                            right: Box::new(right),
+                            predicate,
+                            outer: r#type == ast::JoinType::Left,
+                        }
+                    }
+                    // Right joins are executed as left joins with swapped
+                    // sides, so the predicate columns are remapped and the
+                    // original column order is restored with a projection.
+                    ast::JoinType::Right => {
+                        // Remapping would also have to remap outer column
+                        // references in the subqueries.
+                        if predicate.as_ref().is_some_and(|p| p.has_subqueries()) {
+                            return errinput!(
+                                "subqueries are not supported in RIGHT JOIN predicates"
+                            );
+                        }
+                        predicate = predicate.map(|predicate| {
+                            predicate.map_columns(|i| match i < left_size {
+                                true => i + right_size,
+                                false => i - left_size,
+                            })
+                        });
+                        let size = left_size + right_size;
+                        Node::Projection {
+                            source: Box::new(Node::NestedLoopJoin {
+                                left: Box::new(right),
+                                right: Box::new(left),
+                                predicate,
+                                outer: true,
+                            }),
+                            expressions: (right_size..size)
+                                .chain(0..right_size)
+                                .map(Expression::Column)
+                                .collect(),
+                            aliases: vec![Label::None; size],
+                        }
+                    }
+                }
+            }
         };
         parent_scope.merge(scope)?;
         Ok(node)
@@ -144,9 +747,11 @@ impl<'a, C: Catalog> Planner<'a, C> {
         &self,
         table_name: String,
         columns: Option<Vec<String>>,
-        values: Vec<Vec<ast::Expression>>,
+        source: ast::InsertSource,
+        on_conflict: Option<ast::OnConflict>,
+        returning: Vec<(ast::Expression, Option<String>)>,
     ) -> Result<Plan> {
-        let table = self.catalog.get_table(&table_name)?.unwrap();
+        let table = self.catalog.must_get_table(&table_name)?;
         let mut column_map = None;
         if let Some(columns) = columns {
             let column_map = column_map.insert(HashMap::new());
@@ -154,29 +759,170 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 let Some(cidx) = table.columns.iter().position(|c| c.name == name) else {
                     return errinput!("column not found:{name}");
                 };
-                if column_map.insert(vidx, cidx).is_some() {
+                if column_map.insert(cidx, vidx).is_some() {
                     return errinput!("duplicate column:{name}");
                 };
             }
         }
-        let scope = Scope::new();
-        let values = values
-            .into_iter()
-            .map(|exprs| {
-                exprs
+        let source = match source {
+            ast::InsertSource::Values(values) => {
+                let scope = self.scope();
+                let rows = values
                     .into_iter()
-                    .map(|expr| Self::build_expression(expr, &scope))
-                    .collect()
-            })
-            .collect::<Result<_>>()?;
+                    .map(|exprs| {
+                        exprs
+                            .into_iter()
+                            .map(|expr| Self::build_expression(expr, &scope))
+                            .collect()
+                    })
+                    .collect::<Result<_>>()?;
+                Node::Values { rows }
+            }
+            ast::InsertSource::Select(query) => {
+                let node = self.build_query(*query, self.scope())?;
+                let columns = column_map.as_ref().map_or(table.columns.len(), |m| m.len());
+                if node.columns() != columns {
+                    return errinput!(
+                        "INSERT has {columns} target columns but SELECT returns {}",
+                        node.columns()
+                    );
+                }
+                node
+            }
+        };
 
         Ok(Plan::Insert {
+            on_conflict: on_conflict
+                .map(|on_conflict| self.build_on_conflict(&table, on_conflict))
+                .transpose()?,
+            returning: self.build_returning(&table, returning)?,
             table,
             column_map,
-            source: Node::Values { rows: values },
+            source,
         })
     }
 
+    fn build_update(
+        &self,
+        table_name: String,
+        set: Vec<(ColumnName, ast::Expression)>,
+        r#where: Option<ast::Expression>,
+        returning: Vec<(ast::Expression, Option<String>)>,
+    ) -> Result<Plan> {
+        let table = self.catalog.must_get_table(&table_name)?;
+        let mut scope = self.scope();
+        scope.add_table(&table, None)?;
+        let mut expressions: Vec<(usize, Expression)> = Vec::with_capacity(set.len());
+        for (name, expr) in set {
+            let index = scope.lookup_column(None, &name)?;
+            if expressions.iter().any(|(i, _)| *i == index) {
+                return errinput!("column {name} set multiple times");
+            }
+            self.build_subqueries(&expr, &mut scope)?;
+            expressions.push((index, Self::build_expression(expr, &scope)?));
+        }
+        Ok(Plan::Update {
+            source: self.build_table_source(&table, r#where, &mut scope)?,
+            returning: self.build_returning(&table, returning)?,
+            table,
+            expressions,
+        })
+    }
+
+    fn build_delete(
+        &self,
+        table_name: String,
+        r#where: Option<ast::Expression>,
+        returning: Vec<(ast::Expression, Option<String>)>,
+    ) -> Result<Plan> {
+        let table = self.catalog.must_get_table(&table_name)?;
+        let mut scope = self.scope();
+        scope.add_table(&table, None)?;
+        Ok(Plan::Delete {
+            source: self.build_table_source(&table, r#where, &mut scope)?,
+            returning: self.build_returning(&table, returning)?,
+            table,
+        })
+    }
+
+    /// Builds an ON CONFLICT action. Only primary key conflicts are
+    /// supported. The SET expressions can reference the existing row by the
+    /// table name, and the inserted row as excluded.
+    fn build_on_conflict(&self, table: &Table, on_conflict: ast::OnConflict) -> Result<OnConflict> {
+        if let Some(target) = &on_conflict.target {
+            let Some(index) = table.columns.iter().position(|c| &c.name == target) else {
+                return errinput!("unknown column:{target}");
+            };
+            if index != table.primary_key {
+                return errinput!("ON CONFLICT target must be the primary key column");
+            }
+        }
+        if on_conflict.set.is_empty() {
+            return Ok(OnConflict::Nothing);
+        }
+        let mut scope = self.scope();
+        scope.add_table(table, None)?;
+        scope.add_table(table, Some("excluded"))?;
+        // Unqualified columns reference the existing row.
+        for indexes in scope.unqualified.values_mut() {
+            indexes.retain(|i| *i < table.columns.len());
+        }
+        let mut expressions: Vec<(usize, Expression)> = Vec::with_capacity(on_conflict.set.len());
+        for (name, expr) in on_conflict.set {
+            let index = scope.lookup_column(Some(&table.name), &name)?;
+            if expressions.iter().any(|(i, _)| *i == index) {
+                return errinput!("column {name} set multiple times");
+            }
+            expressions.push((index, Self::build_expression(expr, &scope)?));
+        }
+        Ok(OnConflict::Update(expressions))
+    }
+
+    /// Builds RETURNING expressions, evaluated against the affected table
+    /// rows. * expands to all table columns.
+    fn build_returning(
+        &self,
+        table: &Table,
+        returning: Vec<(ast::Expression, Option<String>)>,
+    ) -> Result<Returning> {
+        let mut scope = self.scope();
+        scope.add_table(table, None)?;
+        let mut expressions = Vec::with_capacity(returning.len());
+        for (expr, alias) in returning {
+            if expr == ast::Expression::All {
+                expressions
+                    .extend((0..table.columns.len()).map(|i| (Expression::Column(i), Label::None)));
+                continue;
+            }
+            let expr = Self::build_expression(expr, &scope)?;
+            expressions.push((expr, alias.map_or(Label::None, Label::Unqualified)));
+        }
+        Ok(expressions)
+    }
+
+    /// Builds a source node for the rows of a table, filtered by an optional
+    /// WHERE predicate.
+    fn build_table_source(
+        &self,
+        table: &Table,
+        r#where: Option<ast::Expression>,
+        scope: &mut Scope,
+    ) -> Result<Node> {
+        let mut node = Node::Scan {
+            table: table.clone(),
+            alias: None,
+            filter: None,
+        };
+        if let Some(r#where) = r#where {
+            self.build_subqueries(&r#where, scope)?;
+            node = Node::Filter {
+                source: Box::new(node),
+                predicate: Self::build_expression(r#where, scope)?,
+            };
+        }
+        Ok(node)
+    }
+
     fn build_create_table(&self, table_name: String, columns: Vec<ast::Column>) -> Result<Plan> {
         let Some(primary_key) = columns.iter().position(|c| c.primary_key) else {
             return errinput!("no primary key for this table:{table_name}");
@@ -184,6 +930,9 @@ impl<'a, C: Catalog> Planner<'a, C> {
         if columns.iter().filter(|c| c.primary_key).count() > 1 {
             return errinput!("multiple primary key for this table:{table_name}");
         };
+        if let Some(column) = columns.iter().find(|c| c.primary_key && c.index) {
+            return errinput!("can't index primary key column {}", column.name);
+        }
         let columns = columns
             .into_iter()
             .map(|c| {
@@ -198,6 +947,8 @@ impl<'a, C: Catalog> Planner<'a, C> {
                         None => None,
                     },
                     unique: c.unique,
+                    index: c.index,
+                    references: c.references,
                 })
             })
             .collect::<Result<_>>()?;
@@ -214,11 +965,56 @@ impl<'a, C: Catalog> Planner<'a, C> {
         Self::build_expression(expr, &Scope::new())?.evaluate(None)
     }
 
+    /// Returns the type of an expression if known at plan time, i.e. for
+    /// constants and function calls with known result types.
+    fn constant_type(expr: &Expression) -> Option<DataType> {
+        match expr {
+            Expression::Constant(value) => value.data_type(),
+            Expression::Function(name, args) => function::lookup(name)?
+                .check(&args.iter().map(Self::constant_type).collect_vec())
+                .ok()?,
+            Expression::UserFunction(function, _) => Some(function.return_type.clone()),
+            Expression::Cast(_, data_type) => Some(data_type.clone()),
+            _ => None,
+        }
+    }
+
+    /// Builds a [I]LIKE pattern match. The ESCAPE character must be constant.
+    fn build_like(
+        lhs: ast::Expression,
+        rhs: ast::Expression,
+        escape: Option<Box<ast::Expression>>,
+        case_insensitive: bool,
+        scope: &Scope,
+    ) -> Result<Expression> {
+        let escape = match escape {
+            Some(escape) => match Self::build_expression(*escape, scope)?.evaluate(None)? {
+                Value::String(s) if s.chars().count() <= 1 => s.chars().next(),
+                value => return errinput!("ESCAPE must be a single character, found {value}"),
+            },
+            None => None,
+        };
+        let kind = PatternKind::Like {
+            escape,
+            case_insensitive,
+        };
+        Expression::pattern_match(
+            Self::build_expression(lhs, scope)?,
+            Self::build_expression(rhs, scope)?,
+            kind,
+        )
+    }
+
     pub fn build_expression(expr: ast::Expression, scope: &Scope) -> Result<Expression> {
         use Expression::*;
         let build_fn = |expr: Box<ast::Expression>| -> Result<Box<Expression>> {
             Ok(Box::new(Self::build_expression(*expr, scope)?))
         };
+        // Expressions that have been aggregated, i.e. aggregate function calls
+        // and GROUP BY expressions, are looked up as aggregate columns.
+        if let Some(index) = scope.lookup_aggregate(&expr) {
+            return Ok(Column(index));
+        }
         let ret = match expr {
             ast::Expression::Literal(lit) => Constant(match lit {
                 ast::Literal::Null => Value::Null,
@@ -226,18 +1022,83 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 ast::Literal::Integer(i) => Value::Integer(i),
                 ast::Literal::Float(f) => Value::Float(f),
                 ast::Literal::String(s) => Value::String(s),
+                ast::Literal::Bytes(b) => Value::Bytes(b),
             }),
-            ast::Expression::Column(table_name, column_name) => {
-                Column(scope.lookup_column(table_name.as_deref(), column_name.as_str())?)
-            }
-            ast::Expression::Function(function_name, mut expression) => {
-                match (function_name.as_str(), expression.len()) {
-                    ("sqrt", 1) => SquareRoot(build_fn(Box::new(expression.remove(0)))?),
-                    (name, n) => {
-                        return errinput!("unknown function {name} with {n} arguments");
+            ast::Expression::Parameter(index) => Parameter(index),
+            // Columns that don't exist in the scope may reference the outer
+            // query, in a correlated subquery.
+            ast::Expression::Column(table, name) => {
+                match scope.lookup_column(table.as_deref(), &name) {
+                    Ok(index) => Column(index),
+                    Err(err) => match scope.lookup_outer_column(table.as_deref(), &name)? {
+                        Some((index, label)) => OuterColumn(index, label),
+                        None => return Err(err),
+                    },
+                }
+            }
+            ast::Expression::Subquery(statement) => {
+                Subquery(Box::new(scope.lookup_subquery(&statement, true)?))
+            }
+            ast::Expression::Exists(statement) => {
+                Exists(Box::new(scope.lookup_subquery(&statement, false)?))
+            }
+            ast::Expression::InSubquery(expr, statement) => InSubquery(
+                build_fn(expr)?,
+                Box::new(scope.lookup_subquery(&statement, true)?),
+            ),
+            ref expr @ ast::Expression::Function { ref name, .. } if Self::is_aggregate(expr) => {
+                return errinput!("aggregate function {name} not allowed here");
+            }
+            ast::Expression::Function {
+                name,
+                args,
+                distinct,
+            } => {
+                if distinct {
+                    return errinput!("DISTINCT is only allowed in aggregate functions");
+                }
+                let args: Vec<_> = args
+                    .into_iter()
+                    .map(|arg| Self::build_expression(arg, scope))
+                    .try_collect()?;
+                if let Some(function) = function::lookup(&name) {
+                    // Check the arguments, with types known at plan time.
+                    function.check(&args.iter().map(Self::constant_type).collect_vec())?;
+                    Function(name, args)
+                } else if let Some(function) = scope.functions.get(&name) {
+                    if args.len() != function.arity {
+                        return errinput!(
+                            "{name}() takes {} argument{}, found {}",
+                            function.arity,
+                            if function.arity == 1 { "" } else { "s" },
+                            args.len()
+                        );
                     }
+                    UserFunction(function.clone(), args)
+                } else {
+                    return errinput!("unknown function {name}");
                 }
             }
+            ast::Expression::Case {
+                operand,
+                when,
+                r#else,
+            } => Case(
+                operand.map(build_fn).transpose()?,
+                when.into_iter()
+                    .map(|(condition, result)| {
+                        Ok((
+                            Self::build_expression(condition, scope)?,
+                            Self::build_expression(result, scope)?,
+                        ))
+                    })
+                    .collect::<Result<_>>()?,
+                match r#else {
+                    Some(expr) => build_fn(expr)?,
+                    None => Box::new(Constant(Value::Null)),
+                },
+            ),
+            ast::Expression::Cast(expr, data_type) => Cast(build_fn(expr)?, data_type),
             ast::Expression::Operator(op) => match op {
                 ast::Operator::And(lhs, rhs) => And(build_fn(lhs)?, build_fn(rhs)?),
                 ast::Operator::Or(lhs, rhs) => Or(build_fn(lhs)?, build_fn(rhs)?),
@@ -264,11 +1125,52 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 ast::Operator::Divide(lhs, rhs) => Divide(build_fn(lhs)?, build_fn(rhs)?),
                 ast::Operator::Remainder(lhs, rhs) => Remainder(build_fn(lhs)?, build_fn(rhs)?),
                 ast::Operator::Exponential(lhs, rhs) => Exponential(build_fn(lhs)?, build_fn(rhs)?),
+                ast::Operator::JsonGet(lhs, rhs) => JsonGet(build_fn(lhs)?, build_fn(rhs)?),
+                ast::Operator::JsonGetText(lhs, rhs) => JsonGetText(build_fn(lhs)?, build_fn(rhs)?),
                 ast::Operator::Identity(expr) => Identity(build_fn(expr)?),
                 ast::Operator::Negate(expr) => Negate(build_fn(expr)?),
 
-                ast::Operator::Like(lhs, rhs) => Like(build_fn(lhs)?, build_fn(rhs)?),
-                op => return errinput!("unsupported operator:{op:?}"),
+                ast::Operator::Is(expr, literal) => {
+                    let value = match literal {
+                        ast::Literal::Null => Value::Null,
+                        ast::Literal::Float(f) if f.is_nan() => Value::Float(f),
+                        literal => return errinput!("invalid IS value {literal:?}"),
+                    };
+                    Is(build_fn(expr)?, value)
+                }
+                // a BETWEEN b AND c is lowered to a >= b AND a <= c.
+                ast::Operator::Between(expr, low, high) => Self::build_expression(
+                    ast::Operator::And(
+                        Box::new(ast::Operator::GreaterThanOrEqual(expr.clone(), low).into()),
+                        Box::new(ast::Operator::LessThanOrEqual(expr, high).into()),
+                    )
+                    .into(),
+                    scope,
+                )?,
+                // a IN (b, c) is lowered to a = b OR a = c, which gives the
+                // SQL NULL semantics and allows index lookups.
+                ast::Operator::InList(expr, list) => {
+                    let expr = build_fn(expr)?;
+                    let mut equals = Vec::with_capacity(list.len());
+                    for item in list {
+                        equals.push(Equal(expr.clone(), build_fn(Box::new(item))?));
+                    }
+                    equals
+                        .into_iter()
+                        .reduce(|lhs, rhs| Or(lhs.into(), rhs.into()))
+                        .expect("empty IN list")
+                }
+                ast::Operator::Like(lhs, rhs, escape) => {
+                    Self::build_like(*lhs, *rhs, escape, false, scope)?
+                }
+                ast::Operator::ILike(lhs, rhs, escape) => {
+                    Self::build_like(*lhs, *rhs, escape, true, scope)?
+                }
+                ast::Operator::Regexp(lhs, rhs) => Expression::pattern_match(
+                    Self::build_expression(*lhs, scope)?,
+                    Self::build_expression(*rhs, scope)?,
+                    PatternKind::Regexp,
+                )?,
             },
             e => return errinput!("unsupported expression:{e:?}"),
         };
@@ -276,6 +1178,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
     }
 }
 
+#[derive(Clone)]
 pub struct Scope {
     columns: Vec<Label>,
     tables: HashSet<String>,
@@ -284,6 +1187,23 @@ pub struct Scope {
 
     /// table_name.column_nam --> column_index
     unqualified: HashMap<String, Vec<usize>>,
+
+    /// Aggregated expressions, i.e. aggregate function calls and GROUP BY
+    /// expressions --> column_index
+    aggregates: HashMap<ast::Expression, usize>,
+
+    /// Built subqueries in this scope, see Planner::build_subqueries().
+    subqueries: HashMap<ast::Statement, Node>,
+
+    /// The outer query's scope, for subqueries.
+    outer: Option<Box<Scope>>,
+
+    /// Common table expressions visible in this scope, including those of
+    /// outer queries.
+    ctes: HashMap<TableName, Node>,
+
+    /// User-defined functions, from the catalog.
+    functions: FunctionRegistry,
 }
 
 impl Scope {
@@ -293,18 +1213,47 @@ impl Scope {
             tables: HashSet::new(),
             qualified: HashMap::new(),
             unqualified: HashMap::new(),
+            aggregates: HashMap::new(),
+            subqueries: HashMap::new(),
+            outer: None,
+            ctes: HashMap::new(),
+            functions: FunctionRegistry::default(),
         }
     }
 
+    /// Creates an empty scope with this scope's CTEs, e.g. for FROM items.
+    fn with_ctes(&self) -> Self {
+        let mut scope = Scope::new();
+        scope.ctes = self.ctes.clone();
+        scope.functions = self.functions.clone();
+        scope
+    }
+
+    /// Creates a new scope for a subquery of the given outer scope.
+    fn with_outer(outer: &Scope) -> Self {
+        let mut scope = outer.with_ctes();
+        scope.outer = Some(Box::new(outer.clone()));
+        scope
+    }
+
     fn spawn(&self) -> Self {
         let mut child = Scope::new();
         child.tables = self.tables.clone();
+        child.outer = self.outer.clone();
+        child.ctes = self.ctes.clone();
+        child.functions = self.functions.clone();
         child
     }
 
     fn project(&self, expression: &[(ast::Expression, Option<String>)]) -> Self {
         let mut child = self.spawn();
         for (expr, alias) in expression {
+            if *expr == ast::Expression::All {
+                for label in &self.columns {
+                    child.add_column(label.clone());
+                }
+                continue;
+            }
             let mut label = Label::None;
             if let Some(alias) = alias {
                 label = Label::Unqualified(alias.clone());
@@ -346,6 +1295,18 @@ impl Scope {
         Ok(())
     }
 
+    /// Adds the columns of a FROM subquery or CTE with the given name.
+    fn add_subquery(&mut self, alias: &str, labels: &[Label]) -> Result<()> {
+        if self.tables.contains(alias) {
+            return errinput!("add met duplicate table:{alias}");
+        }
+        for label in labels {
+            self.add_column(label.clone());
+        }
+        self.tables.insert(alias.to_string());
+        Ok(())
+    }
+
     fn add_column(&mut self, label: Label) -> usize {
         let index = self.columns.len();
         if let Label::Qualified(table, column) = &label {
@@ -362,6 +1323,62 @@ impl Scope {
         index
     }
 
+    fn add_aggregate(&mut self, expr: ast::Expression, index: usize) {
+        self.aggregates.insert(expr, index);
+    }
+
+    fn lookup_aggregate(&self, expr: &ast::Expression) -> Option<usize> {
+        self.aggregates.get(expr).copied()
+    }
+
+    /// Looks up a built subquery. Scalar and IN subqueries must return a
+    /// single column.
+    fn lookup_subquery(&self, statement: &ast::Statement, single: bool) -> Result<Node> {
+        let Some(node) = self.subqueries.get(statement) else {
+            return errinput!("subqueries are not supported here");
+        };
+        if single && node.columns() != 1 {
+            return errinput!("subquery must return a single column");
+        }
+        Ok(node.clone())
+    }
+
+    /// Looks up a column in the outer query's scope, if the column isn't
+    /// known in this scope. Returns its index and label.
+    fn lookup_outer_column(
+        &self,
+        table: Option<&str>,
+        name: &str,
+    ) -> Result<Option<(usize, Label)>> {
+        let Some(outer) = self.outer.as_ref() else {
+            return Ok(None);
+        };
+        let known = match table {
+            Some(table) => self.tables.contains(table),
+            None => self.unqualified.contains_key(name),
+        };
+        if known {
+            return Ok(None);
+        }
+        if let Ok(index) = outer.lookup_column(table, name) {
+            return Ok(Some((index, outer.columns[index].clone())));
+        }
+        // Only the enclosing query's row is bound when executing a subquery.
+        let mut scope = outer.outer.as_deref();
+        while let Some(outer) = scope {
+            if outer.lookup_column(table, name).is_ok() {
+                let name = table
+                    .map(|t| format!("{t}.{name}"))
+                    .unwrap_or(name.to_string());
+                return errinput!(
+                    "correlated subqueries can only reference the enclosing query, found {name}"
+                );
+            }
+            scope = outer.outer.as_deref();
+        }
+        Ok(None)
+    }
+
     fn lookup_column(&self, table: Option<&str>, name: &str) -> Result<usize> {
         let fmtname = || {
             table
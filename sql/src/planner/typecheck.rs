@@ -0,0 +1,359 @@
+use super::{Aggregate, Node, OnConflict, Plan};
+use crate::{
+    errinput,
+    error::Result,
+    types::{
+        expression::Expression,
+        schema::{ColumnType, DataType, Table},
+    },
+};
+
+/// Type checks the plan, rejecting ill-typed expressions and writes of the
+/// wrong type into table columns, and inserts implicit numeric coercions.
+pub fn check_plan(plan: Plan) -> Result<Plan> {
+    Ok(match plan {
+        Plan::Select(root) => Plan::Select(check_node(root)?),
+        Plan::Insert {
+            table,
+            column_map,
+            source,
+            on_conflict,
+            returning,
+        } => {
+            let source = check_node(source)?;
+            // The column map maps table columns to source columns. Missing
+            // and excess values are rejected at execution time.
+            for column in 0..table.columns.len() {
+                let index = match &column_map {
+                    Some(column_map) => column_map.get(&column).copied(),
+                    None => Some(column),
+                };
+                if let Some(index) = index.filter(|index| *index < source.columns()) {
+                    check_assign(&table, column, &source.column_type(index)?)?;
+                }
+            }
+            // ON CONFLICT expressions see the existing row followed by the
+            // inserted row.
+            let on_conflict = match on_conflict {
+                Some(OnConflict::Update(expressions)) => {
+                    let input = |i: usize| Ok(table_column(&table, i % table.columns.len()));
+                    Some(OnConflict::Update(check_assignments(
+                        &table,
+                        expressions,
+                        &input,
+                    )?))
+                }
+                on_conflict => on_conflict,
+            };
+            let returning = check_returning(&table, returning)?;
+            Plan::Insert {
+                table,
+                column_map,
+                source,
+                on_conflict,
+                returning,
+            }
+        }
+        Plan::Update {
+            table,
+            source,
+            expressions,
+            returning,
+        } => {
+            let source = check_node(source)?;
+            let expressions = check_assignments(&table, expressions, &|i| source.column_type(i))?;
+            let returning = check_returning(&table, returning)?;
+            Plan::Update {
+                table,
+                source,
+                expressions,
+                returning,
+            }
+        }
+        Plan::Delete {
+            table,
+            source,
+            returning,
+        } => {
+            let source = check_node(source)?;
+            let returning = check_returning(&table, returning)?;
+            Plan::Delete {
+                table,
+                source,
+                returning,
+            }
+        }
+        plan @ (Plan::CreateTable { .. } | Plan::DropTable { .. } | Plan::CreateIndex { .. }) => {
+            plan
+        }
+    })
+}
+
+/// Type checks the node bottom-up, coercing each node's expressions against
+/// the column types of its input rows.
+pub fn check_node(node: Node) -> Result<Node> {
+    node.transform(&Ok, &check)
+}
+
+/// Type checks a single node, whose sources have already been checked.
+fn check(node: Node) -> Result<Node> {
+    Ok(match node {
+        Node::Filter { source, predicate } => {
+            let predicate = predicate.coerce(&|i| source.column_type(i))?;
+            check_predicate(&predicate, &|i| source.column_type(i), "filter")?;
+            Node::Filter { source, predicate }
+        }
+
+        Node::Scan {
+            table,
+            alias,
+            filter: Some(filter),
+        } => {
+            let input = |i| Ok(table_column(&table, i));
+            let filter = filter.coerce(&input)?;
+            check_predicate(&filter, &input, "filter")?;
+            Node::Scan {
+                table,
+                alias,
+                filter: Some(filter),
+            }
+        }
+
+        Node::NestedLoopJoin {
+            left,
+            right,
+            predicate: Some(predicate),
+            outer,
+        } => {
+            let input = |i| match left.columns() {
+                size if i < size => left.column_type(i),
+                size => right.column_type(i - size),
+            };
+            let predicate = predicate.coerce(&input)?;
+            check_predicate(&predicate, &input, "join")?;
+            Node::NestedLoopJoin {
+                left,
+                right,
+                predicate: Some(predicate),
+                outer,
+            }
+        }
+
+        Node::Projection {
+            source,
+            expressions,
+            aliases,
+        } => {
+            let expressions = expressions
+                .into_iter()
+                .map(|expr| expr.coerce(&|i| source.column_type(i)))
+                .collect::<Result<_>>()?;
+            Node::Projection {
+                source,
+                expressions,
+                aliases,
+            }
+        }
+
+        Node::Aggregate {
+            source,
+            group_by,
+            aggregates,
+        } => {
+            let input = |i| source.column_type(i);
+            let group_by = group_by
+                .into_iter()
+                .map(|expr| expr.coerce(&input))
+                .collect::<Result<_>>()?;
+            let aggregates = aggregates
+                .into_iter()
+                .map(|aggregate| {
+                    let ty = aggregate.expression().data_type(&input)?;
+                    Ok(match aggregate {
+                        Aggregate::Sum(_) | Aggregate::Average(_) if !ty.is_numeric() => {
+                            let op = match aggregate {
+                                Aggregate::Sum(_) => "sum",
+                                _ => "average",
+                            };
+                            return errinput!("can't {op} {}", ty.name());
+                        }
+                        Aggregate::Average(expr) => Aggregate::Average(expr.coerce(&input)?),
+                        Aggregate::Count(expr) => Aggregate::Count(expr.coerce(&input)?),
+                        Aggregate::CountDistinct(expr) => {
+                            Aggregate::CountDistinct(expr.coerce(&input)?)
+                        }
+                        Aggregate::Max(expr) => Aggregate::Max(expr.coerce(&input)?),
+                        Aggregate::Min(expr) => Aggregate::Min(expr.coerce(&input)?),
+                        Aggregate::Sum(expr) => Aggregate::Sum(expr.coerce(&input)?),
+                    })
+                })
+                .collect::<Result<_>>()?;
+            Node::Aggregate {
+                source,
+                group_by,
+                aggregates,
+            }
+        }
+
+        Node::Distinct { source, on } => {
+            let on = on
+                .into_iter()
+                .map(|expr| expr.coerce(&|i| source.column_type(i)))
+                .collect::<Result<_>>()?;
+            Node::Distinct { source, on }
+        }
+
+        Node::Order { source, key } => {
+            let key = key
+                .into_iter()
+                .map(|(expr, dir)| Ok((expr.coerce(&|i| source.column_type(i))?, dir)))
+                .collect::<Result<_>>()?;
+            Node::Order { source, key }
+        }
+
+        Node::Values { rows } => {
+            let rows = rows
+                .into_iter()
+                .map(|row| {
+                    row.into_iter()
+                        .map(|expr| expr.coerce(&|_| Ok(ColumnType::unknown())))
+                        .collect()
+                })
+                .collect::<Result<_>>()?;
+            Node::Values { rows }
+        }
+
+        Node::Union { left, right, all } => {
+            let (left, right) = coerce_set_operation(*left, *right)?;
+            Node::Union { left, right, all }
+        }
+        Node::Intersect { left, right, all } => {
+            let (left, right) = coerce_set_operation(*left, *right)?;
+            Node::Intersect { left, right, all }
+        }
+        Node::Except { left, right, all } => {
+            let (left, right) = coerce_set_operation(*left, *right)?;
+            Node::Except { left, right, all }
+        }
+
+        node => node,
+    })
+}
+
+/// Checks that set operation columns have compatible types. Where different
+/// types are combined, e.g. integers and floats, the columns are cast to
+/// their common type.
+fn coerce_set_operation(left: Node, right: Node) -> Result<(Box<Node>, Box<Node>)> {
+    let mut cast_left = Vec::new();
+    let mut cast_right = Vec::new();
+    for i in 0..left.columns() {
+        let (l, r) = (left.column_type(i)?, right.column_type(i)?);
+        let (Some(l), Some(r)) = (l.data_type, r.data_type) else {
+            continue;
+        };
+        let Some(common) = l.common(&r) else {
+            return errinput!("can't combine {l} and {r} columns in set operation");
+        };
+        if !common.accepts_type(&l) {
+            cast_left.push((i, common.clone()));
+        }
+        if !common.accepts_type(&r) {
+            cast_right.push((i, common));
+        }
+    }
+    Ok((
+        Box::new(cast(left, &cast_left)),
+        Box::new(cast(right, &cast_right)),
+    ))
+}
+
+/// Casts the given columns of the node to the given types, via a projection.
+fn cast(source: Node, columns: &[(usize, DataType)]) -> Node {
+    if columns.is_empty() {
+        return source;
+    }
+    let (expressions, aliases) = (0..source.columns())
+        .map(|i| {
+            let column = Expression::Column(i);
+            let expr = match columns.iter().find(|(c, _)| *c == i) {
+                Some((_, data_type)) => Expression::Cast(Box::new(column), data_type.clone()),
+                None => column,
+            };
+            (expr, source.column_label(i))
+        })
+        .unzip();
+    Node::Projection {
+        source: Box::new(source),
+        expressions,
+        aliases,
+    }
+}
+
+/// Checks that a filter or join predicate is boolean.
+fn check_predicate(
+    predicate: &Expression,
+    input: &dyn Fn(usize) -> Result<ColumnType>,
+    kind: &str,
+) -> Result<()> {
+    let ty = predicate.data_type(input)?;
+    if !ty.is(DataType::Boolean) {
+        return errinput!("{kind} predicate must be boolean, found {}", ty.name());
+    }
+    Ok(())
+}
+
+/// Type checks column assignments, for UPDATE and ON CONFLICT.
+fn check_assignments(
+    table: &Table,
+    expressions: Vec<(usize, Expression)>,
+    input: &dyn Fn(usize) -> Result<ColumnType>,
+) -> Result<Vec<(usize, Expression)>> {
+    expressions
+        .into_iter()
+        .map(|(column, expr)| {
+            let expr = expr.coerce(input)?;
+            check_assign(table, column, &expr.data_type(input)?)?;
+            Ok((column, expr))
+        })
+        .collect()
+}
+
+/// Type checks RETURNING expressions against the table's columns.
+fn check_returning(table: &Table, returning: super::Returning) -> Result<super::Returning> {
+    returning
+        .into_iter()
+        .map(|(expr, label)| Ok((expr.coerce(&|i| Ok(table_column(table, i)))?, label)))
+        .collect()
+}
+
+/// Checks that a value of the given type can be written to a table column.
+/// Numbers are converted to float and decimal columns when written, and
+/// strings to JSON, date and time columns, see DataType::coerce().
+fn check_assign(table: &Table, index: usize, ty: &ColumnType) -> Result<()> {
+    let column = &table.columns[index];
+    match &ty.data_type {
+        Some(DataType::String)
+            if column.data_type == DataType::Json || column.data_type.is_temporal() =>
+        {
+            Ok(())
+        }
+        Some(data_type)
+            if data_type.is_numeric()
+                && matches!(column.data_type, DataType::Float | DataType::Decimal(..)) =>
+        {
+            Ok(())
+        }
+        Some(data_type) if !data_type.is_same_kind(&column.data_type) => errinput!(
+            "invalid datatype {data_type} for {} column {}",
+            column.data_type,
+            column.name
+        ),
+        Some(_) | None => Ok(()),
+    }
+}
+
+/// Returns the type of a table column.
+fn table_column(table: &Table, index: usize) -> ColumnType {
+    let column = &table.columns[index];
+    ColumnType::new(column.data_type.clone(), column.nullable)
+}
@@ -138,6 +138,10 @@ impl<E: Engine> TransactionInner<E> {
         self.write_version(key, Some(value))
     }
 
+    pub fn delete(&self, key: &[u8]) -> Result<()> {
+        self.write_version(key, None)
+    }
+
     pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let mut engine = self.engine.lock()?;
         let from = Key::Version(key.into(), 0).encode();
@@ -0,0 +1,321 @@
+//! Dates, timestamps and intervals. Dates are stored as days since the Unix
+//! epoch (1970-01-01), and timestamps as microseconds since the epoch, both
+//! without time zones. Civil date conversions use the proleptic Gregorian
+//! calendar, see http://howardhinnant.github.io/date_algorithms.html.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{errinput, error::Result};
+
+/// Microseconds per second, minute, hour and day.
+const SECOND: i64 = 1_000_000;
+const MINUTE: i64 = 60 * SECOND;
+const HOUR: i64 = 60 * MINUTE;
+pub const DAY: i64 = 24 * HOUR;
+
+/// The supported year range, such that dates format as YYYY-MM-DD.
+const YEARS: std::ops::RangeInclusive<i64> = 1..=9999;
+
+/// Returns the days since the epoch for a civil date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Returns the civil date (year, month, day) for days since the epoch.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Returns the number of days in the given month.
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Checks that a date (as days since the epoch) is within the supported year
+/// range.
+pub fn check_date(days: i64) -> Result<i32> {
+    match YEARS.contains(&civil_from_days(days).0) {
+        true => Ok(days as i32),
+        false => errinput!("date out of range"),
+    }
+}
+
+/// Checks that a timestamp is within the supported year range.
+pub fn check_timestamp(micros: i64) -> Result<i64> {
+    check_date(micros.div_euclid(DAY))?;
+    Ok(micros)
+}
+
+/// Parses a YYYY-MM-DD date, returning days since the epoch.
+pub fn parse_date(s: &str) -> Option<i32> {
+    let mut parts = s.splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let (year, month, day): (i64, u32, u32) = (
+        parse_digits(year)?,
+        parse_digits(month)?,
+        parse_digits(day)?,
+    );
+    if !YEARS.contains(&year) || !(1..=12).contains(&month) {
+        return None;
+    }
+    if day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) as i32)
+}
+
+/// Formats a date as YYYY-MM-DD.
+pub fn format_date(days: i32) -> String {
+    let (year, month, day) = civil_from_days(days as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Parses a YYYY-MM-DD[ HH:MM:SS[.ffffff]] timestamp, where the date and
+/// time can also be separated by T. Returns microseconds since the epoch.
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    let (date, time) = match s.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time.trim_start())),
+        None => (s, None),
+    };
+    let days = parse_date(date)? as i64;
+    let time = match time {
+        Some(time) => parse_time(time).filter(|time| (0..DAY).contains(time))?,
+        None => 0,
+    };
+    Some(days * DAY + time)
+}
+
+/// Formats a timestamp as YYYY-MM-DD HH:MM:SS[.ffffff], omitting trailing
+/// zeroes in the fractional seconds.
+pub fn format_timestamp(micros: i64) -> String {
+    let date = format_date(micros.div_euclid(DAY) as i32);
+    format!("{date} {}", format_time(micros.rem_euclid(DAY)))
+}
+
+/// Parses a HH:MM[:SS[.ffffff]] time of day or duration, as microseconds.
+fn parse_time(s: &str) -> Option<i64> {
+    let mut parts = s.splitn(3, ':');
+    let (hours, minutes, seconds) = (parts.next()?, parts.next()?, parts.next());
+    if minutes.len() != 2 {
+        return None;
+    }
+    let (hours, minutes): (i64, i64) = (parse_digits(hours)?, parse_digits(minutes)?);
+    if minutes >= 60 {
+        return None;
+    }
+    let mut micros = hours.checked_mul(HOUR)? + minutes * MINUTE;
+    if let Some(seconds) = seconds {
+        let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+        if seconds.len() != 2 || fraction.len() > 6 {
+            return None;
+        }
+        let seconds: i64 = parse_digits(seconds)?;
+        if seconds >= 60 {
+            return None;
+        }
+        micros += seconds * SECOND;
+        if !fraction.is_empty() {
+            micros += parse_digits::<i64>(fraction)? * 10_i64.pow(6 - fraction.len() as u32);
+        }
+    }
+    Some(micros)
+}
+
+/// Formats a non-negative duration as HH:MM:SS[.ffffff].
+fn format_time(micros: i64) -> String {
+    let (hours, minutes) = (micros / HOUR, micros % HOUR / MINUTE);
+    let (seconds, fraction) = (micros % MINUTE / SECOND, micros % SECOND);
+    let mut time = format!("{hours:02}:{minutes:02}:{seconds:02}");
+    if fraction != 0 {
+        time.push_str(format!(".{fraction:06}").trim_end_matches('0'));
+    }
+    time
+}
+
+/// Parses an unsigned number consisting only of ASCII digits.
+fn parse_digits<T: std::str::FromStr>(s: &str) -> Option<T> {
+    match !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+        true => s.parse().ok(),
+        false => None,
+    }
+}
+
+/// Returns the timestamp for a date, at midnight.
+pub fn date_to_timestamp(days: i32) -> i64 {
+    days as i64 * DAY
+}
+
+/// A time interval, with separate month, day and microsecond components,
+/// since months and days have varying lengths. Intervals are compared
+/// component by component, months first.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub micros: i64,
+}
+
+impl Interval {
+    /// Parses an interval as a sequence of quantities and units, e.g. '1 year
+    /// 2 months -3 days', optionally followed by a [-]HH:MM[:SS[.ffffff]]
+    /// time. Seconds can be fractional.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut interval = Self::default();
+        let mut words = s.split_whitespace();
+        let mut empty = true;
+        while let Some(word) = words.next() {
+            empty = false;
+            let (negative, digits) = match word.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, word),
+            };
+            if digits.contains(':') {
+                let micros = parse_time(digits)?;
+                let micros = if negative { -micros } else { micros };
+                interval.micros = interval.micros.checked_add(micros)?;
+                continue;
+            }
+            let unit = words.next()?.to_lowercase();
+            let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+            let mut quantity: i64 = parse_digits(whole)?;
+            if negative {
+                quantity = -quantity;
+            }
+            // Only seconds can have fractions, up to microseconds.
+            let mut fraction_micros = 0;
+            if !fraction.is_empty() || digits.ends_with('.') {
+                if !unit.starts_with("sec") || fraction.len() > 6 {
+                    return None;
+                }
+                fraction_micros = parse_digits::<i64>(fraction)?
+                    * 10_i64.pow(6 - fraction.len() as u32)
+                    * if negative { -1 } else { 1 };
+            }
+            let (months, days, micros) = match unit.trim_end_matches('s') {
+                "year" => (quantity.checked_mul(12)?, 0, 0),
+                "mon" | "month" => (quantity, 0, 0),
+                "week" => (0, quantity.checked_mul(7)?, 0),
+                "day" => (0, quantity, 0),
+                "hour" => (0, 0, quantity.checked_mul(HOUR)?),
+                "min" | "minute" => (0, 0, quantity.checked_mul(MINUTE)?),
+                "sec" | "second" => (0, 0, quantity.checked_mul(SECOND)? + fraction_micros),
+                _ => return None,
+            };
+            interval.months = interval.months.checked_add(months.try_into().ok()?)?;
+            interval.days = interval.days.checked_add(days.try_into().ok()?)?;
+            interval.micros = interval.micros.checked_add(micros)?;
+        }
+        (!empty).then_some(interval)
+    }
+
+    /// Adds two intervals.
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(Self {
+            months: self.months.checked_add(other.months)?,
+            days: self.days.checked_add(other.days)?,
+            micros: self.micros.checked_add(other.micros)?,
+        })
+    }
+
+    /// Negates the interval.
+    pub fn checked_neg(&self) -> Option<Self> {
+        Some(Self {
+            months: self.months.checked_neg()?,
+            days: self.days.checked_neg()?,
+            micros: self.micros.checked_neg()?,
+        })
+    }
+
+    /// Multiplies the interval by an integer.
+    pub fn checked_mul(&self, factor: i64) -> Option<Self> {
+        let factor32: i32 = factor.try_into().ok()?;
+        Some(Self {
+            months: self.months.checked_mul(factor32)?,
+            days: self.days.checked_mul(factor32)?,
+            micros: self.micros.checked_mul(factor)?,
+        })
+    }
+
+    /// Adds the interval to a timestamp. Months are added first, clamping
+    /// the day to the end of the month (e.g. Jan 31 + 1 month is Feb 28),
+    /// then days and microseconds.
+    pub fn add_to(&self, timestamp: i64) -> Result<i64> {
+        let overflow = || crate::error::Error::InvalidInput("timestamp out of range".into());
+        let (days, time) = (timestamp.div_euclid(DAY), timestamp.rem_euclid(DAY));
+        let (year, month, day) = civil_from_days(days);
+        let months = (year * 12 + month as i64 - 1) + self.months as i64;
+        let (year, month) = (months.div_euclid(12), months.rem_euclid(12) as u32 + 1);
+        let day = day.min(days_in_month(year, month));
+        let days = days_from_civil(year, month, day) + self.days as i64;
+        let timestamp = days
+            .checked_mul(DAY)
+            .and_then(|t| t.checked_add(time))
+            .and_then(|t| t.checked_add(self.micros))
+            .ok_or_else(overflow)?;
+        check_timestamp(timestamp).map_err(|_| overflow())
+    }
+
+    /// Returns the interval between two timestamps, as days and
+    /// microseconds.
+    pub fn between(from: i64, to: i64) -> Option<Self> {
+        let micros = to.checked_sub(from)?;
+        Some(Self {
+            months: 0,
+            days: (micros / DAY).try_into().ok()?,
+            micros: micros % DAY,
+        })
+    }
+}
+
+/// Formats the interval like 1 year 2 mons 3 days 04:05:06, omitting zero
+/// components.
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        let (years, months) = (self.months / 12, self.months % 12);
+        let plural = |n: i32| if n.abs() == 1 { "" } else { "s" };
+        if years != 0 {
+            parts.push(format!("{years} year{}", plural(years)));
+        }
+        if months != 0 {
+            parts.push(format!("{months} mon{}", plural(months)));
+        }
+        if self.days != 0 {
+            parts.push(format!("{} day{}", self.days, plural(self.days)));
+        }
+        if self.micros != 0 || parts.is_empty() {
+            let sign = if self.micros < 0 { "-" } else { "" };
+            parts.push(format!(
+                "{sign}{}",
+                format_time(self.micros.unsigned_abs() as i64)
+            ));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
@@ -0,0 +1,296 @@
+//! Fixed-point decimal numbers, stored as an integer mantissa and a scale,
+//! i.e. the number of digits after the decimal point: 12.50 is 1250 with
+//! scale 2. Up to 38 significant digits are supported.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{errinput, error::Result};
+
+/// The maximum precision (significant digits) and scale of a decimal.
+pub const MAX_PRECISION: u8 = 38;
+
+/// The minimum scale of division results.
+const MIN_DIV_SCALE: u8 = 6;
+
+/// A fixed-point decimal number. Decimals with different scales compare
+/// equal if they have the same numeric value, e.g. 1.5 and 1.50.
+///
+/// Serialized as the mantissa followed by the scale, which in KeyCode
+/// preserves ordering between decimals of the same scale. Decimals stored in
+/// a table column are always converted to the column's scale.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u8,
+}
+
+/// Returns 10^exp, which must be at most 10^38.
+fn pow10(exp: u8) -> i128 {
+    10_i128.pow(exp as u32)
+}
+
+impl Decimal {
+    /// Creates a decimal from a mantissa and scale. Errors if it has more
+    /// than 38 digits.
+    pub fn new(mantissa: i128, scale: u8) -> Result<Self> {
+        let decimal = Self { mantissa, scale };
+        if decimal.precision() > MAX_PRECISION || scale > MAX_PRECISION {
+            return errinput!("decimal overflow");
+        }
+        Ok(decimal)
+    }
+
+    /// Returns the scale, i.e. the number of fractional digits.
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    /// Returns the precision, i.e. the number of significant digits
+    /// (ignoring leading zeroes). Zero has precision 1.
+    pub fn precision(&self) -> u8 {
+        let mut digits = 1;
+        let mut mantissa = self.mantissa.unsigned_abs() / 10;
+        while mantissa > 0 {
+            digits += 1;
+            mantissa /= 10;
+        }
+        digits
+    }
+
+    /// Parses a decimal like -12.50, keeping the given number of fractional
+    /// digits as the scale.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() && fraction.is_empty()
+            || !whole
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+        let scale = u8::try_from(fraction.len()).ok()?;
+        let mut mantissa: i128 = 0;
+        for c in whole.chars().chain(fraction.chars()) {
+            mantissa = mantissa
+                .checked_mul(10)?
+                .checked_add(c.to_digit(10)? as i128)?;
+        }
+        if negative {
+            mantissa = -mantissa;
+        }
+        Self::new(mantissa, scale).ok()
+    }
+
+    /// Converts a float to a decimal with the given scale, rounding half away
+    /// from zero. Errors if the float is out of range or not finite.
+    pub fn from_f64(f: f64, scale: u8) -> Result<Self> {
+        let mantissa = (f * pow10(scale) as f64).round();
+        if !mantissa.is_finite() || mantissa.abs() >= pow10(MAX_PRECISION) as f64 {
+            return errinput!("can't convert {f:?} to decimal");
+        }
+        Self::new(mantissa as i128, scale)
+    }
+
+    /// Converts the decimal to a float, possibly losing precision.
+    pub fn to_f64(self) -> f64 {
+        self.mantissa as f64 / pow10(self.scale) as f64
+    }
+
+    /// Converts the decimal to an integer, rounding half away from zero.
+    /// Returns None if out of range.
+    pub fn to_i64(self) -> Option<i64> {
+        self.rescale(0).ok()?.mantissa.try_into().ok()
+    }
+
+    /// Changes the scale of the decimal, rounding half away from zero if the
+    /// scale is reduced.
+    pub fn rescale(&self, scale: u8) -> Result<Self> {
+        if scale >= self.scale {
+            let Some(mantissa) = self.mantissa.checked_mul(pow10(scale - self.scale)) else {
+                return errinput!("decimal overflow");
+            };
+            return Self::new(mantissa, scale);
+        }
+        let divisor = pow10(self.scale - scale);
+        let (quotient, remainder) = (self.mantissa / divisor, self.mantissa % divisor);
+        let mantissa = match remainder.abs() * 2 >= divisor {
+            true => quotient + self.mantissa.signum(),
+            false => quotient,
+        };
+        Self::new(mantissa, scale)
+    }
+
+    /// Converts the decimal to the given precision and scale, rounding if
+    /// necessary. Errors if it doesn't fit in the precision.
+    pub fn fit(&self, precision: u8, scale: u8) -> Result<Self> {
+        let decimal = self.rescale(scale)?;
+        if decimal.precision() > precision && decimal.mantissa != 0 {
+            return errinput!("value {self} out of range for DECIMAL({precision},{scale})");
+        }
+        Ok(decimal)
+    }
+
+    /// Removes trailing zeroes from the fraction.
+    pub fn normalize(&self) -> Self {
+        let mut decimal = *self;
+        while decimal.scale > 0 && decimal.mantissa % 10 == 0 {
+            decimal.mantissa /= 10;
+            decimal.scale -= 1;
+        }
+        decimal
+    }
+
+    /// Returns the scale of the sum or difference of decimals.
+    pub fn add_scale(lhs: u8, rhs: u8) -> u8 {
+        lhs.max(rhs)
+    }
+
+    /// Returns the scale of the product of decimals.
+    pub fn mul_scale(lhs: u8, rhs: u8) -> u8 {
+        lhs.saturating_add(rhs).min(MAX_PRECISION)
+    }
+
+    /// Returns the scale of the quotient of decimals.
+    pub fn div_scale(lhs: u8, rhs: u8) -> u8 {
+        lhs.max(rhs).max(MIN_DIV_SCALE)
+    }
+
+    /// Rescales both decimals to the larger scale.
+    fn align(&self, other: &Self) -> Result<(i128, i128, u8)> {
+        let scale = Self::add_scale(self.scale, other.scale);
+        Ok((
+            self.rescale(scale)?.mantissa,
+            other.rescale(scale)?.mantissa,
+            scale,
+        ))
+    }
+
+    pub fn checked_add(&self, other: &Self) -> Result<Self> {
+        let (lhs, rhs, scale) = self.align(other)?;
+        match lhs.checked_add(rhs) {
+            Some(mantissa) => Self::new(mantissa, scale),
+            None => errinput!("decimal overflow"),
+        }
+    }
+
+    pub fn checked_sub(&self, other: &Self) -> Result<Self> {
+        let (lhs, rhs, scale) = self.align(other)?;
+        match lhs.checked_sub(rhs) {
+            Some(mantissa) => Self::new(mantissa, scale),
+            None => errinput!("decimal overflow"),
+        }
+    }
+
+    pub fn checked_mul(&self, other: &Self) -> Result<Self> {
+        let Some(mantissa) = self.mantissa.checked_mul(other.mantissa) else {
+            return errinput!("decimal overflow");
+        };
+        // The product's scale is the sum of the scales, rounded to the max.
+        let product = Self {
+            mantissa,
+            scale: self.scale + other.scale,
+        };
+        product.rescale(Self::mul_scale(self.scale, other.scale))
+    }
+
+    pub fn checked_div(&self, other: &Self) -> Result<Self> {
+        if other.mantissa == 0 {
+            return errinput!("can't divide by zero");
+        }
+        // Compute the quotient with an extra digit, then round it.
+        let scale = Self::div_scale(self.scale, other.scale);
+        let shift = (scale + 1 + other.scale) as i32 - self.scale as i32;
+        let Some(quotient) = u8::try_from(shift)
+            .ok()
+            .filter(|shift| *shift <= MAX_PRECISION)
+            .and_then(|shift| self.mantissa.checked_mul(pow10(shift)))
+            .map(|dividend| dividend / other.mantissa)
+        else {
+            return errinput!("decimal overflow");
+        };
+        Self {
+            mantissa: quotient,
+            scale: scale + 1,
+        }
+        .rescale(scale)
+    }
+
+    pub fn checked_rem(&self, other: &Self) -> Result<Self> {
+        let (lhs, rhs, scale) = self.align(other)?;
+        if rhs == 0 {
+            return errinput!("can't divide by zero");
+        }
+        Self::new(lhs % rhs, scale)
+    }
+
+    pub fn checked_neg(&self) -> Result<Self> {
+        Self::new(-self.mantissa, self.scale)
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(i: i64) -> Self {
+        Self {
+            mantissa: i as i128,
+            scale: 0,
+        }
+    }
+}
+
+/// Compares the numeric values, comparing the integer parts first such that
+/// the fractions can be aligned without overflowing.
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let (lhs_divisor, rhs_divisor) = (pow10(self.scale), pow10(other.scale));
+        let (lhs_int, rhs_int) = (self.mantissa / lhs_divisor, other.mantissa / rhs_divisor);
+        lhs_int.cmp(&rhs_int).then_with(|| {
+            let scale = self.scale.max(other.scale);
+            let lhs_frac = self.mantissa % lhs_divisor * pow10(scale - self.scale);
+            let rhs_frac = other.mantissa % rhs_divisor * pow10(scale - other.scale);
+            lhs_frac.cmp(&rhs_frac)
+        })
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Decimal {}
+
+/// Hashes the normalized decimal, such that equal decimals hash equally.
+impl std::hash::Hash for Decimal {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        let decimal = self.normalize();
+        decimal.mantissa.hash(state);
+        decimal.scale.hash(state);
+    }
+}
+
+/// Formats the decimal with all digits of its scale, e.g. -12.50.
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{sign}{digits}");
+        }
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{sign}{whole}.{fraction}")
+    }
+}
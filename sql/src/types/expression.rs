@@ -1,12 +1,28 @@
+use itertools::Itertools as _;
 use serde::{Deserialize, Serialize};
 
-use super::value::{Row, Value};
-use crate::{errinput, error::Result, planner::Node, types::value::Label};
+use super::{
+    decimal,
+    function::{self, UserFunction},
+    json,
+    pattern::{Pattern, PatternKind},
+    schema::{ColumnType, DataType},
+    value::{Row, Rows, Value},
+};
+use crate::{
+    errinput,
+    error::Result,
+    planner::{Node, check_node},
+    types::value::Label,
+};
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Expression {
     Constant(Value),
     Column(usize),
+    /// A query parameter placeholder, by zero-based index. Parameters are
+    /// replaced by constants when bound, see Plan::bind().
+    Parameter(usize),
 
     And(Box<Expression>, Box<Expression>),
     Or(Box<Expression>, Box<Expression>),
@@ -34,16 +50,68 @@ pub enum Expression {
     Negate(Box<Expression>),
     /// Exponential an expression：a ^ b
     Exponential(Box<Expression>, Box<Expression>),
+    /// Looks up a JSON object field or array element：a -> b
+    JsonGet(Box<Expression>, Box<Expression>),
+    /// Looks up a JSON object field or array element as a string：a ->> b
+    JsonGetText(Box<Expression>, Box<Expression>),
 
-    SquareRoot(Box<Expression>),
+    /// A scalar function call, by name in the function registry.
+    Function(String, Vec<Expression>),
+    /// A user-defined scalar function call. These can't be serialized.
+    #[serde(skip)]
+    UserFunction(UserFunction, Vec<Expression>),
 
-    Like(Box<Expression>, Box<Expression>),
+    /// A string pattern match: a [I]LIKE b [ESCAPE c] or a ~ b. Constant
+    /// patterns are compiled when the expression is built, see
+    /// Expression::pattern_match(), otherwise for every evaluation.
+    Match(
+        Box<Expression>,
+        Box<Expression>,
+        PatternKind,
+        #[serde(skip)] Option<Pattern>,
+    ),
 
     Is(Box<Expression>, Value),
+
+    /// CASE [operand] WHEN a THEN b ... ELSE c END. With an operand, the
+    /// first WHEN value equal to it is chosen, otherwise the first WHEN
+    /// condition that is true. Only the chosen result is evaluated.
+    Case(
+        Option<Box<Expression>>,
+        Vec<(Expression, Expression)>,
+        Box<Expression>,
+    ),
+    /// Converts a value to the given data type: CAST(a AS type).
+    Cast(Box<Expression>, DataType),
+
+    /// A column of the outer query's current row, referenced from a
+    /// correlated subquery. The label is only used for display.
+    OuterColumn(usize, Label),
+    /// A scalar subquery, returning the value of its single row and column,
+    /// or NULL if it returns no rows.
+    Subquery(Box<Node>),
+    /// EXISTS (subquery): true if the subquery returns any rows.
+    Exists(Box<Node>),
+    /// a IN (subquery): true if the subquery returns a, otherwise NULL if a or
+    /// any returned value is NULL, otherwise false.
+    InSubquery(Box<Expression>, Box<Node>),
 }
 
 impl Expression {
     pub fn evaluate(&self, row: Option<&Row>) -> Result<Value> {
+        self.evaluate_with(row, &|_| {
+            errinput!("subqueries can't be evaluated as constants")
+        })
+    }
+
+    /// Evaluates the expression, executing subqueries via the given function
+    /// when they're reached, e.g. not for untaken CASE branches. The function
+    /// binds any outer column references to the current row.
+    pub fn evaluate_with(
+        &self,
+        row: Option<&Row>,
+        execute: &dyn Fn(&Node) -> Result<Rows>,
+    ) -> Result<Value> {
         use Value::*;
 
         Ok(match self {
@@ -54,66 +122,177 @@ impl Expression {
                     return errinput!("can't reference column {index} with constant evaluation");
                 }
             },
-            Self::Equal(lhs, rhs) => lhs.evaluate(row)?.checked_eq(&rhs.evaluate(row)?)?,
-            Self::GreaterThan(lhs, rhs) => lhs.evaluate(row)?.checked_gt(&rhs.evaluate(row)?)?,
-            Self::LessThan(lhs, rhs) => lhs.evaluate(row)?.checked_lt(&rhs.evaluate(row)?)?,
-            Self::And(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+            Self::Parameter(index) => return errinput!("unbound parameter ${}", index + 1),
+            Self::Equal(lhs, rhs) => lhs
+                .evaluate_with(row, execute)?
+                .checked_eq(&rhs.evaluate_with(row, execute)?)?,
+            Self::GreaterThan(lhs, rhs) => lhs
+                .evaluate_with(row, execute)?
+                .checked_gt(&rhs.evaluate_with(row, execute)?)?,
+            Self::LessThan(lhs, rhs) => lhs
+                .evaluate_with(row, execute)?
+                .checked_lt(&rhs.evaluate_with(row, execute)?)?,
+            Self::And(lhs, rhs) => match (
+                lhs.evaluate_with(row, execute)?,
+                rhs.evaluate_with(row, execute)?,
+            ) {
                 (Boolean(a), Boolean(b)) => Boolean(a && b),
                 (Boolean(b), Null) | (Null, Boolean(b)) if !b => Boolean(false),
                 (Boolean(_), Null) | (Null, Boolean(_)) | (Null, Null) => Null,
                 (lhs, rhs) => return errinput!("can't AND {lhs} and {rhs}"),
             },
-            Self::Or(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+            Self::Or(lhs, rhs) => match (
+                lhs.evaluate_with(row, execute)?,
+                rhs.evaluate_with(row, execute)?,
+            ) {
                 (Boolean(a), Boolean(b)) => Boolean(a || b),
                 (Boolean(b), Null) | (Null, Boolean(b)) if b => Boolean(true),
                 (Boolean(_), Null) | (Null, Boolean(_)) | (Null, Null) => Null,
                 (lhs, rhs) => return errinput!("can't OR {lhs} and {rhs}"),
             },
-            Self::Not(expr) => match expr.evaluate(row)? {
+            Self::Not(expr) => match expr.evaluate_with(row, execute)? {
                 Boolean(b) => Boolean(!b),
                 Null => Null,
                 expr => return errinput!("can't NOT {expr}"),
             },
 
-            Self::Add(lhs, rhs) => lhs.evaluate(row)?.checked_add(&rhs.evaluate(row)?)?,
+            Self::Add(lhs, rhs) => lhs
+                .evaluate_with(row, execute)?
+                .checked_add(&rhs.evaluate_with(row, execute)?)?,
             #[allow(clippy::float_cmp)]
-            Self::Subtract(lhs, rhs) => lhs.evaluate(row)?.checked_sub(&rhs.evaluate(row)?)?,
-            Self::Multiply(lhs, rhs) => lhs.evaluate(row)?.checked_mul(&rhs.evaluate(row)?)?,
-            Self::Divide(lhs, rhs) => lhs.evaluate(row)?.checked_div(&rhs.evaluate(row)?)?,
-            Self::Remainder(lhs, rhs) => lhs.evaluate(row)?.checked_rem(&rhs.evaluate(row)?)?,
-            Self::Identity(expr) => match expr.evaluate(row)? {
-                v @ (Integer(_) | Float(_) | Null) => v,
+            Self::Subtract(lhs, rhs) => lhs
+                .evaluate_with(row, execute)?
+                .checked_sub(&rhs.evaluate_with(row, execute)?)?,
+            Self::Multiply(lhs, rhs) => lhs
+                .evaluate_with(row, execute)?
+                .checked_mul(&rhs.evaluate_with(row, execute)?)?,
+            Self::Divide(lhs, rhs) => lhs
+                .evaluate_with(row, execute)?
+                .checked_div(&rhs.evaluate_with(row, execute)?)?,
+            Self::Remainder(lhs, rhs) => lhs
+                .evaluate_with(row, execute)?
+                .checked_rem(&rhs.evaluate_with(row, execute)?)?,
+            Self::Identity(expr) => match expr.evaluate_with(row, execute)? {
+                v @ (Integer(_) | Float(_) | Decimal(_) | Null) => v,
                 expr => return errinput!("can't take the identity of {expr}"),
             },
-            Self::Negate(expr) => expr.evaluate(row)?.checked_neg()?,
-            Self::Exponential(lhs, rhs) => lhs.evaluate(row)?.checked_pow(&rhs.evaluate(row)?)?,
-            Self::SquareRoot(expr) => match expr.evaluate(row)? {
-                Integer(i) => Float((i as f64).sqrt()),
-                Float(f) => Float(f.sqrt()),
-                _ => return errinput!("can't take square root of {expr:?}"),
-            },
+            Self::Negate(expr) => expr.evaluate_with(row, execute)?.checked_neg()?,
+            Self::Exponential(lhs, rhs) => lhs
+                .evaluate_with(row, execute)?
+                .checked_pow(&rhs.evaluate_with(row, execute)?)?,
+            Self::JsonGet(lhs, rhs) | Self::JsonGetText(lhs, rhs) => {
+                match (
+                    lhs.evaluate_with(row, execute)?,
+                    rhs.evaluate_with(row, execute)?,
+                ) {
+                    (Json(_), Null) | (Null, _) => Null,
+                    (Json(text), key) => {
+                        let value = json::get(&text, &key)?;
+                        match self {
+                            Self::JsonGet(..) => json::to_value(value),
+                            _ => json::to_text(value),
+                        }
+                    }
+                    (lhs, rhs) => return errinput!("can't look up {rhs} in {lhs}"),
+                }
+            }
+            Self::Function(name, args) => {
+                let Some(function) = function::lookup(name) else {
+                    return errinput!("unknown function {name}");
+                };
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| arg.evaluate_with(row, execute))
+                    .try_collect()?;
+                function.evaluate(&args)?
+            }
+            Self::UserFunction(function, args) => {
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| arg.evaluate_with(row, execute))
+                    .try_collect()?;
+                function.evaluate(&args)?
+            }
 
-            Self::Like(lhs, rhg) => match (lhs.evaluate(row)?, rhg.evaluate(row)?) {
-                (String(lhs), String(rhs)) => {
-                    // We could precompile the pattern if it's constant, instead
-                    // of recompiling it for every row, but this is fine.
-                    let pattern = format!(
-                        "^{}$",
-                        regex::escape(&rhs).replace('%', ".*").replace('_', ".")
+            Self::Match(lhs, rhs, kind, Some(pattern)) => match lhs.evaluate_with(row, execute)? {
+                String(lhs) => Boolean(pattern.is_match(&lhs)),
+                Null => Null,
+                lhs => {
+                    return errinput!(
+                        "can't {kind} {lhs} and {}",
+                        rhs.evaluate_with(row, execute)?
                     );
-                    Boolean(regex::Regex::new(&pattern)?.is_match(&lhs))
+                }
+            },
+            Self::Match(lhs, rhs, kind, None) => match (
+                lhs.evaluate_with(row, execute)?,
+                rhs.evaluate_with(row, execute)?,
+            ) {
+                (String(lhs), String(rhs)) => {
+                    Boolean(Pattern::compile(*kind, &rhs)?.is_match(&lhs))
                 }
                 (String(_), Null) | (Null, String(_)) | (Null, Null) => Null,
-                (lhs, rhs) => return errinput!("can't LIKE {lhs} and {rhs}"),
+                (lhs, rhs) => return errinput!("can't {kind} {lhs} and {rhs}"),
             },
 
-            Self::Is(expr, Null) => Boolean(expr.evaluate(row)? == Null),
-            Self::Is(expr, Float(f)) if f.is_nan() => match expr.evaluate(row)? {
+            Self::Is(expr, Null) => Boolean(expr.evaluate_with(row, execute)? == Null),
+            Self::Is(expr, Float(f)) if f.is_nan() => match expr.evaluate_with(row, execute)? {
                 Float(f) => Boolean(f.is_nan()),
                 Null => Null,
                 v => return errinput!("IS NAN can't be used with {}", v.data_type().unwrap()),
             },
             Self::Is(_, v) => panic!("invalid IS value {v}"),
+
+            Self::Case(operand, when, r#else) => {
+                let operand = operand
+                    .as_ref()
+                    .map(|expr| expr.evaluate_with(row, execute))
+                    .transpose()?;
+                for (condition, result) in when {
+                    let value = condition.evaluate_with(row, execute)?;
+                    let matched = match &operand {
+                        Some(operand) => operand.checked_eq(&value)?,
+                        None => value,
+                    };
+                    match matched {
+                        Boolean(true) => return result.evaluate_with(row, execute),
+                        Boolean(false) | Null => {}
+                        value => return errinput!("CASE condition must be boolean, found {value}"),
+                    }
+                }
+                r#else.evaluate_with(row, execute)?
+            }
+            Self::Cast(expr, data_type) => expr.evaluate_with(row, execute)?.cast(data_type)?,
+
+            // Outer columns are bound to constants when executing a subquery.
+            Self::OuterColumn(_, label) => {
+                return errinput!("can't reference outer column {label} here");
+            }
+            Self::Subquery(node) => {
+                let mut rows = execute(node)?;
+                let value = match rows.next().transpose()? {
+                    Some(row) => row.into_iter().next().unwrap_or(Null),
+                    None => Null,
+                };
+                if rows.next().is_some() {
+                    return errinput!("scalar subquery returned more than one row");
+                }
+                value
+            }
+            Self::Exists(node) => Boolean(execute(node)?.next().transpose()?.is_some()),
+            Self::InSubquery(expr, node) => {
+                let value = expr.evaluate_with(row, execute)?;
+                let mut result = Boolean(false);
+                for other in execute(node)? {
+                    let other = other?.into_iter().next().unwrap_or(Null);
+                    match value.checked_eq(&other)? {
+                        Boolean(true) => return Ok(Boolean(true)),
+                        Null => result = Null,
+                        _ => {}
+                    }
+                }
+                result
+            }
         })
     }
 
@@ -122,13 +301,15 @@ impl Expression {
 
         fn precedence(expr: &Expression) -> u8 {
             match expr {
-                Column(_) | Constant(_) | SquareRoot(_) => 11,
+                Column(_) | OuterColumn(..) | Constant(_) | Parameter(_) | Function(..)
+                | UserFunction(..) | Case(..) | Cast(..) | Subquery(_) | Exists(_) => 11,
                 Identity(_) | Negate(_) => 10,
+                JsonGet(..) | JsonGetText(..) => 9,
                 &Exponential(..) => 8,
                 Multiply(..) | Divide(..) | Remainder(..) => 7,
                 Add(..) | Subtract(..) => 6,
                 GreaterThan(..) | LessThan(..) => 5,
-                Equal(..) | Like(..) | Is(..) => 4,
+                Equal(..) | Match(..) | Is(..) | InSubquery(..) => 4,
                 Not(_) => 3,
                 And(..) => 2,
                 Or(..) => 1,
@@ -149,6 +330,14 @@ impl Expression {
                 Label::None => format!("#{index}"),
                 label => format!("{label}"),
             },
+            Parameter(index) => format!("${}", index + 1),
+            OuterColumn(index, label) => match label {
+                Label::None => format!("outer #{index}"),
+                label => format!("{label}"),
+            },
+            Subquery(_) => "(subquery)".to_string(),
+            Exists(_) => "EXISTS (subquery)".to_string(),
+            InSubquery(expr, _) => format!("{} IN (subquery)", format(expr)),
 
             And(lhs, rhs) => format!("{} AND {}", format(lhs), format(rhs)),
             Or(lhs, rhs) => format!("{} OR {}", format(lhs), format(rhs)),
@@ -164,22 +353,496 @@ impl Expression {
             Add(lhs, rhs) => format!("{} + {}", format(lhs), format(rhs)),
             Divide(lhs, rhs) => format!("{} / {}", format(lhs), format(rhs)),
             Exponential(lhs, rhs) => format!("{} ^ {}", format(lhs), format(rhs)),
+            JsonGet(lhs, rhs) => format!("{} -> {}", format(lhs), format(rhs)),
+            JsonGetText(lhs, rhs) => format!("{} ->> {}", format(lhs), format(rhs)),
             Identity(expr) => format(expr),
             Multiply(lhs, rhs) => format!("{} * {}", format(lhs), format(rhs)),
             Negate(expr) => format!("-{}", format(expr)),
             Remainder(lhs, rhs) => format!("{} % {}", format(lhs), format(rhs)),
-            SquareRoot(expr) => format!("sqrt({})", format(expr)),
+            Function(name, args) | UserFunction(function::UserFunction { name, .. }, args) => {
+                format!(
+                    "{name}({})",
+                    args.iter().map(|arg| arg.format(node)).join(", ")
+                )
+            }
             Subtract(lhs, rhs) => format!("{} - {}", format(lhs), format(rhs)),
 
-            Like(lhs, rhs) => format!("{} LIKE {}", format(lhs), format(rhs)),
+            Case(operand, when, r#else) => {
+                let mut string = "CASE".to_string();
+                if let Some(operand) = operand {
+                    string += &format!(" {}", operand.format(node));
+                }
+                for (condition, result) in when {
+                    string += &format!(
+                        " WHEN {} THEN {}",
+                        condition.format(node),
+                        result.format(node)
+                    );
+                }
+                if **r#else != Constant(Value::Null) {
+                    string += &format!(" ELSE {}", r#else.format(node));
+                }
+                string + " END"
+            }
+            Cast(expr, data_type) => format!("CAST({} AS {data_type})", expr.format(node)),
+
+            Match(lhs, rhs, kind, _) => {
+                let mut string = format!("{} {kind} {}", format(lhs), format(rhs));
+                if let PatternKind::Like {
+                    escape: Some(escape),
+                    ..
+                } = kind
+                {
+                    string += &format!(" ESCAPE {}", Value::String(escape.to_string()));
+                }
+                string
+            }
         }
     }
 
+    /// Builds a pattern match, compiling the pattern if it's a constant
+    /// string such that it isn't compiled for every evaluation.
+    pub fn pattern_match(lhs: Expression, rhs: Expression, kind: PatternKind) -> Result<Self> {
+        let pattern = match &rhs {
+            Self::Constant(Value::String(pattern)) => Some(Pattern::compile(kind, pattern)?),
+            _ => None,
+        };
+        Ok(Self::Match(Box::new(lhs), Box::new(rhs), kind, pattern))
+    }
+
+    /// Returns the static type of the expression, given the types of the
+    /// input row's columns. Errors if the expression is ill-typed, e.g. for
+    /// 'a' + 1, such that these are rejected at plan time. Unknown types (e.g.
+    /// NULL) are compatible with any type.
+    pub fn data_type(&self, input: &dyn Fn(usize) -> Result<ColumnType>) -> Result<ColumnType> {
+        use DataType::{Boolean, Float};
+
+        let name = ColumnType::name;
+        let nullable = |types: &[&ColumnType]| types.iter().any(|ty| ty.nullable);
+
+        Ok(match self {
+            Self::Constant(value) => ColumnType {
+                data_type: value.data_type(),
+                nullable: *value == Value::Null,
+            },
+            Self::Column(index) => input(*index)?,
+            // Outer columns are resolved in the outer query's scope.
+            Self::OuterColumn(..) => ColumnType::unknown(),
+            // Parameters are type checked once bound.
+            Self::Parameter(_) => ColumnType::unknown(),
+
+            Self::And(lhs, rhs) | Self::Or(lhs, rhs) => {
+                let (lhs, rhs) = (lhs.data_type(input)?, rhs.data_type(input)?);
+                if !lhs.is(Boolean) || !rhs.is(Boolean) {
+                    let op = if matches!(self, Self::And(..)) {
+                        "AND"
+                    } else {
+                        "OR"
+                    };
+                    return errinput!("can't {op} {} and {}", name(&lhs), name(&rhs));
+                }
+                ColumnType::new(Boolean, nullable(&[&lhs, &rhs]))
+            }
+            Self::Not(expr) => {
+                let ty = expr.data_type(input)?;
+                if !ty.is(Boolean) {
+                    return errinput!("can't NOT {}", name(&ty));
+                }
+                ColumnType::new(Boolean, ty.nullable)
+            }
+
+            Self::Equal(lhs, rhs) | Self::GreaterThan(lhs, rhs) | Self::LessThan(lhs, rhs) => {
+                let (lhs, rhs) = (lhs.data_type(input)?, rhs.data_type(input)?);
+                if !lhs.is_comparable(&rhs) {
+                    return errinput!("can't compare {} and {}", name(&lhs), name(&rhs));
+                }
+                ColumnType::new(Boolean, nullable(&[&lhs, &rhs]))
+            }
+
+            Self::Add(lhs_expr, rhs_expr)
+            | Self::Subtract(lhs_expr, rhs_expr)
+            | Self::Multiply(lhs_expr, rhs_expr)
+            | Self::Divide(lhs_expr, rhs_expr)
+            | Self::Remainder(lhs_expr, rhs_expr)
+            | Self::Exponential(lhs_expr, rhs_expr) => {
+                let (lhs, rhs) = (lhs_expr.data_type(input)?, rhs_expr.data_type(input)?);
+                ColumnType {
+                    data_type: self.arithmetic_type(&lhs, &rhs)?,
+                    nullable: nullable(&[&lhs, &rhs]),
+                }
+            }
+            Self::JsonGet(lhs, rhs) | Self::JsonGetText(lhs, rhs) => {
+                let (lhs, rhs) = (lhs.data_type(input)?, rhs.data_type(input)?);
+                if !lhs.is(DataType::Json)
+                    || !rhs.is(DataType::String) && !rhs.is(DataType::Integer)
+                {
+                    return errinput!("can't look up {} in {}", name(&rhs), name(&lhs));
+                }
+                let data_type = match self {
+                    Self::JsonGet(..) => DataType::Json,
+                    _ => DataType::String,
+                };
+                // Missing fields are NULL.
+                ColumnType::new(data_type, true)
+            }
+            Self::Identity(expr) | Self::Negate(expr) => {
+                let ty = expr.data_type(input)?;
+                let interval = matches!(self, Self::Negate(_)) && ty.is(DataType::Interval);
+                if !ty.is_numeric() && !interval {
+                    return match self {
+                        Self::Identity(_) => errinput!("can't take the identity of {}", name(&ty)),
+                        _ => errinput!("can't negate {}", name(&ty)),
+                    };
+                }
+                ty
+            }
+
+            Self::Function(name, args) => {
+                let Some(function) = function::lookup(name) else {
+                    return errinput!("unknown function {name}");
+                };
+                let types: Vec<_> = args.iter().map(|arg| arg.data_type(input)).try_collect()?;
+                let data_type =
+                    function.check(&types.iter().map(|t| t.data_type.clone()).collect_vec())?;
+                // Non-strict functions may return NULL regardless of arguments.
+                let nullable = !function.strict || types.iter().any(|ty| ty.nullable);
+                ColumnType {
+                    data_type,
+                    nullable,
+                }
+            }
+            Self::UserFunction(function, args) => {
+                for arg in args {
+                    arg.data_type(input)?;
+                }
+                ColumnType::new(function.return_type.clone(), true)
+            }
+
+            Self::Match(lhs, rhs, kind, _) => {
+                let (lhs, rhs) = (lhs.data_type(input)?, rhs.data_type(input)?);
+                if !lhs.is(DataType::String) || !rhs.is(DataType::String) {
+                    return errinput!("can't {kind} {} and {}", name(&lhs), name(&rhs));
+                }
+                ColumnType::new(Boolean, nullable(&[&lhs, &rhs]))
+            }
+            Self::Is(expr, Value::Null) => {
+                expr.data_type(input)?;
+                ColumnType::new(Boolean, false)
+            }
+            Self::Is(expr, _) => {
+                let ty = expr.data_type(input)?;
+                if !ty.is(Float) {
+                    return errinput!("IS NAN can't be used with {}", name(&ty));
+                }
+                ColumnType::new(Boolean, ty.nullable)
+            }
+
+            Self::Case(operand, when, r#else) => {
+                let operand = operand
+                    .as_ref()
+                    .map(|expr| expr.data_type(input))
+                    .transpose()?;
+                let mut results = Vec::with_capacity(when.len() + 1);
+                for (condition, result) in when {
+                    let ty = condition.data_type(input)?;
+                    match &operand {
+                        Some(operand) if !operand.is_comparable(&ty) => {
+                            return errinput!("can't compare {} and {}", name(operand), name(&ty));
+                        }
+                        None if !ty.is(Boolean) => {
+                            return errinput!(
+                                "CASE condition must be boolean, found {}",
+                                name(&ty)
+                            );
+                        }
+                        Some(_) | None => {}
+                    }
+                    results.push(result.data_type(input)?);
+                }
+                results.push(r#else.data_type(input)?);
+                match ColumnType::common(&results) {
+                    Some(ty) => ty,
+                    None => {
+                        let (a, b) = results
+                            .iter()
+                            .filter_map(|ty| ty.data_type.as_ref())
+                            .tuple_combinations()
+                            .find(|(a, b)| a != b && !(a.is_numeric() && b.is_numeric()))
+                            .expect("no incompatible types");
+                        return errinput!("CASE can't mix {a} and {b} results");
+                    }
+                }
+            }
+            Self::Cast(expr, data_type) => {
+                let ty = expr.data_type(input)?;
+                if let (Some(Float), Boolean) = (&ty.data_type, data_type) {
+                    return errinput!("can't cast FLOAT to BOOLEAN");
+                }
+                ColumnType::new(data_type.clone(), ty.nullable)
+            }
+
+            Self::Subquery(node) => ColumnType {
+                nullable: true, // NULL if no rows
+                ..node.column_type(0)?
+            },
+            Self::Exists(_) => ColumnType::new(Boolean, false),
+            Self::InSubquery(expr, node) => {
+                let (lhs, rhs) = (expr.data_type(input)?, node.column_type(0)?);
+                if !lhs.is_comparable(&rhs) {
+                    return errinput!("can't compare {} and {}", name(&lhs), name(&rhs));
+                }
+                ColumnType::new(Boolean, true)
+            }
+        })
+    }
+
+    /// Returns the result type of an arithmetic operator given its operand
+    /// types, or errors if the operator can't be applied to them. This
+    /// mirrors the checked_* operations on Value.
+    fn arithmetic_type(&self, lhs: &ColumnType, rhs: &ColumnType) -> Result<Option<DataType>> {
+        use DataType::*;
+        let (l, r) = (lhs.data_type.as_ref(), rhs.data_type.as_ref());
+
+        // Numbers are floats if either is, otherwise decimals if either is.
+        // Integer exponentiation is only an integer for known non-negative
+        // exponents, otherwise a float, as is decimal exponentiation.
+        if lhs.is_numeric() && rhs.is_numeric() {
+            let pow = match self {
+                Self::Exponential(_, rhs) => !matches!(**rhs, Self::Constant(Value::Integer(0..))),
+                _ => false,
+            };
+            return Ok(match (l, r) {
+                (None, None) => None,
+                (Some(Float), _) | (_, Some(Float)) => Some(Float),
+                (Some(Decimal(..)), _) | (_, Some(Decimal(..))) if pow => Some(Float),
+                (Some(Decimal(..)), _) | (_, Some(Decimal(..))) => {
+                    let scale = |ty: &DataType| match ty {
+                        Decimal(_, scale) => *scale,
+                        _ => 0,
+                    };
+                    let (ls, rs) = (l.or(r).map_or(0, scale), r.or(l).map_or(0, scale));
+                    let scale = match self {
+                        Self::Multiply(..) => decimal::Decimal::mul_scale(ls, rs),
+                        Self::Divide(..) => decimal::Decimal::div_scale(ls, rs),
+                        _ => decimal::Decimal::add_scale(ls, rs),
+                    };
+                    Some(Decimal(decimal::MAX_PRECISION, scale))
+                }
+                _ if pow => Some(Float),
+                _ => Some(Integer),
+            });
+        }
+
+        // Dates, timestamps and intervals.
+        Ok(match (self, l, r) {
+            (_, None, _) | (_, _, None) => None,
+            (Self::Add(..), Some(Date), Some(Integer))
+            | (Self::Add(..), Some(Integer), Some(Date))
+            | (Self::Subtract(..), Some(Date), Some(Integer)) => Some(Date),
+            (Self::Add(..), Some(Date | Timestamp), Some(Interval))
+            | (Self::Add(..), Some(Interval), Some(Date | Timestamp))
+            | (Self::Subtract(..), Some(Date | Timestamp), Some(Interval)) => Some(Timestamp),
+            (Self::Subtract(..), Some(Date), Some(Date)) => Some(Integer),
+            (Self::Subtract(..), Some(Date | Timestamp), Some(Date | Timestamp))
+            | (Self::Add(..) | Self::Subtract(..), Some(Interval), Some(Interval))
+            | (Self::Multiply(..), Some(Interval), Some(Integer))
+            | (Self::Multiply(..), Some(Integer), Some(Interval)) => Some(Interval),
+            _ => {
+                let op = match self {
+                    Self::Add(..) => "add",
+                    Self::Subtract(..) => "subtract",
+                    Self::Multiply(..) => "multiply",
+                    Self::Divide(..) => "divide",
+                    Self::Remainder(..) => "take remainder of",
+                    _ => "exponentiate",
+                };
+                return errinput!("can't {op} {} and {}", lhs.name(), rhs.name());
+            }
+        })
+    }
+
+    /// Type checks the expression, and inserts implicit coercions where
+    /// integers or decimals and floats are combined, such that e.g. the
+    /// results of arithmetic have a single type. Similarly, the results of
+    /// CASE or coalesce() are converted to their common type. Integer
+    /// constants are converted directly, other expressions are cast.
+    /// Comparisons handle mixed types natively, and are left as is to allow
+    /// index lookups.
+    pub fn coerce(self, input: &dyn Fn(usize) -> Result<ColumnType>) -> Result<Self> {
+        use DataType::*;
+        // Coerces an expression to the given type, unless it already is.
+        let convert = |expr: Expression, target: &DataType| -> Result<Expression> {
+            let data_type = expr.data_type(input)?.data_type;
+            Ok(match (expr, target) {
+                (Self::Constant(Value::Integer(i)), Float) => {
+                    Self::Constant(Value::Float(i as f64))
+                }
+                (expr, target) if data_type.is_some_and(|t| !target.accepts_type(&t)) => {
+                    Self::Cast(Box::new(expr), target.clone())
+                }
+                (expr, _) => expr,
+            })
+        };
+        // Coerces an expression to a float if it's an integer or decimal.
+        let float = |expr: Expression| -> Result<Expression> {
+            match expr.data_type(input)?.data_type {
+                Some(Integer | Decimal(..)) => convert(expr, &Float),
+                _ => Ok(expr),
+            }
+        };
+        let is_float = |expr: &Expression| -> Result<bool> {
+            Ok(expr.data_type(input)?.data_type == Some(Float))
+        };
+        // Coerces the operands of a binary operator to floats, if either is.
+        let binary =
+            |lhs: Box<Expression>, rhs: Box<Expression>| -> Result<(Box<Self>, Box<Self>)> {
+                Ok(match is_float(&lhs)? || is_float(&rhs)? {
+                    true => (Box::new(float(*lhs)?), Box::new(float(*rhs)?)),
+                    false => (lhs, rhs),
+                })
+            };
+
+        // Type check subqueries first, as separate nodes.
+        let subquery = |expr| -> Result<Self> {
+            Ok(match expr {
+                Self::Subquery(node) => Self::Subquery(Box::new(check_node(*node)?)),
+                Self::Exists(node) => Self::Exists(Box::new(check_node(*node)?)),
+                Self::InSubquery(expr, node) => {
+                    Self::InSubquery(expr, Box::new(check_node(*node)?))
+                }
+                expr => expr,
+            })
+        };
+
+        self.transform(&subquery, &|expr| {
+            let data_type = expr.data_type(input)?.data_type;
+            Ok(match (expr, data_type) {
+                (Self::Add(lhs, rhs), _) => binary(lhs, rhs).map(|(l, r)| Self::Add(l, r))?,
+                (Self::Subtract(lhs, rhs), _) => {
+                    binary(lhs, rhs).map(|(l, r)| Self::Subtract(l, r))?
+                }
+                (Self::Multiply(lhs, rhs), _) => {
+                    binary(lhs, rhs).map(|(l, r)| Self::Multiply(l, r))?
+                }
+                (Self::Divide(lhs, rhs), _) => binary(lhs, rhs).map(|(l, r)| Self::Divide(l, r))?,
+                (Self::Remainder(lhs, rhs), _) => {
+                    binary(lhs, rhs).map(|(l, r)| Self::Remainder(l, r))?
+                }
+                // Only the base is coerced, the exponent may remain an integer.
+                (Self::Exponential(lhs, rhs), Some(Float)) => {
+                    Self::Exponential(Box::new(float(*lhs)?), rhs)
+                }
+                (Self::Case(operand, when, r#else), Some(target)) => Self::Case(
+                    operand,
+                    when.into_iter()
+                        .map(|(condition, result)| Ok((condition, convert(result, &target)?)))
+                        .collect::<Result<_>>()?,
+                    Box::new(convert(*r#else, &target)?),
+                ),
+                (Self::Function(name, args), Some(target))
+                    if function::lookup(&name).is_some_and(|f| f.compatible) =>
+                {
+                    let args = args
+                        .into_iter()
+                        .map(|arg| convert(arg, &target))
+                        .try_collect()?;
+                    Self::Function(name, args)
+                }
+                (expr, _) => expr,
+            })
+        })
+    }
+
+    /// Splits the expression into its top-level AND clauses, i.e. the clauses
+    /// of a conjunction. Expressions without AND return a single clause.
+    pub fn into_cnf_vec(self) -> Vec<Self> {
+        let mut cnf = Vec::new();
+        let mut stack = vec![self];
+        while let Some(expr) = stack.pop() {
+            match expr {
+                Self::And(lhs, rhs) => {
+                    stack.push(*rhs);
+                    stack.push(*lhs);
+                }
+                expr => cnf.push(expr),
+            }
+        }
+        cnf
+    }
+
+    /// Joins the given expressions with AND, or returns None if empty.
+    pub fn and_vec(exprs: Vec<Self>) -> Option<Self> {
+        let mut exprs = exprs.into_iter();
+        let mut expr = exprs.next()?;
+        for rhs in exprs {
+            expr = Self::And(expr.into(), rhs.into());
+        }
+        Some(expr)
+    }
+
+    /// If the expression is a lookup of the given column, i.e. column = value
+    /// optionally ORed with further such lookups, returns the looked up values.
+    pub fn lookup_values(&self, column: usize) -> Option<Vec<Value>> {
+        match self {
+            Self::Equal(lhs, rhs) => match (lhs.as_ref(), rhs.as_ref()) {
+                (Self::Column(index), Self::Constant(value))
+                | (Self::Constant(value), Self::Column(index))
+                    if *index == column =>
+                {
+                    Some(vec![value.clone()])
+                }
+                _ => None,
+            },
+            Self::Or(lhs, rhs) => {
+                let mut values = lhs.lookup_values(column)?;
+                values.extend(rhs.lookup_values(column)?);
+                Some(values)
+            }
+            _ => None,
+        }
+    }
+
+    /// Remaps column references using the given index mapping, e.g. when
+    /// moving an expression between the two sides of a join.
+    pub fn map_columns(self, map: impl Fn(usize) -> usize) -> Self {
+        self.transform(&Ok, &|expr| match expr {
+            Self::Column(index) => Ok(Self::Column(map(index))),
+            expr => Ok(expr),
+        })
+        .expect("column mapping can't fail")
+    }
+
     pub fn contains(&self, visitor: &impl Fn(&Expression) -> bool) -> bool {
         !self.walk(&mut |expr| !visitor(expr))
     }
 
-    pub fn walk(&self, visitor: &mut impl FnMut(&Expression) -> bool) -> bool {
+    /// Returns true if the expression contains subqueries, which must be
+    /// executed rather than evaluated.
+    pub fn has_subqueries(&self) -> bool {
+        self.contains(&|expr| {
+            matches!(
+                expr,
+                Self::Subquery(_) | Self::Exists(_) | Self::InSubquery(..)
+            )
+        })
+    }
+
+    /// Returns the subquery nodes in the expression, in order.
+    pub fn subqueries(&self) -> Vec<&Node> {
+        let mut nodes = Vec::new();
+        self.walk(&mut |expr| {
+            match expr {
+                Self::Subquery(node) | Self::Exists(node) | Self::InSubquery(_, node) => {
+                    nodes.push(node.as_ref())
+                }
+                _ => {}
+            }
+            true
+        });
+        nodes
+    }
+
+    pub fn walk<'a>(&'a self, visitor: &mut impl FnMut(&'a Expression) -> bool) -> bool {
         if !visitor(self) {
             return false;
         }
@@ -190,8 +853,10 @@ impl Expression {
             | Self::Equal(lhs, rhs)
             | Self::Exponential(lhs, rhs)
             | Self::GreaterThan(lhs, rhs)
+            | Self::JsonGet(lhs, rhs)
+            | Self::JsonGetText(lhs, rhs)
             | Self::LessThan(lhs, rhs)
-            | Self::Like(lhs, rhs)
+            | Self::Match(lhs, rhs, ..)
             | Self::Multiply(lhs, rhs)
             | Self::Or(lhs, rhs)
             | Self::Remainder(lhs, rhs)
@@ -199,9 +864,26 @@ impl Expression {
             Self::Not(expr)
             | Self::Identity(expr)
             | Self::Negate(expr)
-            | Self::SquareRoot(expr)
-            | Self::Is(expr, _) => expr.walk(visitor),
-            Self::Constant(_) | Self::Column(_) => true,
+            | Self::Is(expr, _)
+            | Self::InSubquery(expr, _) => expr.walk(visitor),
+            Self::Function(_, args) | Self::UserFunction(_, args) => {
+                args.iter().all(|arg| arg.walk(visitor))
+            }
+            Self::Case(operand, when, r#else) => {
+                operand.iter().all(|expr| expr.walk(visitor))
+                    && when
+                        .iter()
+                        .all(|(condition, result)| condition.walk(visitor) && result.walk(visitor))
+                    && r#else.walk(visitor)
+            }
+            Self::Cast(expr, _) => expr.walk(visitor),
+            // Subquery nodes aren't walked, only their expressions here.
+            Self::Constant(_)
+            | Self::Column(_)
+            | Self::Parameter(_)
+            | Self::OuterColumn(..)
+            | Self::Subquery(_)
+            | Self::Exists(_) => true,
         }
     }
 
@@ -221,9 +903,13 @@ impl Expression {
             Self::Divide(lhs, rhs) => Self::Divide(xform(lhs)?, xform(rhs)?),
             Self::Equal(lhs, rhs) => Self::Equal(xform(lhs)?, xform(rhs)?),
             Self::Exponential(lhs, rhs) => Self::Exponential(xform(lhs)?, xform(rhs)?),
+            Self::JsonGet(lhs, rhs) => Self::JsonGet(xform(lhs)?, xform(rhs)?),
+            Self::JsonGetText(lhs, rhs) => Self::JsonGetText(xform(lhs)?, xform(rhs)?),
             Self::GreaterThan(lhs, rhs) => Self::GreaterThan(xform(lhs)?, xform(rhs)?),
             Self::LessThan(lhs, rhs) => Self::LessThan(xform(lhs)?, xform(rhs)?),
-            Self::Like(lhs, rhs) => Self::Like(xform(lhs)?, xform(rhs)?),
+            Self::Match(lhs, rhs, kind, pattern) => {
+                Self::Match(xform(lhs)?, xform(rhs)?, kind, pattern)
+            }
             Self::Multiply(lhs, rhs) => Self::Multiply(xform(lhs)?, xform(rhs)?),
             Self::Or(lhs, rhs) => Self::Or(xform(lhs)?, xform(rhs)?),
             Self::Remainder(lhs, rhs) => Self::Remainder(xform(lhs)?, xform(rhs)?),
@@ -231,9 +917,39 @@ impl Expression {
             Self::Not(expr) => Self::Not(xform(expr)?),
             Self::Identity(expr) => Self::Identity(xform(expr)?),
             Self::Negate(expr) => Self::Negate(xform(expr)?),
-            Self::SquareRoot(expr) => Self::SquareRoot(xform(expr)?),
+            Self::Function(name, args) => Self::Function(
+                name,
+                args.into_iter()
+                    .map(|arg| arg.transform(before, after))
+                    .try_collect()?,
+            ),
+            Self::UserFunction(function, args) => Self::UserFunction(
+                function,
+                args.into_iter()
+                    .map(|arg| arg.transform(before, after))
+                    .try_collect()?,
+            ),
             Self::Is(expr, value) => Self::Is(xform(expr)?, value.clone()),
-            Self::Constant(_) | Self::Column(_) => self,
+            Self::Case(operand, when, r#else) => Self::Case(
+                operand.map(xform).transpose()?,
+                when.into_iter()
+                    .map(|(condition, result)| {
+                        Ok((
+                            condition.transform(before, after)?,
+                            result.transform(before, after)?,
+                        ))
+                    })
+                    .collect::<Result<_>>()?,
+                xform(r#else)?,
+            ),
+            Self::Cast(expr, data_type) => Self::Cast(xform(expr)?, data_type),
+            Self::InSubquery(expr, node) => Self::InSubquery(xform(expr)?, node),
+            Self::Constant(_)
+            | Self::Column(_)
+            | Self::Parameter(_)
+            | Self::OuterColumn(..)
+            | Self::Subquery(_)
+            | Self::Exists(_) => self,
         };
         self = after(self)?;
         Ok(self)
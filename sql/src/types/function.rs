@@ -0,0 +1,563 @@
+use std::{collections::HashMap, sync::Arc};
+
+use itertools::Itertools as _;
+
+use super::{json, schema::DataType, value::Value};
+use crate::{errinput, error::Result};
+
+/// The argument types accepted by a function parameter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgType {
+    /// Any type.
+    Any,
+    /// INTEGER or FLOAT.
+    Numeric,
+    Integer,
+    String,
+    /// STRING or BYTES.
+    StringOrBytes,
+    Json,
+}
+
+impl ArgType {
+    /// Returns true if the parameter accepts the given type.
+    fn accepts(&self, data_type: &DataType) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Numeric => matches!(data_type, DataType::Integer | DataType::Float),
+            Self::Integer => *data_type == DataType::Integer,
+            Self::String => *data_type == DataType::String,
+            Self::StringOrBytes => matches!(data_type, DataType::String | DataType::Bytes),
+            Self::Json => *data_type == DataType::Json,
+        }
+    }
+}
+
+impl std::fmt::Display for ArgType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Any => write!(f, "any type"),
+            Self::Numeric => write!(f, "INTEGER or FLOAT"),
+            Self::Integer => write!(f, "INTEGER"),
+            Self::String => write!(f, "STRING"),
+            Self::StringOrBytes => write!(f, "STRING or BYTES"),
+            Self::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+/// A scalar function, called as name(args). Functions are looked up by name in
+/// the FUNCTIONS registry when planning and evaluating expressions.
+pub struct Function {
+    /// The lowercase function name.
+    pub name: &'static str,
+    /// The parameter types. If variadic, the last parameter can be repeated.
+    pub params: &'static [ArgType],
+    /// The number of trailing parameters that are optional.
+    pub optional: usize,
+    /// Whether the last parameter can be repeated any number of times.
+    pub variadic: bool,
+    /// Whether all arguments must have compatible types, e.g. for functions
+    /// that return one of the arguments.
+    pub compatible: bool,
+    /// Whether the function returns NULL when any argument is NULL. If true,
+    /// eval is never called with NULL arguments.
+    pub strict: bool,
+    /// Returns the result type for the given argument types, if known.
+    pub returns: fn(&[Option<DataType>]) -> Option<DataType>,
+    /// Evaluates the function. The arguments have been checked.
+    pub eval: fn(&[Value]) -> Result<Value>,
+}
+
+impl Function {
+    /// Checks the number of arguments and their types, where known (e.g. NULL
+    /// has no type), and returns the result type if known. This is used both
+    /// when planning and when evaluating the function.
+    pub fn check(&self, types: &[Option<DataType>]) -> Result<Option<DataType>> {
+        let (name, max, min) = (
+            self.name,
+            self.params.len(),
+            self.params.len() - self.optional,
+        );
+        if types.len() < min || !self.variadic && types.len() > max {
+            let expected = match (self.variadic, min == max) {
+                (true, _) => format!("at least {min}"),
+                (false, true) => format!("{min}"),
+                (false, false) => format!("{min} to {max}"),
+            };
+            let count = if self.variadic { min } else { max };
+            let plural = if count == 1 { "" } else { "s" };
+            return errinput!(
+                "{name}() takes {expected} argument{plural}, found {}",
+                types.len()
+            );
+        }
+        for (i, data_type) in types.iter().enumerate() {
+            let param = self.params[i.min(max - 1)];
+            if let Some(data_type) = data_type {
+                if !param.accepts(data_type) {
+                    return errinput!(
+                        "{name}() argument {} must be {param}, found {data_type}",
+                        i + 1
+                    );
+                }
+            }
+        }
+        if self.compatible {
+            if let Some((a, b)) = types
+                .iter()
+                .flatten()
+                .tuple_combinations()
+                .find(|(a, b)| common_type(a, b).is_none())
+            {
+                return errinput!("{name}() can't mix {a} and {b} arguments");
+            }
+        }
+        Ok((self.returns)(types))
+    }
+
+    /// Evaluates the function for the given arguments.
+    pub fn evaluate(&self, args: &[Value]) -> Result<Value> {
+        self.check(&args.iter().map(|arg| arg.data_type()).collect_vec())?;
+        if self.strict && args.contains(&Value::Null) {
+            return Ok(Value::Null);
+        }
+        (self.eval)(args)
+    }
+}
+
+/// Returns the common type of two types, if any, see DataType::common().
+fn common_type(a: &DataType, b: &DataType) -> Option<DataType> {
+    a.common(b)
+}
+
+/// Returns the common type of all known argument types, if any.
+fn common_arg_type(types: &[Option<DataType>]) -> Option<DataType> {
+    types
+        .iter()
+        .flatten()
+        .cloned()
+        .reduce(|a, b| common_type(&a, &b).unwrap_or(a))
+}
+
+/// Returns the type of the first argument, if known.
+fn first_arg_type(types: &[Option<DataType>]) -> Option<DataType> {
+    types.first().cloned().flatten()
+}
+
+/// The built-in scalar functions.
+pub static FUNCTIONS: &[Function] = &[
+    Function {
+        name: "abs",
+        params: &[ArgType::Numeric],
+        optional: 0,
+        variadic: false,
+        compatible: false,
+        strict: true,
+        returns: first_arg_type,
+        eval: |args| match &args[0] {
+            Value::Integer(i) => match i.checked_abs() {
+                Some(i) => Ok(Value::Integer(i)),
+                None => errinput!("integer overflow"),
+            },
+            Value::Float(f) => Ok(Value::Float(f.abs())),
+            value => panic!("unexpected value {value}"),
+        },
+    },
+    Function {
+        name: "ceil",
+        params: &[ArgType::Numeric],
+        optional: 0,
+        variadic: false,
+        compatible: false,
+        strict: true,
+        returns: first_arg_type,
+        eval: |args| match &args[0] {
+            Value::Float(f) => Ok(Value::Float(f.ceil())),
+            value => Ok(value.clone()),
+        },
+    },
+    Function {
+        name: "coalesce",
+        params: &[ArgType::Any],
+        optional: 0,
+        variadic: true,
+        compatible: true,
+        strict: false,
+        returns: common_arg_type,
+        eval: |args| {
+            Ok(args
+                .iter()
+                .find(|arg| **arg != Value::Null)
+                .cloned()
+                .unwrap_or(Value::Null))
+        },
+    },
+    Function {
+        name: "concat",
+        params: &[ArgType::Any],
+        optional: 0,
+        variadic: true,
+        compatible: false,
+        strict: false,
+        returns: |_| Some(DataType::String),
+        // NULL arguments are ignored, and other values are formatted.
+        eval: |args| {
+            let mut string = String::new();
+            for arg in args {
+                match arg {
+                    Value::Null => {}
+                    Value::String(s) => string.push_str(s),
+                    value => string.push_str(&value.to_string()),
+                }
+            }
+            Ok(Value::String(string))
+        },
+    },
+    Function {
+        name: "floor",
+        params: &[ArgType::Numeric],
+        optional: 0,
+        variadic: false,
+        compatible: false,
+        strict: true,
+        returns: first_arg_type,
+        eval: |args| match &args[0] {
+            Value::Float(f) => Ok(Value::Float(f.floor())),
+            value => Ok(value.clone()),
+        },
+    },
+    Function {
+        name: "greatest",
+        params: &[ArgType::Any],
+        optional: 0,
+        variadic: true,
+        compatible: true,
+        strict: false,
+        returns: common_arg_type,
+        // NULL arguments are ignored, unless all are NULL.
+        eval: |args| {
+            Ok(args
+                .iter()
+                .filter(|arg| **arg != Value::Null)
+                .max()
+                .cloned()
+                .unwrap_or(Value::Null))
+        },
+    },
+    Function {
+        name: "json_array_length",
+        params: &[ArgType::Json, ArgType::String],
+        optional: 1,
+        variadic: false,
+        compatible: false,
+        strict: false,
+        returns: |_| Some(DataType::Integer),
+        // Returns the length of the array at the optional path, or NULL if the
+        // path doesn't exist. Not strict, since it may return NULL anyway.
+        eval: |args| {
+            let (text, path) = match (&args[0], args.get(1)) {
+                (Value::Null, _) | (_, Some(Value::Null)) => return Ok(Value::Null),
+                (Value::Json(text), Some(Value::String(path))) => (text, path.as_str()),
+                (Value::Json(text), _) => (text, "$"),
+                (value, _) => panic!("unexpected value {value}"),
+            };
+            match json::extract(text, path)? {
+                Some(array) => json::array_length(&array),
+                None => Ok(Value::Null),
+            }
+        },
+    },
+    Function {
+        name: "json_extract",
+        params: &[ArgType::Json, ArgType::String],
+        optional: 0,
+        variadic: false,
+        compatible: false,
+        strict: false,
+        returns: |_| Some(DataType::Json),
+        // Returns the JSON value at the path, e.g. $.a.b[0], or NULL if the
+        // path doesn't exist. Not strict, since it may return NULL anyway.
+        eval: |args| match (&args[0], &args[1]) {
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+            (Value::Json(text), Value::String(path)) => {
+                Ok(json::to_value(json::extract(text, path)?))
+            }
+            (json, path) => panic!("unexpected values {json} and {path}"),
+        },
+    },
+    Function {
+        name: "least",
+        params: &[ArgType::Any],
+        optional: 0,
+        variadic: true,
+        compatible: true,
+        strict: false,
+        returns: common_arg_type,
+        // NULL arguments are ignored, unless all are NULL.
+        eval: |args| {
+            Ok(args
+                .iter()
+                .filter(|arg| **arg != Value::Null)
+                .min()
+                .cloned()
+                .unwrap_or(Value::Null))
+        },
+    },
+    Function {
+        name: "length",
+        params: &[ArgType::StringOrBytes],
+        optional: 0,
+        variadic: false,
+        compatible: false,
+        strict: true,
+        returns: |_| Some(DataType::Integer),
+        eval: |args| match &args[0] {
+            Value::String(s) => Ok(Value::Integer(s.chars().count() as i64)),
+            Value::Bytes(b) => Ok(Value::Integer(b.len() as i64)),
+            value => panic!("unexpected value {value}"),
+        },
+    },
+    Function {
+        name: "lower",
+        params: &[ArgType::String],
+        optional: 0,
+        variadic: false,
+        compatible: false,
+        strict: true,
+        returns: |_| Some(DataType::String),
+        eval: |args| match &args[0] {
+            Value::String(s) => Ok(Value::String(s.to_lowercase())),
+            value => panic!("unexpected value {value}"),
+        },
+    },
+    Function {
+        name: "nullif",
+        params: &[ArgType::Any, ArgType::Any],
+        optional: 0,
+        variadic: false,
+        compatible: true,
+        strict: false,
+        returns: first_arg_type,
+        // Returns NULL if the arguments are equal, otherwise the first one.
+        eval: |args| match args[0].checked_eq(&args[1])? {
+            Value::Boolean(true) => Ok(Value::Null),
+            _ => Ok(args[0].clone()),
+        },
+    },
+    Function {
+        name: "round",
+        params: &[ArgType::Numeric, ArgType::Integer],
+        optional: 1,
+        variadic: false,
+        compatible: false,
+        strict: true,
+        returns: first_arg_type,
+        // Rounds half away from zero to the given number of decimal places,
+        // which may be negative. Defaults to 0.
+        eval: |args| {
+            let places = match args.get(1) {
+                Some(Value::Integer(places)) => (*places).clamp(-308, 308) as i32,
+                _ => 0,
+            };
+            match &args[0] {
+                // Negative places divide by the factor, since 10^places may
+                // not be representable. Scaled values of 2^52 or more are
+                // already integral, and are returned as is to avoid overflow
+                // and precision loss beyond the float's precision.
+                Value::Float(f) => {
+                    let factor = 10_f64.powi(places.abs());
+                    let scaled = if places >= 0 { f * factor } else { f / factor };
+                    if !scaled.is_finite() || scaled.abs() >= 2_f64.powi(52) {
+                        return Ok(Value::Float(*f));
+                    }
+                    Ok(Value::Float(match places {
+                        0.. => scaled.round() / factor,
+                        _ => scaled.round() * factor,
+                    }))
+                }
+                Value::Integer(i) if places < 0 => {
+                    let factor = 10_f64.powi(-places);
+                    let rounded = (*i as f64 / factor).round() * factor;
+                    if rounded.abs() > i64::MAX as f64 {
+                        return errinput!("integer overflow");
+                    }
+                    Ok(Value::Integer(rounded as i64))
+                }
+                value => Ok(value.clone()),
+            }
+        },
+    },
+    Function {
+        name: "sqrt",
+        params: &[ArgType::Numeric],
+        optional: 0,
+        variadic: false,
+        compatible: false,
+        strict: true,
+        returns: |_| Some(DataType::Float),
+        eval: |args| match &args[0] {
+            Value::Integer(i) => Ok(Value::Float((*i as f64).sqrt())),
+            Value::Float(f) => Ok(Value::Float(f.sqrt())),
+            value => panic!("unexpected value {value}"),
+        },
+    },
+    Function {
+        name: "substr",
+        params: &[ArgType::String, ArgType::Integer, ArgType::Integer],
+        optional: 1,
+        variadic: false,
+        compatible: false,
+        strict: true,
+        returns: |_| Some(DataType::String),
+        // Returns the substring from the 1-based start character with the given
+        // length, or to the end of the string. Like in PostgreSQL, a start
+        // before the first character shortens the substring.
+        eval: |args| {
+            let (Value::String(s), Value::Integer(start)) = (&args[0], &args[1]) else {
+                panic!("unexpected values {} and {}", args[0], args[1]);
+            };
+            let end = match args.get(2) {
+                Some(Value::Integer(length)) if *length < 0 => {
+                    return errinput!("negative substring length not allowed");
+                }
+                Some(Value::Integer(length)) => start.saturating_add(*length),
+                _ => i64::MAX,
+            };
+            let (skip, end) = (start.max(&1) - 1, end.max(1) - 1);
+            let take = end.saturating_sub(skip);
+            Ok(Value::String(
+                s.chars().skip(skip as usize).take(take as usize).collect(),
+            ))
+        },
+    },
+    Function {
+        name: "trim",
+        params: &[ArgType::String],
+        optional: 0,
+        variadic: false,
+        compatible: false,
+        strict: true,
+        returns: |_| Some(DataType::String),
+        eval: |args| match &args[0] {
+            Value::String(s) => Ok(Value::String(s.trim().to_string())),
+            value => panic!("unexpected value {value}"),
+        },
+    },
+    Function {
+        name: "upper",
+        params: &[ArgType::String],
+        optional: 0,
+        variadic: false,
+        compatible: false,
+        strict: true,
+        returns: |_| Some(DataType::String),
+        eval: |args| match &args[0] {
+            Value::String(s) => Ok(Value::String(s.to_uppercase())),
+            value => panic!("unexpected value {value}"),
+        },
+    },
+];
+
+/// Looks up a built-in function by lowercase name.
+pub fn lookup(name: &str) -> Option<&'static Function> {
+    FUNCTIONS.iter().find(|function| function.name == name)
+}
+
+/// A user-defined scalar function implementation.
+pub type UserFn = dyn Fn(&[Value]) -> Result<Value> + Send + Sync;
+
+/// A user-defined scalar function, registered from Rust via
+/// FunctionRegistry::register().
+#[derive(Clone)]
+pub struct UserFunction {
+    pub name: String,
+    pub arity: usize,
+    pub return_type: DataType,
+    function: Arc<UserFn>,
+}
+
+impl UserFunction {
+    /// Evaluates the function, checking that it returns the declared type (or
+    /// NULL). Integers are converted to floats for FLOAT functions.
+    pub fn evaluate(&self, args: &[Value]) -> Result<Value> {
+        if args.len() != self.arity {
+            return errinput!(
+                "{}() takes {} arguments, found {}",
+                self.name,
+                self.arity,
+                args.len()
+            );
+        }
+        match ((self.function)(args)?, &self.return_type) {
+            (Value::Integer(i), DataType::Float) => Ok(Value::Float(i as f64)),
+            (value, return_type) => match value.data_type() {
+                Some(data_type) if data_type != *return_type => errinput!(
+                    "{}() returned {data_type}, expected {return_type}",
+                    self.name
+                ),
+                _ => Ok(value),
+            },
+        }
+    }
+}
+
+impl std::fmt::Debug for UserFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UserFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .field("return_type", &self.return_type)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for UserFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Arc::ptr_eq(&self.function, &other.function)
+    }
+}
+
+/// A registry of user-defined scalar functions, callable from SQL like the
+/// built-in functions. It is cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct FunctionRegistry {
+    functions: Arc<HashMap<String, UserFunction>>,
+}
+
+impl FunctionRegistry {
+    /// Registers a function with the given name, number of arguments and
+    /// return type. Names are case-insensitive, and can't shadow built-in or
+    /// aggregate functions. The function must be deterministic, since calls
+    /// with constant arguments are evaluated during planning.
+    pub fn register(
+        &mut self,
+        name: &str,
+        arity: usize,
+        return_type: DataType,
+        function: impl Fn(&[Value]) -> Result<Value> + Send + Sync + 'static,
+    ) -> Result<()> {
+        let name = name.to_lowercase();
+        if lookup(&name).is_some()
+            || matches!(name.as_str(), "avg" | "count" | "max" | "min" | "sum")
+        {
+            return errinput!("can't register built-in function {name}");
+        }
+        if self.functions.contains_key(&name) {
+            return errinput!("function {name} already registered");
+        }
+        let function = UserFunction {
+            name: name.clone(),
+            arity,
+            return_type,
+            function: Arc::new(function),
+        };
+        Arc::make_mut(&mut self.functions).insert(name, function);
+        Ok(())
+    }
+
+    /// Looks up a user-defined function by lowercase name.
+    pub fn get(&self, name: &str) -> Option<&UserFunction> {
+        self.functions.get(name)
+    }
+}
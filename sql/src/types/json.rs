@@ -0,0 +1,121 @@
+//! JSON values, stored as normalized JSON text: parsed and serialized without
+//! insignificant whitespace and with object keys in sorted order, such that
+//! equal documents have equal text. Lookups parse the text on demand.
+
+use serde_json::Value as JsonValue;
+
+use super::value::Value;
+use crate::{errinput, error::Result};
+
+/// Parses and normalizes JSON text.
+pub fn normalize(text: &str) -> Result<String> {
+    Ok(parse(text)?.to_string())
+}
+
+/// Parses JSON text.
+fn parse(text: &str) -> Result<JsonValue> {
+    match serde_json::from_str(text) {
+        Ok(json) => Ok(json),
+        Err(err) => errinput!("invalid JSON: {err}"),
+    }
+}
+
+/// Looks up an object field by string key, or an array element by integer
+/// index, where negative indexes count from the end. Returns None if missing.
+pub fn get(text: &str, key: &Value) -> Result<Option<JsonValue>> {
+    lookup(parse(text)?, key)
+}
+
+/// Looks up a path like $.a.b[0] or $."some key", where $ is the document
+/// root. Returns None if missing.
+pub fn extract(text: &str, path: &str) -> Result<Option<JsonValue>> {
+    let mut json = parse(text)?;
+    for key in parse_path(path)? {
+        match lookup(json, &key)? {
+            Some(next) => json = next,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(json))
+}
+
+/// Looks up an object field or array element, see get().
+fn lookup(json: JsonValue, key: &Value) -> Result<Option<JsonValue>> {
+    Ok(match (json, key) {
+        (JsonValue::Object(mut object), Value::String(key)) => object.remove(key),
+        (JsonValue::Array(mut array), Value::Integer(index)) => {
+            let index = match *index {
+                index if index < 0 => array.len().checked_sub(index.unsigned_abs() as usize),
+                index => Some(index as usize),
+            };
+            index
+                .filter(|i| *i < array.len())
+                .map(|i| array.swap_remove(i))
+        }
+        (_, Value::String(_) | Value::Integer(_)) => None,
+        (_, key) => return errinput!("invalid JSON key {key}"),
+    })
+}
+
+/// Parses a JSON path into field names (strings) and array indexes (integers).
+fn parse_path(path: &str) -> Result<Vec<Value>> {
+    let invalid = || errinput!("invalid JSON path {path}");
+    let Some(mut rest) = path.strip_prefix('$') else {
+        return invalid();
+    };
+    let mut keys = Vec::new();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix(".\"") {
+            let Some((key, tail)) = quoted.split_once('"') else {
+                return invalid();
+            };
+            keys.push(Value::String(key.to_string()));
+            rest = tail;
+        } else if let Some(field) = rest.strip_prefix('.') {
+            let end = field.find(['.', '[']).unwrap_or(field.len());
+            if end == 0 {
+                return invalid();
+            }
+            keys.push(Value::String(field[..end].to_string()));
+            rest = &field[end..];
+        } else if let Some(index) = rest.strip_prefix('[') {
+            let Some((index, tail)) = index.split_once(']') else {
+                return invalid();
+            };
+            let Ok(index) = index.trim().parse() else {
+                return invalid();
+            };
+            keys.push(Value::Integer(index));
+            rest = tail;
+        } else {
+            return invalid();
+        }
+    }
+    Ok(keys)
+}
+
+/// Converts a JSON value into a SQL JSON value, or NULL if missing.
+pub fn to_value(json: Option<JsonValue>) -> Value {
+    match json {
+        Some(json) => Value::Json(json.to_string()),
+        None => Value::Null,
+    }
+}
+
+/// Converts a JSON value into a SQL string, or NULL if missing or JSON null.
+/// Strings are unquoted, other values are formatted as JSON text.
+pub fn to_text(json: Option<JsonValue>) -> Value {
+    match json {
+        None | Some(JsonValue::Null) => Value::Null,
+        Some(JsonValue::String(s)) => Value::String(s),
+        Some(json) => Value::String(json.to_string()),
+    }
+}
+
+/// Returns the length of a JSON array, or errors if it isn't an array.
+pub fn array_length(json: &JsonValue) -> Result<Value> {
+    match json {
+        JsonValue::Array(array) => Ok(Value::Integer(array.len() as i64)),
+        json => errinput!("can't get array length of non-array JSON {json}"),
+    }
+}
@@ -1,3 +1,8 @@
+pub mod datetime;
+pub mod decimal;
 pub mod expression;
+pub mod function;
+pub mod json;
+pub mod pattern;
 pub mod schema;
 pub mod value;
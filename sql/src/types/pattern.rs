@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errinput,
+    error::{Error, Result},
+};
+
+/// The kind of a string pattern match.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PatternKind {
+    /// a [I]LIKE b [ESCAPE c]: matches the whole string, where % matches any
+    /// number of characters and _ matches a single character. The escape
+    /// character, if any, matches the following character literally.
+    Like {
+        escape: Option<char>,
+        case_insensitive: bool,
+    },
+    /// a ~ b: matches a regular expression anywhere in the string.
+    Regexp,
+}
+
+impl std::fmt::Display for PatternKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Like {
+                case_insensitive: false,
+                ..
+            } => "LIKE",
+            Self::Like {
+                case_insensitive: true,
+                ..
+            } => "ILIKE",
+            Self::Regexp => "~",
+        })
+    }
+}
+
+/// A compiled string pattern.
+#[derive(Clone, Debug)]
+pub struct Pattern(regex::Regex);
+
+impl Pattern {
+    /// Compiles a pattern of the given kind.
+    pub fn compile(kind: PatternKind, pattern: &str) -> Result<Self> {
+        let regex = match kind {
+            PatternKind::Like {
+                escape,
+                case_insensitive,
+            } => {
+                // Build an anchored regex, where . also matches newlines.
+                let mut regex = String::from(if case_insensitive { "(?is)^" } else { "(?s)^" });
+                let mut chars = pattern.chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        c if Some(c) == escape => match chars.next() {
+                            Some(c) => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                            None => {
+                                return errinput!("LIKE pattern can't end with escape character");
+                            }
+                        },
+                        '%' => regex.push_str(".*"),
+                        '_' => regex.push('.'),
+                        c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                    }
+                }
+                regex.push('$');
+                regex
+            }
+            PatternKind::Regexp => pattern.to_string(),
+        };
+        let regex = regex::Regex::new(&regex).map_err(|err| {
+            // Syntax errors span several lines, with the reason last.
+            let reason = match &err {
+                regex::Error::Syntax(message) => message.lines().last().unwrap_or_default(),
+                _ => "",
+            };
+            let reason = reason.trim_start_matches("error: ");
+            Error::InvalidInput(format!("invalid regular expression '{pattern}': {reason}"))
+        })?;
+        Ok(Self(regex))
+    }
+
+    /// Returns true if the pattern matches the string.
+    pub fn is_match(&self, string: &str) -> bool {
+        self.0.is_match(string)
+    }
+}
+
+/// Patterns are compiled from the same source, so compare the regexes.
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
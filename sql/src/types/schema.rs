@@ -1,7 +1,18 @@
 use serde::{Deserialize, Serialize};
 
-use super::value::Value;
-use crate::encoding;
+use super::{
+    decimal,
+    expression::Expression,
+    json,
+    value::{Row, Value},
+};
+use crate::{
+    encoding,
+    engine::{Catalog, Transaction},
+    errinput,
+    error::Result,
+    parser::quote_ident,
+};
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Table {
@@ -12,6 +23,125 @@ pub struct Table {
 
 impl encoding::Value for Table {}
 
+impl Table {
+    /// Validates the table schema, in particular that foreign key references
+    /// point to existing tables with a matching primary key type.
+    pub fn validate(&self, catalog: &impl Catalog) -> Result<()> {
+        for column in &self.columns {
+            let Some(target) = &column.references else {
+                continue;
+            };
+            let target_type = match *target == self.name {
+                true => self.columns[self.primary_key].data_type.clone(),
+                false => match catalog.get_table(target)? {
+                    Some(table) => table.columns[table.primary_key].data_type.clone(),
+                    None => {
+                        return errinput!(
+                            "table {target} referenced by column {} does not exist",
+                            column.name
+                        );
+                    }
+                },
+            };
+            if column.data_type != target_type {
+                return errinput!(
+                    "can't reference {target_type} primary key of table {target} from {} column {}",
+                    column.data_type,
+                    column.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates a row for insertion into the table, or for an update of an
+    /// existing row with the same primary key. Values are coerced to the
+    /// column types (see DataType::coerce), so the row may be modified.
+    pub fn validate_row(&self, row: &mut Row, update: bool, txn: &impl Transaction) -> Result<()> {
+        if row.len() != self.columns.len() {
+            return errinput!(
+                "invalid row size {} for table {}, expected {}",
+                row.len(),
+                self.name,
+                self.columns.len()
+            );
+        }
+        for (column, value) in self.columns.iter().zip(row.iter_mut()) {
+            *value = column
+                .data_type
+                .coerce(std::mem::replace(value, Value::Null))?;
+            match value.data_type() {
+                None if column.nullable => {}
+                None => return errinput!("NULL value not allowed for column {}", column.name),
+                Some(data_type) if !column.data_type.accepts(value) => {
+                    return errinput!(
+                        "invalid datatype {data_type} for {} column {}",
+                        column.data_type,
+                        column.name
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+
+        let id = &row[self.primary_key];
+        if !update && !txn.get(&self.name, std::slice::from_ref(id))?.is_empty() {
+            return errinput!("primary key {id} already exists");
+        }
+
+        // Check unique columns, via the secondary index if any. NULLs are
+        // never considered duplicates.
+        for (i, column) in self.columns.iter().enumerate() {
+            if !column.unique || i == self.primary_key || row[i] == Value::Null {
+                continue;
+            }
+            let value = &row[i];
+            let duplicate = if column.index {
+                txn.lookup_index(&self.name, &column.name, std::slice::from_ref(value))?
+                    .into_iter()
+                    .any(|other| other != *id)
+            } else {
+                let filter = Expression::Equal(
+                    Expression::Column(i).into(),
+                    Expression::Constant(value.clone()).into(),
+                );
+                let mut duplicate = false;
+                for other in txn.scan(&self.name, Some(filter))? {
+                    if other?[self.primary_key] != *id {
+                        duplicate = true;
+                        break;
+                    }
+                }
+                duplicate
+            };
+            if duplicate {
+                return errinput!(
+                    "unique value {value} already exists for column {}",
+                    column.name
+                );
+            }
+        }
+
+        // Check foreign key references. NULLs don't reference anything, and
+        // rows may reference themselves.
+        for (i, column) in self.columns.iter().enumerate() {
+            let Some(target) = &column.references else {
+                continue;
+            };
+            let value = &row[i];
+            if *value == Value::Null || (*target == self.name && value == id) {
+                continue;
+            }
+            if txn.get(target, std::slice::from_ref(value))?.is_empty() {
+                return errinput!(
+                    "referenced primary key {value} in table {target} does not exist"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Column {
     pub name: String,
@@ -19,14 +149,25 @@ pub struct Column {
     pub nullable: bool,
     pub default: Option<Value>,
     pub unique: bool,
+    /// Whether the column has a secondary index.
+    pub index: bool,
+    /// A foreign key reference to the primary key of the given table.
+    pub references: Option<String>,
 }
 
-#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
 pub enum DataType {
     Boolean,
     Integer,
     Float,
     String,
+    /// A fixed-point decimal with the given precision and scale.
+    Decimal(u8, u8),
+    Date,
+    Timestamp,
+    Interval,
+    Bytes,
+    Json,
 }
 
 impl std::fmt::Display for DataType {
@@ -36,6 +177,224 @@ impl std::fmt::Display for DataType {
             Self::Integer => write!(f, "INTEGER"),
             Self::Float => write!(f, "FLOAT"),
             Self::String => write!(f, "STRING"),
+            Self::Decimal(precision, scale) => write!(f, "DECIMAL({precision},{scale})"),
+            Self::Date => write!(f, "DATE"),
+            Self::Timestamp => write!(f, "TIMESTAMP"),
+            Self::Interval => write!(f, "INTERVAL"),
+            Self::Bytes => write!(f, "BYTES"),
+            Self::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+impl DataType {
+    /// Returns true for numeric types, i.e. integers, floats and decimals.
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Self::Integer | Self::Float | Self::Decimal(..))
+    }
+
+    /// Returns true for date and time types, i.e. dates, timestamps and
+    /// intervals.
+    pub fn is_temporal(&self) -> bool {
+        matches!(self, Self::Date | Self::Timestamp | Self::Interval)
+    }
+
+    /// Returns true if the types are the same, ignoring decimal precision
+    /// and scale.
+    pub fn is_same_kind(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    /// Returns true if values of the types can be compared: if they're the
+    /// same kind, both numeric, dates and timestamps, or strings and date or
+    /// time types (the strings are parsed, see Value::checked_eq()).
+    pub fn is_comparable(&self, other: &Self) -> bool {
+        self.is_same_kind(other)
+            || self.is_numeric() && other.is_numeric()
+            || matches!(
+                (self, other),
+                (Self::Date, Self::Timestamp) | (Self::Timestamp, Self::Date)
+            )
+            || *self == Self::String && other.is_temporal()
+            || self.is_temporal() && *other == Self::String
+    }
+
+    /// Returns the common type of values of the types, if any. Mixed numbers
+    /// are converted to FLOAT if any is a float, otherwise DECIMAL, and
+    /// dates mix with timestamps as TIMESTAMP.
+    pub fn common(&self, other: &Self) -> Option<Self> {
+        use DataType::*;
+        Some(match (self, other) {
+            (lhs, rhs) if lhs == rhs => lhs.clone(),
+            (Float, rhs) | (rhs, Float) if rhs.is_numeric() => Float,
+            // Keep enough integer digits and the larger scale.
+            (Decimal(lp, ls), Decimal(rp, rs)) => {
+                let scale = *ls.max(rs);
+                let digits = (lp - ls).max(rp - rs);
+                Decimal((digits + scale).min(decimal::MAX_PRECISION), scale)
+            }
+            (Integer, Decimal(_, scale)) | (Decimal(_, scale), Integer) => {
+                Decimal(decimal::MAX_PRECISION, *scale)
+            }
+            (Date, Timestamp) | (Timestamp, Date) => Timestamp,
+            _ => return None,
+        })
+    }
+
+    /// Converts a value for storage in a column of this type, where numbers
+    /// are converted between floats and decimals, integers are converted to
+    /// either, decimals are rounded to the column's scale, and strings are
+    /// parsed as JSON, dates, timestamps or intervals. Other values are
+    /// returned as is, and are validated by the caller.
+    pub fn coerce(&self, value: Value) -> Result<Value> {
+        Ok(match (self, value) {
+            (Self::Float, Value::Integer(i)) => Value::Float(i as f64),
+            (Self::Float, Value::Decimal(d)) => Value::Float(d.to_f64()),
+            (Self::Json, Value::String(s)) => Value::Json(json::normalize(&s)?),
+            (data_type, value @ Value::String(_)) if data_type.is_temporal() => {
+                value.cast(data_type)?
+            }
+            (Self::Decimal(precision, scale), Value::Integer(i)) => {
+                Value::Decimal(decimal::Decimal::from(i).fit(*precision, *scale)?)
+            }
+            (Self::Decimal(precision, scale), Value::Float(f)) => {
+                Value::Decimal(decimal::Decimal::from_f64(f, *scale)?.fit(*precision, *scale)?)
+            }
+            (Self::Decimal(precision, scale), Value::Decimal(d)) => {
+                Value::Decimal(d.fit(*precision, *scale)?)
+            }
+            (_, value) => value,
+        })
+    }
+
+    /// Returns true if values of the other type are also values of this
+    /// type, i.e. if they're the same type or decimals with the same scale.
+    pub fn accepts_type(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Decimal(_, scale), Self::Decimal(_, other_scale)) => scale == other_scale,
+            (data_type, other) => data_type == other,
+        }
+    }
+
+    /// Returns true if the value can be stored in a column of this type,
+    /// after coercion.
+    pub fn accepts(&self, value: &Value) -> bool {
+        value.data_type().is_some_and(|t| self.accepts_type(&t))
+    }
+}
+
+/// The static type of an expression or result column, inferred at plan time.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ColumnType {
+    /// The data type, or None if unknown, e.g. for NULL constants.
+    pub data_type: Option<DataType>,
+    /// Whether the value can be NULL.
+    pub nullable: bool,
+}
+
+impl ColumnType {
+    pub fn new(data_type: DataType, nullable: bool) -> Self {
+        Self {
+            data_type: Some(data_type),
+            nullable,
+        }
+    }
+
+    /// An unknown type, which can be NULL.
+    pub fn unknown() -> Self {
+        Self {
+            data_type: None,
+            nullable: true,
+        }
+    }
+
+    /// Returns the data type name, or NULL if unknown.
+    pub fn name(&self) -> String {
+        self.data_type
+            .as_ref()
+            .map_or("NULL".to_string(), |data_type| data_type.to_string())
+    }
+
+    /// Returns true if the type is unknown or numeric.
+    pub fn is_numeric(&self) -> bool {
+        self.data_type.as_ref().is_none_or(DataType::is_numeric)
+    }
+
+    /// Returns true if the type is unknown or the given data type.
+    pub fn is(&self, data_type: DataType) -> bool {
+        self.data_type.as_ref().is_none_or(|t| *t == data_type)
+    }
+
+    /// Returns true if values of the types can be compared, see
+    /// DataType::is_comparable().
+    pub fn is_comparable(&self, other: &Self) -> bool {
+        match (&self.data_type, &other.data_type) {
+            (Some(lhs), Some(rhs)) => lhs.is_comparable(rhs),
+            (None, _) | (_, None) => true,
+        }
+    }
+
+    /// Returns the common type of values of the given types, if any, see
+    /// DataType::common(). Unknown types are ignored, and the common type can
+    /// be NULL if any of them can.
+    pub fn common(types: &[ColumnType]) -> Option<Self> {
+        let mut common = Self {
+            data_type: None,
+            nullable: false,
+        };
+        for ty in types {
+            common.nullable |= ty.nullable;
+            common.data_type = match (common.data_type, &ty.data_type) {
+                (None, data_type) => data_type.clone(),
+                (Some(data_type), None) => Some(data_type),
+                (Some(lhs), Some(rhs)) => Some(lhs.common(rhs)?),
+            };
+        }
+        Some(common)
+    }
+}
+
+/// Formats the type as its data type and nullability, or NULL if unknown.
+impl std::fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.data_type {
+            Some(data_type) if self.nullable => write!(f, "{data_type} NULL"),
+            Some(data_type) => write!(f, "{data_type} NOT NULL"),
+            None => write!(f, "NULL"),
+        }
+    }
+}
+
+/// Formats the table schema as a CREATE TABLE statement.
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "CREATE TABLE {} (", quote_ident(&self.name))?;
+        for (i, column) in self.columns.iter().enumerate() {
+            write!(f, "  {} {}", quote_ident(&column.name), column.data_type)?;
+            if i == self.primary_key {
+                write!(f, " PRIMARY KEY")?;
+            } else if column.nullable {
+                write!(f, " NULL")?;
+            } else {
+                write!(f, " NOT NULL")?;
+            }
+            if let Some(default) = &column.default {
+                write!(f, " DEFAULT {default}")?;
+            }
+            if i != self.primary_key && column.unique {
+                write!(f, " UNIQUE")?;
+            }
+            if i != self.primary_key && column.index {
+                write!(f, " INDEX")?;
+            }
+            if let Some(target) = &column.references {
+                write!(f, " REFERENCES {}", quote_ident(target))?;
+            }
+            if i < self.columns.len() - 1 {
+                write!(f, ",")?;
+            }
+            writeln!(f)?;
         }
+        write!(f, ")")
     }
 }
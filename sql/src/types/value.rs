@@ -3,7 +3,12 @@ use std::borrow::Cow;
 use dyn_clone::DynClone;
 use serde::{Deserialize, Serialize};
 
-use super::schema::DataType;
+use super::{
+    datetime::{self, Interval},
+    decimal::{self, Decimal},
+    json,
+    schema::DataType,
+};
 use crate::{
     encoding, errdata, errinput,
     error::{Error, Result},
@@ -17,6 +22,15 @@ pub enum Value {
     Integer(i64),
     Float(f64),
     String(String),
+    Decimal(Decimal),
+    /// Days since 1970-01-01.
+    Date(i32),
+    /// Microseconds since 1970-01-01 00:00:00.
+    Timestamp(i64),
+    Interval(Interval),
+    Bytes(#[serde(with = "serde_bytes")] Vec<u8>),
+    /// Normalized JSON text, see the json module.
+    Json(String),
 }
 
 impl encoding::Value for Value {}
@@ -28,12 +42,38 @@ impl std::cmp::PartialEq for Value {
             (Self::Integer(l), Self::Integer(r)) => l == r,
             (Self::Float(l), Self::Float(r)) => l == r || l.is_nan() && r.is_nan(),
             (Self::String(l), Self::String(r)) => l == r,
+            (Self::Decimal(l), Self::Decimal(r)) => l == r,
+            (Self::Date(l), Self::Date(r)) => l == r,
+            (Self::Timestamp(l), Self::Timestamp(r)) => l == r,
+            (Self::Interval(l), Self::Interval(r)) => l == r,
+            (Self::Bytes(l), Self::Bytes(r)) => l == r,
+            (Self::Json(l), Self::Json(r)) => l == r,
             (l, r) => core::mem::discriminant(l) == core::mem::discriminant(r),
         }
     }
 }
 impl std::cmp::Eq for Value {}
 
+impl std::hash::Hash for Value {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        core::mem::discriminant(self).hash(state);
+        // Normalize to treat +/-0.0 and +/-NAN as equal when hashing.
+        match self.normalize_ref().as_ref() {
+            Self::Null => {}
+            Self::Boolean(v) => v.hash(state),
+            Self::Integer(v) => v.hash(state),
+            Self::Float(v) => v.to_bits().hash(state),
+            Self::String(v) => v.hash(state),
+            Self::Decimal(v) => v.hash(state),
+            Self::Date(v) => v.hash(state),
+            Self::Timestamp(v) => v.hash(state),
+            Self::Interval(v) => v.hash(state),
+            Self::Bytes(v) => v.hash(state),
+            Self::Json(v) => v.hash(state),
+        }
+    }
+}
+
 // For ordering purposes, we consider NULL and NaN equal. We establish a total
 // order across all types, even though mixed types will rarely/never come up.
 impl Ord for Value {
@@ -49,6 +89,18 @@ impl Ord for Value {
             (Float(a), Integer(b)) => a.total_cmp(&(*b as f64)),
             (Float(a), Float(b)) => a.total_cmp(b),
             (String(a), String(b)) => a.cmp(b),
+            (Decimal(a), Decimal(b)) => a.cmp(b),
+            (Integer(a), Decimal(b)) => decimal::Decimal::from(*a).cmp(b),
+            (Decimal(a), Integer(b)) => a.cmp(&decimal::Decimal::from(*b)),
+            (Float(a), Decimal(b)) => a.total_cmp(&b.to_f64()),
+            (Decimal(a), Float(b)) => a.to_f64().total_cmp(b),
+            (Date(a), Date(b)) => a.cmp(b),
+            (Timestamp(a), Timestamp(b)) => a.cmp(b),
+            (Date(a), Timestamp(b)) => datetime::date_to_timestamp(*a).cmp(b),
+            (Timestamp(a), Date(b)) => a.cmp(&datetime::date_to_timestamp(*b)),
+            (Interval(a), Interval(b)) => a.cmp(b),
+            (Bytes(a), Bytes(b)) => a.cmp(b),
+            (Json(a), Json(b)) => a.cmp(b),
 
             (Null, _) => Less,
             (_, Null) => Greater,
@@ -58,7 +110,19 @@ impl Ord for Value {
             (_, Float(_)) => Greater,
             (Integer(_), _) => Less,
             (_, Integer(_)) => Greater,
-            // String is ordered last.
+            (Decimal(_), _) => Less,
+            (_, Decimal(_)) => Greater,
+            (Date(_), _) => Less,
+            (_, Date(_)) => Greater,
+            (Timestamp(_), _) => Less,
+            (_, Timestamp(_)) => Greater,
+            (Interval(_), _) => Less,
+            (_, Interval(_)) => Greater,
+            (Bytes(_), _) => Less,
+            (_, Bytes(_)) => Greater,
+            (String(_), _) => Less,
+            (_, String(_)) => Greater,
+            // JSON is ordered last.
         }
     }
 }
@@ -97,6 +161,12 @@ impl Value {
             Self::Integer(_) => Some(DataType::Integer),
             Self::Float(_) => Some(DataType::Float),
             Self::String(_) => Some(DataType::String),
+            Self::Decimal(d) => Some(DataType::Decimal(decimal::MAX_PRECISION, d.scale())),
+            Self::Date(_) => Some(DataType::Date),
+            Self::Timestamp(_) => Some(DataType::Timestamp),
+            Self::Interval(_) => Some(DataType::Interval),
+            Self::Bytes(_) => Some(DataType::Bytes),
+            Self::Json(_) => Some(DataType::Json),
         }
     }
 }
@@ -110,6 +180,16 @@ impl std::fmt::Display for Value {
             Self::Integer(integer) => integer.fmt(f),
             Self::Float(float) => write!(f, "{float:?}"),
             Self::String(string) => write!(f, "'{}'", string.escape_debug()),
+            Self::Decimal(decimal) => decimal.fmt(f),
+            Self::Date(date) => write!(f, "DATE '{}'", datetime::format_date(*date)),
+            Self::Timestamp(ts) => write!(f, "TIMESTAMP '{}'", datetime::format_timestamp(*ts)),
+            Self::Interval(interval) => write!(f, "INTERVAL '{interval}'"),
+            Self::Bytes(bytes) => {
+                write!(f, "X'")?;
+                bytes.iter().try_for_each(|b| write!(f, "{b:02x}"))?;
+                write!(f, "'")
+            }
+            Self::Json(json) => write!(f, "JSON '{json}'"),
         }
     }
 }
@@ -195,6 +275,30 @@ impl<'a> From<&'a Value> for Cow<'a, Value> {
 }
 
 impl Value {
+    /// Returns true if the values have types that are compared via Ord:
+    /// decimals with integers or decimals, dates and timestamps, intervals,
+    /// bytes, or JSON. Other comparisons are handled explicitly.
+    fn is_ordered_with(&self, other: &Self) -> bool {
+        use Value::*;
+        matches!(
+            (self, other),
+            (Decimal(_), Integer(_) | Decimal(_))
+                | (Integer(_), Decimal(_))
+                | (Date(_) | Timestamp(_), Date(_) | Timestamp(_))
+                | (Interval(_), Interval(_))
+                | (Bytes(_), Bytes(_))
+                | (Json(_), Json(_))
+        )
+    }
+
+    /// Adds days to a date, erroring if out of range.
+    fn add_days(date: i32, days: i64) -> Result<i32> {
+        match (date as i64).checked_add(days) {
+            Some(date) => datetime::check_date(date),
+            None => errinput!("date out of range"),
+        }
+    }
+
     pub fn checked_add(&self, other: &Self) -> Result<Self> {
         use Value::*;
         Ok(match (self, other) {
@@ -205,8 +309,28 @@ impl Value {
             (Integer(lhs), Float(rhs)) => Float(*lhs as f64 + rhs),
             (Float(lhs), Integer(rhs)) => Float(lhs + *rhs as f64),
             (Float(lhs), Float(rhs)) => Float(lhs + rhs),
-            (Null, Integer(_) | Float(_) | Null) => Null,
-            (Integer(_) | Float(_), Null) => Null,
+            (Decimal(lhs), Decimal(rhs)) => Decimal(lhs.checked_add(rhs)?),
+            (Decimal(lhs), Integer(rhs)) => Decimal(lhs.checked_add(&(*rhs).into())?),
+            (Integer(lhs), Decimal(rhs)) => Decimal(decimal::Decimal::from(*lhs).checked_add(rhs)?),
+            (Decimal(lhs), Float(rhs)) => Float(lhs.to_f64() + rhs),
+            (Float(lhs), Decimal(rhs)) => Float(lhs + rhs.to_f64()),
+            (Date(date), Integer(days)) | (Integer(days), Date(date)) => {
+                Date(Self::add_days(*date, *days)?)
+            }
+            (Date(date), Interval(interval)) | (Interval(interval), Date(date)) => {
+                Timestamp(interval.add_to(datetime::date_to_timestamp(*date))?)
+            }
+            (Timestamp(ts), Interval(interval)) | (Interval(interval), Timestamp(ts)) => {
+                Timestamp(interval.add_to(*ts)?)
+            }
+            (Interval(lhs), Interval(rhs)) => match lhs.checked_add(rhs) {
+                Some(interval) => Interval(interval),
+                None => return errinput!("interval overflow"),
+            },
+            (Null, Integer(_) | Float(_) | Decimal(_) | Null) => Null,
+            (Integer(_) | Float(_) | Decimal(_), Null) => Null,
+            (Null, Date(_) | Timestamp(_) | Interval(_)) => Null,
+            (Date(_) | Timestamp(_) | Interval(_), Null) => Null,
             (lhs, rhs) => return errinput!("can't add {lhs} and {rhs}"),
         })
     }
@@ -221,8 +345,39 @@ impl Value {
             (Integer(lhs), Float(rhs)) => Float(*lhs as f64 - rhs),
             (Float(lhs), Integer(rhs)) => Float(lhs - *rhs as f64),
             (Float(lhs), Float(rhs)) => Float(lhs - rhs),
-            (Null, Integer(_) | Float(_) | Null) => Null,
-            (Integer(_) | Float(_), Null) => Null,
+            (Decimal(lhs), Decimal(rhs)) => Decimal(lhs.checked_sub(rhs)?),
+            (Decimal(lhs), Integer(rhs)) => Decimal(lhs.checked_sub(&(*rhs).into())?),
+            (Integer(lhs), Decimal(rhs)) => Decimal(decimal::Decimal::from(*lhs).checked_sub(rhs)?),
+            (Decimal(lhs), Float(rhs)) => Float(lhs.to_f64() - rhs),
+            (Float(lhs), Decimal(rhs)) => Float(lhs - rhs.to_f64()),
+            (Date(date), Integer(days)) => match days.checked_neg() {
+                Some(days) => Date(Self::add_days(*date, days)?),
+                None => return errinput!("date out of range"),
+            },
+            (Date(lhs), Date(rhs)) => Integer(*lhs as i64 - *rhs as i64),
+            (Date(_) | Timestamp(_), Interval(interval)) => match interval.checked_neg() {
+                Some(interval) => Interval(interval).checked_add(self)?,
+                None => return errinput!("interval overflow"),
+            },
+            (Date(_) | Timestamp(_), Date(_) | Timestamp(_)) => {
+                let timestamp = |value: &Self| match value {
+                    Date(date) => datetime::date_to_timestamp(*date),
+                    Timestamp(ts) => *ts,
+                    _ => unreachable!(),
+                };
+                match datetime::Interval::between(timestamp(other), timestamp(self)) {
+                    Some(interval) => Interval(interval),
+                    None => return errinput!("interval overflow"),
+                }
+            }
+            (Interval(lhs), Interval(rhs)) => match rhs.checked_neg() {
+                Some(rhs) => Interval(*lhs).checked_add(&Interval(rhs))?,
+                None => return errinput!("interval overflow"),
+            },
+            (Null, Integer(_) | Float(_) | Decimal(_) | Null) => Null,
+            (Integer(_) | Float(_) | Decimal(_), Null) => Null,
+            (Null, Date(_) | Timestamp(_) | Interval(_)) => Null,
+            (Date(_) | Timestamp(_) | Interval(_), Null) => Null,
             (lhs, rhs) => return errinput!("can't subtract {lhs} and {rhs}"),
         })
     }
@@ -237,8 +392,21 @@ impl Value {
             (Integer(lhs), Float(rhs)) => Float(*lhs as f64 * rhs),
             (Float(lhs), Integer(rhs)) => Float(lhs * (*rhs as f64)),
             (Float(lhs), Float(rhs)) => Float(lhs * rhs),
-            (Null, Integer(_) | Float(_) | Null) => Null,
-            (Integer(_) | Float(_), Null) => Null,
+            (Decimal(lhs), Decimal(rhs)) => Decimal(lhs.checked_mul(rhs)?),
+            (Decimal(lhs), Integer(rhs)) => Decimal(lhs.checked_mul(&(*rhs).into())?),
+            (Integer(lhs), Decimal(rhs)) => Decimal(decimal::Decimal::from(*lhs).checked_mul(rhs)?),
+            (Decimal(lhs), Float(rhs)) => Float(lhs.to_f64() * rhs),
+            (Float(lhs), Decimal(rhs)) => Float(lhs * rhs.to_f64()),
+            (Interval(interval), Integer(factor)) | (Integer(factor), Interval(interval)) => {
+                match interval.checked_mul(*factor) {
+                    Some(interval) => Interval(interval),
+                    None => return errinput!("interval overflow"),
+                }
+            }
+            (Null, Integer(_) | Float(_) | Decimal(_) | Null) => Null,
+            (Integer(_) | Float(_) | Decimal(_), Null) => Null,
+            (Null, Date(_) | Timestamp(_) | Interval(_)) => Null,
+            (Date(_) | Timestamp(_) | Interval(_), Null) => Null,
             (lhs, rhs) => return errinput!("can't multiply {lhs} and {rhs}"),
         })
     }
@@ -254,8 +422,15 @@ impl Value {
             (Integer(lhs), Float(rhs)) => Float(*lhs as f64 / rhs),
             (Float(lhs), Integer(rhs)) => Float(lhs / (*rhs as f64)),
             (Float(lhs), Float(rhs)) => Float(lhs / rhs),
-            (Null, Integer(_) | Float(_) | Null) => Null,
-            (Integer(_) | Float(_), Null) => Null,
+            (Decimal(lhs), Decimal(rhs)) => Decimal(lhs.checked_div(rhs)?),
+            (Decimal(lhs), Integer(rhs)) => Decimal(lhs.checked_div(&(*rhs).into())?),
+            (Integer(lhs), Decimal(rhs)) => Decimal(decimal::Decimal::from(*lhs).checked_div(rhs)?),
+            (Decimal(lhs), Float(rhs)) => Float(lhs.to_f64() / rhs),
+            (Float(lhs), Decimal(rhs)) => Float(lhs / rhs.to_f64()),
+            (Null, Integer(_) | Float(_) | Decimal(_) | Null) => Null,
+            (Integer(_) | Float(_) | Decimal(_), Null) => Null,
+            (Null, Date(_) | Timestamp(_) | Interval(_)) => Null,
+            (Date(_) | Timestamp(_) | Interval(_), Null) => Null,
             (lhs, rhs) => return errinput!("can't divide {lhs} and {rhs}"),
         })
     }
@@ -271,8 +446,15 @@ impl Value {
             (Integer(lhs), Float(rhs)) => Float(*lhs as f64 % rhs),
             (Float(lhs), Integer(rhs)) => Float(lhs % (*rhs as f64)),
             (Float(lhs), Float(rhs)) => Float(lhs % rhs),
-            (Null, Integer(_) | Float(_) | Null) => Null,
-            (Integer(_) | Float(_), Null) => Null,
+            (Decimal(lhs), Decimal(rhs)) => Decimal(lhs.checked_rem(rhs)?),
+            (Decimal(lhs), Integer(rhs)) => Decimal(lhs.checked_rem(&(*rhs).into())?),
+            (Integer(lhs), Decimal(rhs)) => Decimal(decimal::Decimal::from(*lhs).checked_rem(rhs)?),
+            (Decimal(lhs), Float(rhs)) => Float(lhs.to_f64() % rhs),
+            (Float(lhs), Decimal(rhs)) => Float(lhs % rhs.to_f64()),
+            (Null, Integer(_) | Float(_) | Decimal(_) | Null) => Null,
+            (Integer(_) | Float(_) | Decimal(_), Null) => Null,
+            (Null, Date(_) | Timestamp(_) | Interval(_)) => Null,
+            (Date(_) | Timestamp(_) | Interval(_), Null) => Null,
             (lhs, rhs) => return errinput!("can't take remainder of {lhs} and {rhs}"),
         })
     }
@@ -282,20 +464,47 @@ impl Value {
         Ok(match self {
             Integer(i) => Integer(-i),
             Float(f) => Float(-f),
+            Decimal(d) => Decimal(d.checked_neg()?),
+            Interval(interval) => match interval.checked_neg() {
+                Some(interval) => Interval(interval),
+                None => return errinput!("interval overflow"),
+            },
             Null => Null,
             _ => return errinput!("can't negate {self}"),
         })
     }
 
+    /// Returns true for dates, timestamps and intervals.
+    fn is_temporal(&self) -> bool {
+        matches!(self, Self::Date(_) | Self::Timestamp(_) | Self::Interval(_))
+    }
+
+    /// Parses a string compared with a date or time value as the other
+    /// value's type, see DataType::is_comparable(). Other values are
+    /// returned as is.
+    fn parse_temporal(&self, other: &Self) -> Result<Self> {
+        match (self, other.data_type()) {
+            (Self::String(_), Some(data_type)) if other.is_temporal() => self.cast(&data_type),
+            (value, _) => Ok(value.clone()),
+        }
+    }
+
     pub fn checked_eq(&self, other: &Self) -> Result<Self> {
         use Value::*;
         Ok(match (self, other) {
+            (String(_), rhs) | (rhs, String(_)) if rhs.is_temporal() => {
+                let (lhs, rhs) = (self.parse_temporal(other)?, other.parse_temporal(self)?);
+                return lhs.checked_eq(&rhs);
+            }
             (Boolean(lhs), Boolean(rhs)) => Boolean(lhs == rhs),
             (Integer(lhs), Integer(rhs)) => Boolean(lhs == rhs),
             (Integer(lhs), Float(rhs)) => Boolean(*lhs as f64 == *rhs),
             (Float(lhs), Integer(rhs)) => Boolean(*lhs == *rhs as f64),
             (Float(lhs), Float(rhs)) => Boolean(lhs == rhs),
             (String(lhs), String(rhs)) => Boolean(lhs == rhs),
+            (Decimal(lhs), Float(rhs)) => Boolean(lhs.to_f64() == *rhs),
+            (Float(lhs), Decimal(rhs)) => Boolean(*lhs == rhs.to_f64()),
+            (lhs, rhs) if lhs.is_ordered_with(rhs) => Boolean(lhs.cmp(rhs).is_eq()),
             (Null, _) | (_, Null) => Null,
             (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
         })
@@ -304,6 +513,10 @@ impl Value {
     pub fn checked_gt(&self, other: &Self) -> Result<Self> {
         use Value::*;
         Ok(match (self, other) {
+            (String(_), rhs) | (rhs, String(_)) if rhs.is_temporal() => {
+                let (lhs, rhs) = (self.parse_temporal(other)?, other.parse_temporal(self)?);
+                return lhs.checked_gt(&rhs);
+            }
             #[allow(clippy::bool_comparison)]
             (Boolean(lhs), Boolean(rhs)) => Boolean(lhs > rhs),
             (Integer(lhs), Integer(rhs)) => Boolean(lhs > rhs),
@@ -311,6 +524,9 @@ impl Value {
             (Float(lhs), Integer(rhs)) => Boolean(*lhs > *rhs as f64),
             (Float(lhs), Float(rhs)) => Boolean(lhs > rhs),
             (String(lhs), String(rhs)) => Boolean(lhs > rhs),
+            (Decimal(lhs), Float(rhs)) => Boolean(lhs.to_f64() > *rhs),
+            (Float(lhs), Decimal(rhs)) => Boolean(*lhs > rhs.to_f64()),
+            (lhs, rhs) if lhs.is_ordered_with(rhs) => Boolean(lhs.cmp(rhs).is_gt()),
             (Null, _) | (_, Null) => Null,
             (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
         })
@@ -319,6 +535,10 @@ impl Value {
     pub fn checked_lt(&self, other: &Self) -> Result<Self> {
         use Value::*;
         Ok(match (self, other) {
+            (String(_), rhs) | (rhs, String(_)) if rhs.is_temporal() => {
+                let (lhs, rhs) = (self.parse_temporal(other)?, other.parse_temporal(self)?);
+                return lhs.checked_lt(&rhs);
+            }
             #[allow(clippy::bool_comparison)]
             (Boolean(lhs), Boolean(rhs)) => Boolean(lhs < rhs),
             (Integer(lhs), Integer(rhs)) => Boolean(lhs < rhs),
@@ -326,6 +546,9 @@ impl Value {
             (Float(lhs), Integer(rhs)) => Boolean(*lhs < *rhs as f64),
             (Float(lhs), Float(rhs)) => Boolean(lhs < rhs),
             (String(lhs), String(rhs)) => Boolean(lhs < rhs),
+            (Decimal(lhs), Float(rhs)) => Boolean(lhs.to_f64() < *rhs),
+            (Float(lhs), Decimal(rhs)) => Boolean(*lhs < rhs.to_f64()),
+            (lhs, rhs) if lhs.is_ordered_with(rhs) => Boolean(lhs.cmp(rhs).is_lt()),
             (Null, _) | (_, Null) => Null,
             (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
         })
@@ -347,11 +570,115 @@ impl Value {
             (Integer(lhs), Float(rhs)) => Float((*lhs as f64).powf(*rhs)),
             (Float(lhs), Integer(rhs)) => Float((lhs).powi(*rhs as i32)),
             (Float(lhs), Float(rhs)) => Float((lhs).powf(*rhs)),
+            // Decimals are exponentiated as floats.
+            (Decimal(lhs), rhs) => Float(lhs.to_f64()).checked_pow(rhs)?,
+            (lhs, Decimal(rhs)) => lhs.checked_pow(&Float(rhs.to_f64()))?,
             (Integer(_) | Float(_), Null) => Null,
             (Null, Integer(_) | Float(_) | Null) => Null,
             (lhs, rhs) => return errinput!("can't Exponential {lhs} and {rhs}"),
         })
     }
+
+    /// Converts the value to the given data type. NULL converts to NULL.
+    pub fn cast(&self, data_type: &DataType) -> Result<Self> {
+        use Value::*;
+        Ok(match (self, data_type) {
+            (Null, _) => Null,
+            (Boolean(_), DataType::Boolean)
+            | (Integer(_), DataType::Integer)
+            | (Float(_), DataType::Float)
+            | (String(_), DataType::String)
+            | (Date(_), DataType::Date)
+            | (Timestamp(_), DataType::Timestamp)
+            | (Interval(_), DataType::Interval)
+            | (Bytes(_), DataType::Bytes)
+            | (Json(_), DataType::Json) => self.clone(),
+
+            (Boolean(b), DataType::Integer) => Integer(*b as i64),
+            (Boolean(b), DataType::Float) => Float(*b as i64 as f64),
+            (Integer(i), DataType::Boolean) => Boolean(*i != 0),
+            (Integer(i), DataType::Float) => Float(*i as f64),
+            // Floats are rounded, and must be in the integer range.
+            (Float(f), DataType::Integer)
+                if f.is_finite() && f.round() >= i64::MIN as f64 && f.round() < i64::MAX as f64 =>
+            {
+                Integer(f.round() as i64)
+            }
+
+            (Boolean(b), DataType::String) => String(if *b { "TRUE" } else { "FALSE" }.into()),
+            (Integer(i), DataType::String) => String(i.to_string()),
+            (Float(f), DataType::String) => String(format!("{f:?}")),
+            (String(s), DataType::Boolean) => match s.trim().to_lowercase().as_str() {
+                "true" | "t" | "yes" | "y" | "1" => Boolean(true),
+                "false" | "f" | "no" | "n" | "0" => Boolean(false),
+                _ => return errinput!("can't cast {self} to {data_type}"),
+            },
+            (String(s), DataType::Integer) => match s.trim().parse() {
+                Ok(i) => Integer(i),
+                Err(_) => return errinput!("can't cast {self} to {data_type}"),
+            },
+            (String(s), DataType::Float) => match s.trim().parse() {
+                Ok(f) => Float(f),
+                Err(_) => return errinput!("can't cast {self} to {data_type}"),
+            },
+
+            // Decimals are rounded to the scale, and must fit the precision.
+            (Decimal(d), DataType::Decimal(precision, scale)) => {
+                Decimal(d.fit(*precision, *scale)?)
+            }
+            (Integer(i), DataType::Decimal(precision, scale)) => {
+                Decimal(decimal::Decimal::from(*i).fit(*precision, *scale)?)
+            }
+            (Float(f), DataType::Decimal(precision, scale)) => {
+                Decimal(decimal::Decimal::from_f64(*f, *scale)?.fit(*precision, *scale)?)
+            }
+            (Decimal(d), DataType::Integer) => match d.to_i64() {
+                Some(i) => Integer(i),
+                None => return errinput!("can't cast {self} to {data_type}"),
+            },
+            (Decimal(d), DataType::Float) => Float(d.to_f64()),
+            (Decimal(d), DataType::String) => String(d.to_string()),
+            (String(s), DataType::Decimal(precision, scale)) => {
+                match decimal::Decimal::parse(s.trim()) {
+                    Some(d) => Decimal(d.fit(*precision, *scale)?),
+                    None => return errinput!("can't cast {self} to {data_type}"),
+                }
+            }
+
+            // Dates are midnight timestamps, and timestamps are truncated to
+            // dates.
+            (Date(date), DataType::Timestamp) => Timestamp(datetime::date_to_timestamp(*date)),
+            (Timestamp(ts), DataType::Date) => Date(ts.div_euclid(datetime::DAY) as i32),
+            (Date(date), DataType::String) => String(datetime::format_date(*date)),
+            (Timestamp(ts), DataType::String) => String(datetime::format_timestamp(*ts)),
+            (Interval(interval), DataType::String) => String(interval.to_string()),
+            (String(s), DataType::Date) => match datetime::parse_date(s.trim()) {
+                Some(date) => Date(date),
+                None => return errinput!("can't cast {self} to {data_type}"),
+            },
+            (String(s), DataType::Timestamp) => match datetime::parse_timestamp(s.trim()) {
+                Some(ts) => Timestamp(ts),
+                None => return errinput!("can't cast {self} to {data_type}"),
+            },
+            (String(s), DataType::Interval) => match datetime::Interval::parse(s) {
+                Some(interval) => Interval(interval),
+                None => return errinput!("can't cast {self} to {data_type}"),
+            },
+
+            // Strings are parsed as JSON, and JSON is formatted as text.
+            (String(s), DataType::Json) => Json(json::normalize(s)?),
+            (Json(json), DataType::String) => String(json.clone()),
+
+            // Strings are converted to and from UTF-8 bytes.
+            (String(s), DataType::Bytes) => Bytes(s.as_bytes().to_vec()),
+            (Bytes(bytes), DataType::String) => match std::str::from_utf8(bytes) {
+                Ok(s) => String(s.to_string()),
+                Err(_) => return errinput!("can't cast {self} to {data_type}"),
+            },
+
+            _ => return errinput!("can't cast {self} to {data_type}"),
+        })
+    }
 }
 
 pub type Row = Vec<Value>;
@@ -34,7 +34,7 @@ mod tests {
             let mut parser = Parser::new(input);
             let ast = parser.parse_expression()?;
             let scope = Scope::new();
-            if let Some(next) = parser.lexer.next().transpose()? {
+            if let Some((next, _)) = parser.lexer.next().transpose()? {
                 return Err(format!("unconsumed token {next}").into());
             }
             let expr = Planner::<Catalog>::build_expression(ast, &scope)?;
@@ -88,7 +88,7 @@ mod tests {
             // Execute the statement.
             let result = session.execute(input)?;
             match result {
-                StatementResult::Select { columns, rows } => {
+                StatementResult::Select { columns, rows, .. } => {
                     if tags.remove("header") {
                         writeln!(output, "{}", columns.into_iter().join(", "))?;
                     }
@@ -96,6 +96,13 @@ mod tests {
                         writeln!(output, "{}", row.into_iter().join(", "))?;
                     }
                 }
+                StatementResult::ShowTables { tables } => {
+                    for table in tables {
+                        writeln!(output, "{table}")?;
+                    }
+                }
+                StatementResult::ShowTable { table } => writeln!(output, "{table}")?,
+                StatementResult::Explain(plan) => writeln!(output, "{plan}")?,
                 result if tags.remove("result") => writeln!(output, "{result:?}")?,
                 _ => {}
             }
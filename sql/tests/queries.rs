@@ -1,11 +1,15 @@
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, error::Error, fmt::Write};
+    use std::{
+        collections::{HashMap, HashSet},
+        error::Error,
+        fmt::Write,
+    };
 
     use itertools::Itertools;
     use sql::{
-        Parser, Planner,
-        engine::{Engine, Local, Session, StatementResult},
+        DataType, Parser, Planner, Value,
+        engine::{Engine, Local, PreparedStatement, Session, StatementResult},
         storage::{self, BitCask},
     };
     use test_each_file::test_each_path;
@@ -15,14 +19,38 @@ mod tests {
     fn test_goldenscript(path: &std::path::Path) {
         let tempdir = tempfile::TempDir::with_prefix("db").expect("tempdir create failed");
         let bitcask = BitCask::new(tempdir.path().join("bitcask")).expect("bitcask init failed");
-        let engine = Local::new(bitcask);
+        let mut engine = Local::new(bitcask);
+        register_functions(&mut engine).expect("function registration failed");
         let mut runner = SQLRunner::new(&engine);
         goldenscript::run(&mut runner, path).expect("goldenscript failed");
     }
 
+    /// Registers user-defined functions for use in test scripts.
+    fn register_functions(engine: &mut TestEngine) -> sql::Result<()> {
+        engine.register_function("add_one", 1, DataType::Integer, |args| match &args[0] {
+            Value::Integer(i) => Ok(Value::Integer(i + 1)),
+            Value::Null => Ok(Value::Null),
+            value => Err(sql::Error::InvalidInput(format!("can't add 1 to {value}"))),
+        })?;
+        engine.register_function("greet", 1, DataType::String, |args| match &args[0] {
+            Value::String(name) => Ok(Value::String(format!("hello {name}"))),
+            value => Ok(Value::String(format!("hello {value}"))),
+        })?;
+        engine.register_function("half", 1, DataType::Float, |args| match &args[0] {
+            Value::Integer(i) => Ok(Value::Integer(i / 2)),
+            value => Ok(value.clone()),
+        })?;
+        engine.register_function("broken", 0, DataType::Integer, |_| {
+            Ok(Value::String("oops".into()))
+        })?;
+        Ok(())
+    }
+
     struct SQLRunner<'a> {
         engine: &'a TestEngine,
         sessions: HashMap<String, Session<'a, TestEngine>>,
+        /// The last prepared statement of each session.
+        prepared: HashMap<String, PreparedStatement>,
     }
 
     type TestEngine = Local<storage::BitCask>;
@@ -32,8 +60,43 @@ mod tests {
             Self {
                 engine,
                 sessions: HashMap::new(),
+                prepared: HashMap::new(),
             }
         }
+
+        fn write_result(
+            output: &mut String,
+            result: StatementResult,
+            tags: &HashSet<String>,
+        ) -> Result<(), Box<dyn Error>> {
+            match result {
+                StatementResult::Select {
+                    columns,
+                    types,
+                    rows,
+                } => {
+                    if tags.contains("header") {
+                        writeln!(output, "{}", columns.into_iter().join(", "))?;
+                    }
+                    if tags.contains("types") {
+                        writeln!(output, "{}", types.into_iter().join(", "))?;
+                    }
+                    for row in rows {
+                        writeln!(output, "{}", row.into_iter().join(", "))?;
+                    }
+                }
+                StatementResult::ShowTables { tables } => {
+                    for table in tables {
+                        writeln!(output, "{table}")?;
+                    }
+                }
+                StatementResult::ShowTable { table } => writeln!(output, "{table}")?,
+                StatementResult::Explain(plan) => writeln!(output, "{plan}")?,
+                result if tags.contains("result") => writeln!(output, "{result:?}")?,
+                _ => {}
+            }
+            Ok(())
+        }
     }
 
     impl<'a> goldenscript::Runner for SQLRunner<'a> {
@@ -42,7 +105,7 @@ mod tests {
             let prefix = command.prefix.clone().unwrap_or_default();
             let session = self
                 .sessions
-                .entry(prefix)
+                .entry(prefix.clone())
                 .or_insert_with(|| self.engine.session());
             if !command.args.is_empty() {
                 return Err("SQL statements should be given as command with no args".into());
@@ -57,19 +120,32 @@ mod tests {
                 }
                 writeln!(output, "{plan}")?;
             }
-            // Execute the statement.
-            let result = session.execute(input)?;
-            match result {
-                StatementResult::Select { columns, rows } => {
-                    if tags.remove("header") {
-                        writeln!(output, "{}", columns.into_iter().join(", "))?;
-                    }
-                    for row in rows {
-                        writeln!(output, "{}", row.into_iter().join(", "))?;
-                    }
-                }
-                result if tags.remove("result") => writeln!(output, "{result:?}")?,
-                _ => {}
+            // Prepare the statement for later execution with parameters.
+            if tags.remove("prepare") {
+                self.prepared.insert(prefix, session.prepare(input)?);
+                return Ok(output);
+            }
+            // Execute the session's prepared statement, with the input as a
+            // comma-separated list of parameter values.
+            if tags.remove("execute") {
+                let Some(statement) = self.prepared.get(&prefix) else {
+                    return Err("no prepared statement".into());
+                };
+                let parameters = match session.execute(&format!("SELECT {input}"))? {
+                    StatementResult::Select { mut rows, .. } => rows.remove(0),
+                    result => return Err(format!("unexpected result {result:?}").into()),
+                };
+                let result = session.execute_prepared(statement, &parameters)?;
+                Self::write_result(&mut output, result, &tags)?;
+                return Ok(output);
+            }
+            // Execute the statement, or a script of statements if requested.
+            let results = match tags.remove("script") {
+                true => session.execute_script(input)?,
+                false => vec![session.execute(input)?],
+            };
+            for result in results {
+                Self::write_result(&mut output, result, &tags)?;
             }
             Ok(output)
         }